@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use tokio::task::spawn_blocking;
+
+use super::{
+    columns::{Column, ColumnName, TypedColumn},
+    iterator::IteratorMode,
+    ledger_column::LedgerColumn,
+};
+use crate::errors::LedgerResult;
+
+/// Async facade of a [LedgerColumn] for async contexts like RPC handlers.
+///
+/// Every call runs the blocking RocksDB operation on tokio's blocking pool,
+/// so the calling worker thread isn't stalled by disk I/O.
+pub struct AsyncLedgerColumn<C>
+where
+    C: Column + ColumnName,
+{
+    column: Arc<LedgerColumn<C>>,
+}
+
+impl<C> Clone for AsyncLedgerColumn<C>
+where
+    C: Column + ColumnName,
+{
+    fn clone(&self) -> Self {
+        Self {
+            column: self.column.clone(),
+        }
+    }
+}
+
+impl<C> AsyncLedgerColumn<C>
+where
+    C: Column + ColumnName + Send + Sync + 'static,
+    C::Index: Send + 'static,
+{
+    pub fn new(column: Arc<LedgerColumn<C>>) -> Self {
+        Self { column }
+    }
+
+    /// The wrapped column, for the synchronous API
+    pub fn inner(&self) -> &Arc<LedgerColumn<C>> {
+        &self.column
+    }
+
+    /// Collects the entries yielded by [LedgerColumn::iter] for
+    /// `iterator_mode`, stopping after `limit` entries if provided
+    pub async fn iter_collect(
+        &self,
+        iterator_mode: IteratorMode<C::Index>,
+        limit: Option<usize>,
+    ) -> LedgerResult<Vec<(C::Index, Box<[u8]>)>> {
+        let column = self.column.clone();
+        spawn_blocking(move || {
+            let iter = column.iter(iterator_mode)?;
+            Ok(iter.take(limit.unwrap_or(usize::MAX)).collect())
+        })
+        .await?
+    }
+}
+
+impl<C> AsyncLedgerColumn<C>
+where
+    C: TypedColumn + ColumnName + Send + Sync + 'static,
+    C::Index: Send + 'static,
+    C::Type: Send + 'static,
+{
+    /// See [LedgerColumn::get]
+    pub async fn get(&self, key: C::Index) -> LedgerResult<Option<C::Type>> {
+        let column = self.column.clone();
+        spawn_blocking(move || column.get(key)).await?
+    }
+
+    /// See [LedgerColumn::multi_get]
+    pub async fn multi_get(
+        &self,
+        keys: Vec<C::Index>,
+    ) -> LedgerResult<Vec<LedgerResult<Option<C::Type>>>> {
+        let column = self.column.clone();
+        Ok(spawn_blocking(move || column.multi_get(keys)).await?)
+    }
+
+    /// See [LedgerColumn::put]
+    pub async fn put(&self, key: C::Index, value: C::Type) -> LedgerResult<()> {
+        let column = self.column.clone();
+        spawn_blocking(move || column.put(key, &value)).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::database::{
+        columns as cf, db::Database, iterator::IteratorDirection,
+        options::LedgerOptions,
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_matches_sync() {
+        let temp_dir = tempdir().unwrap();
+        let db =
+            Database::open(temp_dir.path(), LedgerOptions::default()).unwrap();
+        let column = Arc::new(db.column::<cf::Blocktime>());
+        let async_column = AsyncLedgerColumn::new(column.clone());
+
+        for slot in 0..10 {
+            async_column.put(slot, slot as i64 * 100).await.unwrap();
+        }
+
+        for slot in 0..11 {
+            assert_eq!(
+                async_column.get(slot).await.unwrap(),
+                column.get(slot).unwrap()
+            );
+        }
+
+        let keys = vec![0, 5, 42];
+        let async_values = async_column
+            .multi_get(keys.clone())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let sync_values = column
+            .multi_get(keys)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(async_values, sync_values);
+        assert_eq!(async_values, vec![Some(0), Some(500), None]);
+
+        let mode = || IteratorMode::From(7, IteratorDirection::Reverse);
+        let async_entries =
+            async_column.iter_collect(mode(), Some(3)).await.unwrap();
+        let sync_entries =
+            column.iter(mode()).unwrap().take(3).collect::<Vec<_>>();
+        assert_eq!(async_entries, sync_entries);
+        assert_eq!(
+            async_entries
+                .iter()
+                .map(|(slot, _)| *slot)
+                .collect::<Vec<_>>(),
+            vec![7, 6, 5]
+        );
+    }
+}
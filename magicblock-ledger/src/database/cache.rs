@@ -0,0 +1,213 @@
+use std::{
+    any::Any,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+const MAX_SHARDS: usize = 16;
+/// Number of versions tracked per shard, keys hashing to the same stripe
+/// share one
+const NUM_VERSION_STRIPES: usize = 64;
+
+type CachedValue = Box<dyn Any + Send + Sync>;
+
+// -----------------
+// ColumnCache
+// -----------------
+/// Bounded LRU cache of deserialized column values keyed by their RocksDB
+/// key. The entries are spread over independently locked shards, each of
+/// them evicting its least recently used entry once full, so the capacity
+/// is only approximately LRU across the whole cache.
+///
+/// Values are only cached if their key wasn't written or invalidated since
+/// the caller took its [ColumnCache::version], so a value read or written
+/// concurrently with a newer write can't end up replacing the newer one.
+pub struct ColumnCache {
+    shards: Vec<Mutex<LruShard>>,
+}
+
+impl ColumnCache {
+    /// Creates a cache holding at most `capacity` entries, `capacity` must
+    /// be greater than 0
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "column cache capacity must be positive");
+        let num_shards = capacity.min(MAX_SHARDS);
+        let shards = (0..num_shards)
+            .map(|shard| {
+                // Spread the remainder over the first shards
+                let shard_capacity = capacity / num_shards
+                    + usize::from(shard < capacity % num_shards);
+                Mutex::new(LruShard::new(shard_capacity))
+            })
+            .collect();
+        Self { shards }
+    }
+
+    /// Returns a copy of the value cached under `key` and marks it as the
+    /// most recently used one.
+    pub fn get<T: Clone + 'static>(&self, key: &[u8]) -> Option<T> {
+        self.shard(key).get(key)?.downcast_ref::<T>().cloned()
+    }
+
+    /// Version of the entry of `key`, to be taken before reading the value
+    /// from or writing it to the column, see [ColumnCache::fill] and
+    /// [ColumnCache::fill_written]
+    pub fn version(&self, key: &[u8]) -> u64 {
+        let (shard, stripe) = self.locate(key);
+        shard.versions[stripe]
+    }
+
+    /// Caches `value` read from the column, unless `key` was written or
+    /// invalidated since `version` was taken
+    pub fn fill<T: Send + Sync + 'static>(
+        &self,
+        key: &[u8],
+        version: u64,
+        value: T,
+    ) {
+        let (mut shard, stripe) = self.locate(key);
+        if shard.versions[stripe] == version {
+            shard.insert(key.to_vec(), Box::new(value));
+        }
+    }
+
+    /// Caches `value` written to the column, unless `key` was written or
+    /// invalidated since `version` was taken. The entry is dropped instead
+    /// then, since the other write may have reached the column after this
+    /// one. Either way the version is bumped, so that reads that started
+    /// before the write don't cache what they read.
+    pub fn fill_written<T: Send + Sync + 'static>(
+        &self,
+        key: &[u8],
+        version: u64,
+        value: T,
+    ) {
+        let (mut shard, stripe) = self.locate(key);
+        if shard.versions[stripe] == version {
+            shard.versions[stripe] += 1;
+            shard.insert(key.to_vec(), Box::new(value));
+        } else {
+            shard.remove(key, stripe);
+        }
+    }
+
+    pub fn remove(&self, key: &[u8]) {
+        let (mut shard, stripe) = self.locate(key);
+        shard.remove(key, stripe);
+    }
+
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().expect("column cache poisoned").clear();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().expect("column cache poisoned").len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn shard(&self, key: &[u8]) -> std::sync::MutexGuard<'_, LruShard> {
+        self.locate(key).0
+    }
+
+    /// Locks the shard of `key` and returns it along with the version
+    /// stripe of `key` within it
+    fn locate(
+        &self,
+        key: &[u8],
+    ) -> (std::sync::MutexGuard<'_, LruShard>, usize) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let shard = hash as usize % self.shards.len();
+        let stripe = (hash >> 32) as usize % NUM_VERSION_STRIPES;
+        (
+            self.shards[shard].lock().expect("column cache poisoned"),
+            stripe,
+        )
+    }
+}
+
+impl std::fmt::Debug for ColumnCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ColumnCache")
+            .field("shards", &self.shards.len())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+// -----------------
+// LruShard
+// -----------------
+struct LruShard {
+    capacity: usize,
+    // Incremented on every access, the entry with the lowest tick is the
+    // least recently used one
+    tick: u64,
+    entries: HashMap<Vec<u8>, (u64, CachedValue)>,
+    recency: BTreeMap<u64, Vec<u8>>,
+    // Bumped whenever an entry of the stripe is written or invalidated
+    versions: [u64; NUM_VERSION_STRIPES],
+}
+
+impl LruShard {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::with_capacity(capacity),
+            recency: BTreeMap::new(),
+            versions: [0; NUM_VERSION_STRIPES],
+        }
+    }
+
+    fn get(&mut self, key: &[u8]) -> Option<&CachedValue> {
+        self.tick += 1;
+        let (tick, value) = self.entries.get_mut(key)?;
+        let key = self.recency.remove(tick).expect("tracked entry");
+        self.recency.insert(self.tick, key);
+        *tick = self.tick;
+        Some(value)
+    }
+
+    fn insert(&mut self, key: Vec<u8>, value: CachedValue) {
+        self.tick += 1;
+        if let Some((tick, _)) = self.entries.remove(&key) {
+            self.recency.remove(&tick);
+        } else if self.entries.len() >= self.capacity {
+            if let Some((_, evicted)) = self.recency.pop_first() {
+                self.entries.remove(&evicted);
+            }
+        }
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (self.tick, value));
+    }
+
+    fn remove(&mut self, key: &[u8], stripe: usize) {
+        self.versions[stripe] += 1;
+        if let Some((tick, _)) = self.entries.remove(key) {
+            self.recency.remove(&tick);
+        }
+    }
+
+    fn clear(&mut self) {
+        for version in &mut self.versions {
+            *version += 1;
+        }
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
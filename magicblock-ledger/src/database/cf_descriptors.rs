@@ -1,10 +1,23 @@
-use std::{collections::HashSet, path::Path};
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use log::*;
-use rocksdb::{ColumnFamilyDescriptor, DBCompressionType, Options, DB};
+use rocksdb::{
+    compaction_filter::Decision, ColumnFamilyDescriptor, DBCompressionType,
+    MergeOperands, Options, DB,
+};
 
 use super::{
-    columns::{should_enable_compression, Column, ColumnName},
+    columns::{
+        cf_name, is_slot_keyed, should_enable_compression, split_cf_name,
+        Column, ColumnName, MergeColumn,
+    },
     consts,
     options::{LedgerColumnOptions, LedgerOptions},
     rocksdb_options::should_disable_auto_compactions,
@@ -20,28 +33,25 @@ use crate::database::{columns, options::AccessType};
 /// One case where columns could be unknown is if a RocksDB database is modified with a newer
 /// software version that adds a new column, and then also opened with an older version that
 /// did not have knowledge of that new column.
+///
+/// The columns of the namespaces other than [LedgerOptions::namespace] that were discovered
+/// are opened with their regular options, so that ledgers of those namespaces can share the
+/// database, see [crate::Ledger::open_with_namespace].
 pub fn cf_descriptors(
     path: &Path,
     options: &LedgerOptions,
 ) -> Vec<ColumnFamilyDescriptor> {
-    use columns::*;
-
-    let mut cf_descriptors = vec![
-        new_cf_descriptor::<TransactionStatus>(options),
-        new_cf_descriptor::<AddressSignatures>(options),
-        new_cf_descriptor::<SlotSignatures>(options),
-        new_cf_descriptor::<Blocktime>(options),
-        new_cf_descriptor::<Blockhash>(options),
-        new_cf_descriptor::<Transaction>(options),
-        new_cf_descriptor::<TransactionMemos>(options),
-        new_cf_descriptor::<PerfSamples>(options),
-        new_cf_descriptor::<AccountModDatas>(options),
-    ];
+    let namespace = options.namespace.as_deref();
+    let mut cf_descriptors = namespace_cf_descriptors(
+        namespace,
+        options,
+        options.cleanup_compaction_filter,
+    );
 
     // If the access type is Secondary, we don't need to open all of the
     // columns so we can just return immediately.
     match options.access_type {
-        AccessType::Secondary => {
+        AccessType::Secondary | AccessType::ReadOnly => {
             return cf_descriptors;
         }
         AccessType::Primary | AccessType::PrimaryForMaintenance => {}
@@ -50,7 +60,11 @@ pub fn cf_descriptors(
     // Attempt to detect the column families that are present. It is not a
     // fatal error if we cannot, for example, if the Blockstore is brand
     // new and will be created by the call to Rocks::open().
-    let detected_cfs = match DB::list_cf(&Options::default(), path) {
+    let mut list_options = Options::default();
+    if let Some(env) = &options.env {
+        list_options.set_env(env);
+    }
+    let detected_cfs = match DB::list_cf(&list_options, path) {
         Ok(detected_cfs) => detected_cfs,
         Err(err) => {
             warn!("Unable to detect Rocks columns: {err:?}. This is expected for a new ledger.");
@@ -58,6 +72,22 @@ pub fn cf_descriptors(
         }
     };
 
+    // The cleanup slots of the other namespaces are tracked by their own
+    // ledgers, so their columns can't be cleaned up by compaction filters
+    let other_namespaces: BTreeSet<_> = detected_cfs
+        .iter()
+        .filter_map(|cf_name| split_cf_name(cf_name))
+        .map(|(detected_namespace, _)| detected_namespace)
+        .filter(|detected_namespace| *detected_namespace != namespace)
+        .collect();
+    for other_namespace in other_namespaces {
+        cf_descriptors.extend(namespace_cf_descriptors(
+            other_namespace,
+            options,
+            false,
+        ));
+    }
+
     // The default column is handled automatically, we don't need to create
     // a descriptor for it
     const DEFAULT_COLUMN_NAME: &str = "default";
@@ -85,15 +115,86 @@ pub fn cf_descriptors(
     cf_descriptors
 }
 
-fn new_cf_descriptor<C: 'static + Column + ColumnName>(
+/// Descriptors of all the known columns in `namespace`
+fn namespace_cf_descriptors(
+    namespace: Option<&str>,
     options: &LedgerOptions,
-) -> ColumnFamilyDescriptor {
-    ColumnFamilyDescriptor::new(C::NAME, get_cf_options::<C>(options))
+    cleanup_compaction_filter: bool,
+) -> Vec<ColumnFamilyDescriptor> {
+    namespace_cf_options(namespace, options, cleanup_compaction_filter)
+        .into_iter()
+        .map(|(name, cf_options)| ColumnFamilyDescriptor::new(name, cf_options))
+        .collect()
+}
+
+/// Names and options of the column families of all the known columns in
+/// `namespace`, i.e. to create them in a database that is already open
+pub fn namespace_cf_options(
+    namespace: Option<&str>,
+    options: &LedgerOptions,
+    cleanup_compaction_filter: bool,
+) -> Vec<(String, Options)> {
+    use columns::*;
+
+    let filter = cleanup_compaction_filter;
+    vec![
+        new_cf_options::<TransactionStatus>(namespace, options, filter),
+        new_cf_options::<AddressSignatures>(namespace, options, filter),
+        new_cf_options::<SlotSignatures>(namespace, options, filter),
+        new_cf_options::<Blocktime>(namespace, options, filter),
+        new_cf_options::<Blockhash>(namespace, options, filter),
+        new_cf_options::<Transaction>(namespace, options, filter),
+        new_cf_options::<TransactionMemos>(namespace, options, filter),
+        new_cf_options::<PerfSamples>(namespace, options, filter),
+        new_cf_options::<AccountModDatas>(namespace, options, filter),
+        new_merge_cf_options::<ProgramTransactionCounts>(
+            namespace, options, filter,
+        ),
+    ]
+}
+
+fn new_cf_options<C: 'static + Column + ColumnName>(
+    namespace: Option<&str>,
+    options: &LedgerOptions,
+    cleanup_compaction_filter: bool,
+) -> (String, Options) {
+    (
+        cf_name(namespace, C::NAME).into_owned(),
+        get_cf_options::<C>(options, cleanup_compaction_filter),
+    )
+}
+
+fn new_merge_cf_options<C: 'static + MergeColumn + ColumnName>(
+    namespace: Option<&str>,
+    options: &LedgerOptions,
+    cleanup_compaction_filter: bool,
+) -> (String, Options) {
+    let mut cf_options =
+        get_cf_options::<C>(options, cleanup_compaction_filter);
+    cf_options.set_merge_operator_associative("i64_add", merge_i64_add);
+    (cf_name(namespace, C::NAME).into_owned(), cf_options)
+}
+
+/// Merge operator of [MergeColumn]s, sums the existing value and all the
+/// operands as little endian encoded i64s.
+fn merge_i64_add(
+    _key: &[u8],
+    existing_value: Option<&[u8]>,
+    operands: &MergeOperands,
+) -> Option<Vec<u8>> {
+    let decode = |bytes: &[u8]| bytes.try_into().ok().map(i64::from_le_bytes);
+
+    let mut sum = existing_value.map_or(Some(0), decode)?;
+    for operand in operands {
+        sum = sum.wrapping_add(decode(operand)?);
+    }
+    Some(sum.to_le_bytes().to_vec())
 }
 
 // FROM ledger/src/blockstore_db.rs :2010
 fn get_cf_options<C: 'static + Column + ColumnName>(
     options: &LedgerOptions,
+    cleanup_compaction_filter: bool,
 ) -> Options {
     let mut cf_options = Options::default();
     // 256 * 8 = 2GB. 6 of these columns should take at most 12GB of RAM
@@ -120,9 +221,35 @@ fn get_cf_options<C: 'static + Column + ColumnName>(
 
     process_cf_options_advanced::<C>(&mut cf_options, &options.column_options);
 
+    if cleanup_compaction_filter && is_slot_keyed::<C>() {
+        cf_options.set_compaction_filter(
+            "cleanup_slot_filter",
+            cleanup_slot_filter::<C>(options.cleanup_slot_watermark.clone()),
+        );
+    }
+
     cf_options
 }
 
+/// Compaction filter dropping the entries at or below `watermark`, i.e.
+/// the entries of slots that were already cleaned up.
+/// Entries with malformed keys are kept, since the filter runs inside RocksDB
+/// and mustn't panic.
+fn cleanup_slot_filter<C: 'static + Column>(
+    watermark: Arc<AtomicU64>,
+) -> impl FnMut(u32, &[u8], &[u8]) -> Decision + Send + 'static {
+    move |_level, key, _value| {
+        let watermark = watermark.load(Ordering::Relaxed);
+        // Slot 0 is only cleaned up once the watermark moved past it
+        match C::try_index(key).map(C::slot) {
+            Some(slot) if watermark > 0 && slot <= watermark => {
+                Decision::Remove
+            }
+            _ => Decision::Keep,
+        }
+    }
+}
+
 fn process_cf_options_advanced<C: 'static + Column + ColumnName>(
     cf_options: &mut Options,
     column_options: &LedgerColumnOptions,
@@ -138,4 +265,136 @@ fn process_cf_options_advanced<C: 'static + Column + ColumnName>(
                 .to_rocksdb_compression_type(),
         );
     }
+
+    if let Some(compaction_style) =
+        column_options.compaction_styles.get(C::NAME)
+    {
+        cf_options.set_compaction_style(
+            compaction_style.to_rocksdb_compaction_style(),
+        );
+    }
+
+    if let Some(comparator) = C::comparator() {
+        cf_options
+            .set_comparator(comparator.name, Box::new(comparator.compare));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use byteorder::{BigEndian, ByteOrder};
+    use rocksdb::IteratorMode;
+    use solana_sdk::clock::Slot;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::database::columns::KeyComparator;
+
+    /// Keyed by `(Slot, u32)`, but ordered by the `u32` first
+    struct SecondaryOrdered;
+
+    impl Column for SecondaryOrdered {
+        type Index = (Slot, u32);
+
+        fn key((slot, index): (Slot, u32)) -> Vec<u8> {
+            let mut key = vec![0; 12];
+            BigEndian::write_u64(&mut key[0..8], slot);
+            BigEndian::write_u32(&mut key[8..12], index);
+            key
+        }
+
+        fn index(key: &[u8]) -> (Slot, u32) {
+            (
+                BigEndian::read_u64(&key[0..8]),
+                BigEndian::read_u32(&key[8..12]),
+            )
+        }
+
+        fn as_index(slot: Slot) -> (Slot, u32) {
+            (slot, 0)
+        }
+
+        fn slot((slot, _): (Slot, u32)) -> Slot {
+            slot
+        }
+
+        fn comparator() -> Option<KeyComparator> {
+            Some(KeyComparator {
+                name: "secondary_ordered_v1",
+                compare: compare_secondary_first,
+            })
+        }
+    }
+
+    impl ColumnName for SecondaryOrdered {
+        const NAME: &'static str = "secondary_ordered";
+    }
+
+    fn compare_secondary_first(a: &[u8], b: &[u8]) -> Ordering {
+        let (a_slot, a_index) = SecondaryOrdered::index(a);
+        let (b_slot, b_index) = SecondaryOrdered::index(b);
+        (a_index, a_slot).cmp(&(b_index, b_slot))
+    }
+
+    #[test]
+    fn test_column_comparator() {
+        let temp_dir = tempdir().unwrap();
+        let mut db_options = Options::default();
+        db_options.create_if_missing(true);
+        db_options.create_missing_column_families(true);
+        let (name, cf_options) = new_cf_options::<SecondaryOrdered>(
+            None,
+            &LedgerOptions::default(),
+            false,
+        );
+        let db = DB::open_cf_descriptors(
+            &db_options,
+            temp_dir.path(),
+            vec![ColumnFamilyDescriptor::new(name, cf_options)],
+        )
+        .unwrap();
+        let cf = db.cf_handle(SecondaryOrdered::NAME).unwrap();
+
+        let indexes = [(1, 2), (2, 1), (1, 1), (2, 0)];
+        for index in indexes {
+            db.put_cf(&cf, SecondaryOrdered::key(index), []).unwrap();
+        }
+
+        let iterated = db
+            .iterator_cf(&cf, IteratorMode::Start)
+            .map(|pair| SecondaryOrdered::index(&pair.unwrap().0))
+            .collect::<Vec<_>>();
+        assert_eq!(iterated, vec![(2, 0), (1, 1), (2, 1), (1, 2)]);
+
+        // Raw byte order would start with slot 1
+        let mut byte_ordered = indexes.map(SecondaryOrdered::key);
+        byte_ordered.sort();
+        assert_eq!(SecondaryOrdered::index(&byte_ordered[0]), (1, 1));
+    }
+
+    #[test]
+    fn test_cleanup_slot_filter_malformed_keys() {
+        let watermark = Arc::new(AtomicU64::new(5));
+        let mut filter =
+            cleanup_slot_filter::<columns::SlotSignatures>(watermark.clone());
+
+        let below = columns::SlotSignatures::key((3, 0));
+        let above = columns::SlotSignatures::key((7, 0));
+        assert!(matches!(filter(0, &below, &[]), Decision::Remove));
+        assert!(matches!(filter(0, &above, &[]), Decision::Keep));
+        // Keys neither of the current nor of the deprecated length are kept
+        // instead of falling back to slot 0 or panicking
+        for key in [&[][..], &below[..4], &[0; 13][..]] {
+            assert!(matches!(filter(0, key, &[]), Decision::Keep));
+        }
+
+        let mut filter = cleanup_slot_filter::<columns::Blocktime>(watermark);
+        assert!(matches!(filter(0, &[0; 4], &[]), Decision::Keep));
+        assert!(matches!(
+            filter(0, &columns::Blocktime::key(2), &[]),
+            Decision::Remove
+        ));
+    }
 }
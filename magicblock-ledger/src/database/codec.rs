@@ -0,0 +1,40 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::errors::{CodecError, LedgerError, LedgerResult};
+
+/// Encoding of the values of a [super::columns::TypedColumn]
+pub trait ColumnCodec<T> {
+    fn encode(value: &T) -> LedgerResult<Vec<u8>>;
+    fn decode(bytes: &[u8]) -> Result<T, CodecError>;
+}
+
+/// Encodes values with bincode, the encoding of all the ledger columns
+pub struct Bincode;
+
+impl<T: Serialize + DeserializeOwned> ColumnCodec<T> for Bincode {
+    fn encode(value: &T) -> LedgerResult<Vec<u8>> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<T, CodecError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// Encodes values with borsh, i.e. to match the encoding of on-chain data
+/// or to be read from other languages
+// None of the ledger columns is borsh encoded yet
+#[allow(unused)]
+pub struct Borsh;
+
+impl<T: BorshSerialize + BorshDeserialize> ColumnCodec<T> for Borsh {
+    fn encode(value: &T) -> LedgerResult<Vec<u8>> {
+        borsh::to_vec(value)
+            .map_err(|err| LedgerError::Encode(CodecError::Borsh(err)))
+    }
+
+    fn decode(bytes: &[u8]) -> Result<T, CodecError> {
+        Ok(borsh::from_slice(bytes)?)
+    }
+}
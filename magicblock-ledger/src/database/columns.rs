@@ -1,9 +1,13 @@
+use std::{borrow::Cow, cmp::Ordering};
+
 use byteorder::{BigEndian, ByteOrder};
-use serde::{de::DeserializeOwned, Serialize};
 use solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Signature};
 use solana_storage_proto::convert::generated;
 
-use super::meta;
+use super::{
+    codec::{Bincode, ColumnCodec},
+    meta,
+};
 
 /// Column family for Transaction Status
 const TRANSACTION_STATUS_CF: &str = "transaction_status";
@@ -23,6 +27,8 @@ const TRANSACTION_MEMOS_CF: &str = "transaction_memos";
 const PERF_SAMPLES_CF: &str = "perf_samples";
 /// Column family for AccountModDatas
 const ACCOUNT_MOD_DATAS_CF: &str = "account_mod_datas";
+/// Column family for Program Transaction Counts
+const PROGRAM_TRANSACTION_COUNTS_CF: &str = "program_transaction_counts";
 
 #[derive(Debug)]
 /// The transaction status column
@@ -95,9 +101,16 @@ pub struct PerfSamples;
 /// * value type: [`crate::database::meta::AccountModData`]
 pub struct AccountModDatas;
 
+/// The per program transaction count column
+///
+/// * index type: [`Pubkey`]
+/// * value type: `i64`, updated via merges (see [`MergeColumn`])
+pub struct ProgramTransactionCounts;
+
 // When adding a new column ...
 // - Add struct below and implement `Column` and `ColumnName` traits
 // - Add descriptor in Rocks::cf_descriptors() and name in Rocks::columns()
+// - Add its `LedgerColumn` to `with_column!` in store/api.rs
 // - Account for column in both `run_purge_with_stats()` and
 //   `compact_storage()` in ledger/src/blockstore/blockstore_purge.rs !!
 // - Account for column in `analyze_storage()` in ledger-tool/src/main.rs
@@ -113,9 +126,37 @@ pub fn columns() -> Vec<&'static str> {
         TransactionMemos::NAME,
         PerfSamples::NAME,
         AccountModDatas::NAME,
+        ProgramTransactionCounts::NAME,
     ]
 }
 
+/// Separates the namespace from the column name in the names of namespaced
+/// column families, see [super::options::LedgerOptions::namespace]
+const NAMESPACE_SEPARATOR: char = '/';
+
+/// Name of the column family backing the column `name` in `namespace`
+pub fn cf_name<'a>(namespace: Option<&str>, name: &'a str) -> Cow<'a, str> {
+    match namespace {
+        Some(namespace) => {
+            Cow::Owned(format!("{namespace}{NAMESPACE_SEPARATOR}{name}"))
+        }
+        None => Cow::Borrowed(name),
+    }
+}
+
+/// Inverse of [cf_name], `None` if `cf_name` isn't the name of a known
+/// column in any namespace
+pub fn split_cf_name(cf_name: &str) -> Option<(Option<&str>, &str)> {
+    let known_columns = columns();
+    if known_columns.contains(&cf_name) {
+        return Some((None, cf_name));
+    }
+    let (namespace, name) = cf_name.rsplit_once(NAMESPACE_SEPARATOR)?;
+    known_columns
+        .contains(&name)
+        .then_some((Some(namespace), name))
+}
+
 // -----------------
 // Traits
 // -----------------
@@ -124,11 +165,39 @@ pub trait Column {
 
     fn key(index: Self::Index) -> Vec<u8>;
     fn index(key: &[u8]) -> Self::Index;
+
+    /// Like [Column::index], but returns `None` instead of panicking or
+    /// falling back to a default index when `key` is malformed.
+    /// Defaults to accepting the keys as long as [Column::key] builds
+    fn try_index(key: &[u8]) -> Option<Self::Index> {
+        (key.len() == Self::key(Self::as_index(0)).len())
+            .then(|| Self::index(key))
+    }
+
     // This trait method is primarily used by `Database::delete_range_cf()`, and is therefore only
     // relevant for columns keyed by Slot: ie. SlotColumns and columns that feature a Slot as the
     // first item in the key.
     fn as_index(slot: Slot) -> Self::Index;
     fn slot(index: Self::Index) -> Slot;
+
+    /// Comparator ordering the keys of the column, registered when the
+    /// column is opened. It defines the semantics of seeks, iteration and
+    /// range deletes of the column.
+    /// Defaults to `None`, i.e. the lexicographic byte order of [Column::key]
+    fn comparator() -> Option<KeyComparator> {
+        None
+    }
+}
+
+/// Custom key ordering of a [Column], see [Column::comparator].
+///
+/// RocksDB refuses to open a column with a comparator `name` different from
+/// the one it was created with, so the name has to change along with the
+/// ordering. A column can't switch from the default ordering to a custom one.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyComparator {
+    pub name: &'static str,
+    pub compare: fn(&[u8], &[u8]) -> Ordering,
 }
 
 pub trait ColumnName {
@@ -136,21 +205,35 @@ pub trait ColumnName {
 }
 
 pub trait TypedColumn: Column {
-    type Type: Serialize + DeserializeOwned;
+    /// Values are cloned out of the column cache, see
+    /// [crate::database::options::LedgerColumnOptions::cache_capacities]
+    type Type: Clone + Send + Sync + 'static;
+    /// Encoding of the values, [Bincode] for all the ledger columns
+    type Codec: ColumnCodec<Self::Type>;
 }
 
 impl TypedColumn for AddressSignatures {
     type Type = meta::AddressSignatureMeta;
+    type Codec = Bincode;
 }
 
 impl TypedColumn for SlotSignatures {
     type Type = Signature;
+    type Codec = Bincode;
 }
 
 pub trait ProtobufColumn: Column {
     type Type: prost::Message + Default;
 }
 
+/// MergeColumn is a trait for counter columns whose values are updated with
+/// RocksDB merges instead of read-modify-write. The merge operator registered
+/// for these columns adds every merge operand to the stored value.
+///
+/// Values are encoded the same way bincode encodes an `i64`, so they can be
+/// read with [`TypedColumn`] methods.
+pub trait MergeColumn: TypedColumn<Type = i64> {}
+
 /// SlotColumn is a trait for slot-based column families.  Its index is
 /// essentially Slot (or more generally speaking, has a 1:1 mapping to Slot).
 ///
@@ -209,15 +292,20 @@ pub trait ColumnIndexDeprecation: Column {
     fn convert_index(deprecated_index: Self::DeprecatedIndex) -> Self::Index;
 
     fn index(key: &[u8]) -> Self::Index {
+        // Way back in the day, we broke the TransactionStatus column key. This fallback
+        // preserves the existing logic for ancient keys, but realistically should never be
+        // executed.
+        <Self as ColumnIndexDeprecation>::try_index(key)
+            .unwrap_or_else(|| Self::as_index(0))
+    }
+
+    fn try_index(key: &[u8]) -> Option<Self::Index> {
         if let Ok(index) = Self::try_current_index(key) {
-            index
-        } else if let Ok(index) = Self::try_deprecated_index(key) {
-            Self::convert_index(index)
+            Some(index)
         } else {
-            // Way back in the day, we broke the TransactionStatus column key. This fallback
-            // preserves the existing logic for ancient keys, but realistically should never be
-            // executed.
-            Self::as_index(0)
+            Self::try_deprecated_index(key)
+                .ok()
+                .map(Self::convert_index)
         }
     }
 }
@@ -243,6 +331,10 @@ impl Column for AddressSignatures {
         <AddressSignatures as ColumnIndexDeprecation>::index(key)
     }
 
+    fn try_index(key: &[u8]) -> Option<Self::Index> {
+        <AddressSignatures as ColumnIndexDeprecation>::try_index(key)
+    }
+
     fn slot(index: Self::Index) -> Slot {
         index.1
     }
@@ -323,6 +415,10 @@ impl Column for SlotSignatures {
         <SlotSignatures as ColumnIndexDeprecation>::index(key)
     }
 
+    fn try_index(key: &[u8]) -> Option<Self::Index> {
+        <SlotSignatures as ColumnIndexDeprecation>::try_index(key)
+    }
+
     fn slot(index: Self::Index) -> Slot {
         index.0
     }
@@ -398,6 +494,10 @@ impl Column for TransactionStatus {
         <TransactionStatus as ColumnIndexDeprecation>::index(key)
     }
 
+    fn try_index(key: &[u8]) -> Option<Self::Index> {
+        <TransactionStatus as ColumnIndexDeprecation>::try_index(key)
+    }
+
     fn slot(index: Self::Index) -> Slot {
         index.1
     }
@@ -469,6 +569,7 @@ impl ColumnName for Blocktime {
 }
 impl TypedColumn for Blocktime {
     type Type = solana_sdk::clock::UnixTimestamp;
+    type Codec = Bincode;
 }
 
 // -----------------
@@ -480,6 +581,7 @@ impl ColumnName for Blockhash {
 }
 impl TypedColumn for Blockhash {
     type Type = solana_sdk::hash::Hash;
+    type Codec = Bincode;
 }
 
 // -----------------
@@ -497,6 +599,10 @@ impl Column for Transaction {
         <TransactionStatus as Column>::index(key)
     }
 
+    fn try_index(key: &[u8]) -> Option<Self::Index> {
+        <TransactionStatus as Column>::try_index(key)
+    }
+
     fn slot(index: Self::Index) -> Slot {
         <TransactionStatus as Column>::slot(index)
     }
@@ -555,6 +661,7 @@ impl ColumnIndexDeprecation for Transaction {
 // -----------------
 impl TypedColumn for TransactionMemos {
     type Type = String;
+    type Codec = Bincode;
 }
 
 impl Column for TransactionMemos {
@@ -571,6 +678,10 @@ impl Column for TransactionMemos {
         <TransactionMemos as ColumnIndexDeprecation>::index(key)
     }
 
+    fn try_index(key: &[u8]) -> Option<Self::Index> {
+        <TransactionMemos as ColumnIndexDeprecation>::try_index(key)
+    }
+
     fn slot(index: Self::Index) -> Slot {
         index.1
     }
@@ -655,8 +766,45 @@ impl Column for AccountModDatas {
 
 impl TypedColumn for AccountModDatas {
     type Type = meta::AccountModData;
+    type Codec = Bincode;
+}
+
+// -----------------
+// ProgramTransactionCounts
+// -----------------
+impl ColumnName for ProgramTransactionCounts {
+    const NAME: &'static str = PROGRAM_TRANSACTION_COUNTS_CF;
+}
+
+impl Column for ProgramTransactionCounts {
+    type Index = Pubkey;
+
+    fn key(pubkey: Self::Index) -> Vec<u8> {
+        pubkey.to_bytes().to_vec()
+    }
+
+    fn index(key: &[u8]) -> Self::Index {
+        Pubkey::try_from(key).unwrap()
+    }
+
+    // ProgramTransactionCounts column is not keyed by slot so this method is meaningless
+    fn slot(_index: Self::Index) -> Slot {
+        0
+    }
+
+    // ProgramTransactionCounts column is not keyed by slot so this method is meaningless
+    fn as_index(_slot: Slot) -> Self::Index {
+        Pubkey::default()
+    }
+}
+
+impl TypedColumn for ProgramTransactionCounts {
+    type Type = i64;
+    type Codec = Bincode;
 }
 
+impl MergeColumn for ProgramTransactionCounts {}
+
 // -----------------
 // Column Configuration
 // -----------------
@@ -666,6 +814,22 @@ pub fn should_enable_compression<C: 'static + Column + ColumnName>() -> bool {
     C::NAME == TransactionStatus::NAME
 }
 
+// Returns true if the keys of the column family contain the slot of the entry,
+// i.e. if [Column::slot] is meaningful.
+pub fn is_slot_keyed<C: Column + ColumnName>() -> bool {
+    matches!(
+        C::NAME,
+        TransactionStatus::NAME
+            | AddressSignatures::NAME
+            | SlotSignatures::NAME
+            | Blocktime::NAME
+            | Blockhash::NAME
+            | Transaction::NAME
+            | TransactionMemos::NAME
+            | PerfSamples::NAME
+    )
+}
+
 // -----------------
 // Column Queries
 // -----------------
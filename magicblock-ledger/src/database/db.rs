@@ -1,24 +1,31 @@
 use std::{
+    collections::HashMap,
     marker::PhantomData,
     path::Path,
-    sync::{atomic::AtomicI64, Arc},
+    sync::{
+        atomic::{AtomicI64, AtomicU64},
+        Arc, Mutex,
+    },
 };
 
-use bincode::deserialize;
-use rocksdb::{ColumnFamily, DBRawIterator, LiveFile};
+use rocksdb::{DBRawIterator, LiveFile};
 use solana_sdk::clock::Slot;
 
 use super::{
+    cache::ColumnCache,
+    codec::ColumnCodec,
     columns::{columns, Column, ColumnName, TypedColumn},
     iterator::IteratorMode,
     ledger_column::LedgerColumn,
     options::{LedgerColumnOptions, LedgerOptions},
-    rocks_db::Rocks,
-    write_batch::WriteBatch,
+    rate_limiter::WriteRateLimiter,
+    rocks_db::{CfHandle, Rocks},
+    write_batch::{BatchOp, WriteBatch},
 };
 use crate::{
-    database::columns::DIRTY_COUNT, errors::LedgerError,
-    metrics::PerfSamplingStatus,
+    database::columns::DIRTY_COUNT,
+    errors::LedgerError,
+    metrics::{ColumnLatencies, PerfSamplingStatus},
 };
 
 #[derive(Debug)]
@@ -26,6 +33,18 @@ pub struct Database {
     pub(crate) backend: Arc<Rocks>,
     path: Arc<Path>,
     column_options: Arc<LedgerColumnOptions>,
+    // Shared by all handles of the same column, so that writes through one
+    // of them invalidate the values cached by the others
+    column_caches: HashMap<&'static str, Arc<ColumnCache>>,
+    // Shared for the same reason, so the limit applies to the column as a
+    // whole rather than to each handle
+    column_rate_limiters: HashMap<&'static str, Arc<WriteRateLimiter>>,
+    // Shared so that check-then-write sequences are serialized per column
+    // rather than per handle, see [LedgerColumn::get_or_insert_with]
+    column_insert_locks: HashMap<&'static str, Arc<Mutex<()>>>,
+    // Shared so that the latencies of all handles of a column are accounted
+    // together, but apart from the ones of other databases
+    column_latencies: HashMap<&'static str, Arc<ColumnLatencies>>,
 }
 
 impl Database {
@@ -34,12 +53,45 @@ impl Database {
         options: LedgerOptions,
     ) -> Result<Self, LedgerError> {
         let column_options = Arc::new(options.column_options.clone());
+        let column_caches = column_caches(&column_options);
+        let column_rate_limiters = column_rate_limiters(&column_options);
+        let column_insert_locks = column_insert_locks();
+        let column_latencies = column_latencies();
         let backend = Arc::new(Rocks::open(path, options)?);
 
         Ok(Database {
             backend,
             path: Arc::from(path),
             column_options,
+            column_caches,
+            column_rate_limiters,
+            column_insert_locks,
+            column_latencies,
+        })
+    }
+
+    /// Opens only the `columns` of an existing database in read only mode,
+    /// see [Rocks::open_read_only]
+    pub fn open_columns(
+        path: &Path,
+        options: LedgerOptions,
+        columns: &[&'static str],
+    ) -> Result<Self, LedgerError> {
+        let column_options = Arc::new(options.column_options.clone());
+        let column_caches = column_caches(&column_options);
+        let column_rate_limiters = column_rate_limiters(&column_options);
+        let column_insert_locks = column_insert_locks();
+        let column_latencies = column_latencies();
+        let backend = Arc::new(Rocks::open_read_only(path, options, columns)?);
+
+        Ok(Database {
+            backend,
+            path: Arc::from(path),
+            column_options,
+            column_caches,
+            column_rate_limiters,
+            column_insert_locks,
+            column_latencies,
         })
     }
 
@@ -53,11 +105,12 @@ impl Database {
     where
         C: TypedColumn + ColumnName,
     {
-        if let Some(pinnable_slice) = self
-            .backend
-            .get_pinned_cf(self.cf_handle::<C>(), &C::key(key))?
+        let key = C::key(key);
+        if let Some(pinnable_slice) =
+            self.backend.get_pinned_cf(&self.cf_handle::<C>(), &key)?
         {
-            let value = deserialize(pinnable_slice.as_ref())?;
+            let value = C::Codec::decode(pinnable_slice.as_ref())
+                .map_err(|err| LedgerError::deserialize(C::NAME, &key, err))?;
             Ok(Some(value))
         } else {
             Ok(None)
@@ -72,7 +125,7 @@ impl Database {
         C: Column + ColumnName,
     {
         let cf = self.cf_handle::<C>();
-        let iter = self.backend.iterator_cf::<C>(cf, iterator_mode);
+        let iter = self.backend.iterator_cf::<C>(&cf, iterator_mode);
         Ok(iter.map(|pair| {
             let (key, value) = pair.unwrap();
             (C::index(&key), value)
@@ -80,7 +133,7 @@ impl Database {
     }
 
     #[inline]
-    pub fn cf_handle<C>(&self) -> &ColumnFamily
+    pub fn cf_handle<C>(&self) -> CfHandle
     where
         C: Column + ColumnName,
     {
@@ -91,18 +144,37 @@ impl Database {
     where
         C: Column + ColumnName,
     {
-        LedgerColumn {
+        let column = LedgerColumn {
             backend: Arc::clone(&self.backend),
             column: PhantomData,
             column_options: Arc::clone(&self.column_options),
             read_perf_status: PerfSamplingStatus::default(),
             write_perf_status: PerfSamplingStatus::default(),
             entry_counter: AtomicI64::new(DIRTY_COUNT),
-        }
+            entry_counter_generation: AtomicU64::new(0),
+            counted_writes_in_flight: AtomicU64::new(0),
+            insert_lock: self
+                .column_insert_locks
+                .get(C::NAME)
+                .cloned()
+                .unwrap_or_default(),
+            recount_lock: Mutex::new(()),
+            #[cfg(test)]
+            num_count_scans: AtomicU64::new(0),
+            cache: self.column_caches.get(C::NAME).cloned(),
+            rate_limiter: self.column_rate_limiters.get(C::NAME).cloned(),
+            latencies: self
+                .column_latencies
+                .get(C::NAME)
+                .cloned()
+                .unwrap_or_default(),
+        };
+        column.validate_counter();
+        column
     }
 
     #[inline]
-    pub fn raw_iterator_cf(&self, cf: &ColumnFamily) -> DBRawIterator {
+    pub fn raw_iterator_cf(&self, cf: &CfHandle) -> DBRawIterator {
         self.backend.raw_iterator_cf(cf)
     }
 
@@ -110,14 +182,49 @@ impl Database {
         let write_batch = self.backend.batch();
         let map = columns()
             .into_iter()
-            .map(|desc| (desc, self.backend.cf_handle(desc)))
+            .filter_map(|desc| {
+                let cf = self.backend.try_cf_handle(desc).ok()?;
+                Some((desc, cf))
+            })
             .collect();
 
-        WriteBatch { write_batch, map }
+        WriteBatch {
+            write_batch,
+            map,
+            column_not_opened: None,
+            ops: Vec::new(),
+            min_put_slot: None,
+        }
     }
 
+    /// Writes the batch atomically and then drops the cached values of the
+    /// columns it modified
     pub fn write(&self, batch: WriteBatch) -> Result<(), LedgerError> {
-        self.backend.write(batch.write_batch)
+        if let Some(column) = batch.column_not_opened {
+            return Err(LedgerError::ColumnNotOpened(column));
+        }
+        self.backend.write(batch.write_batch)?;
+        self.invalidate_cached(&batch.ops);
+        Ok(())
+    }
+
+    /// Drops the cached values of the keys written or deleted by a batch,
+    /// including the ones read back from RocksDB while it was pending
+    fn invalidate_cached(&self, ops: &[BatchOp]) {
+        for op in ops {
+            match op {
+                BatchOp::Put { cf, key } | BatchOp::Delete { cf, key } => {
+                    if let Some(cache) = self.column_caches.get(cf) {
+                        cache.remove(key);
+                    }
+                }
+                BatchOp::DeleteRange { cf, .. } => {
+                    if let Some(cache) = self.column_caches.get(cf) {
+                        cache.clear();
+                    }
+                }
+            }
+        }
     }
 
     pub fn storage_size(&self) -> Result<u64, LedgerError> {
@@ -135,7 +242,6 @@ impl Database {
     ) where
         C: Column + ColumnName,
     {
-        let cf = self.cf_handle::<C>();
         // Note that the default behavior of rocksdb's delete_range_cf deletes
         // files within [from, to), while our purge logic applies to [from, to].
         //
@@ -143,7 +249,7 @@ impl Database {
         // adjusting the `to` slot range by 1.
         let from_index = C::as_index(from);
         let to_index = C::as_index(to.saturating_add(1));
-        batch.delete_range_cf::<C>(cf, from_index, to_index);
+        batch.delete_range_cf::<C>(from_index, to_index);
     }
 
     /// Delete files whose slot range is within \[`from`, `to`\].
@@ -156,7 +262,7 @@ impl Database {
         C: Column + ColumnName,
     {
         self.backend.delete_file_in_range_cf(
-            self.cf_handle::<C>(),
+            &self.cf_handle::<C>(),
             &C::key(C::as_index(from)),
             &C::key(C::as_index(to)),
         )
@@ -171,7 +277,7 @@ impl Database {
         C: Column + ColumnName,
     {
         self.backend.compact_range_cf(
-            self.cf_handle::<C>(),
+            &self.cf_handle::<C>(),
             from.map(|index| C::key(index)),
             to.map(|index| C::key(index)),
         )
@@ -187,3 +293,38 @@ impl Database {
         self.backend.live_files_metadata()
     }
 }
+
+fn column_caches(
+    options: &LedgerColumnOptions,
+) -> HashMap<&'static str, Arc<ColumnCache>> {
+    options
+        .cache_capacities
+        .iter()
+        .filter(|(_, capacity)| **capacity > 0)
+        .map(|(name, capacity)| (*name, Arc::new(ColumnCache::new(*capacity))))
+        .collect()
+}
+
+fn column_insert_locks() -> HashMap<&'static str, Arc<Mutex<()>>> {
+    columns()
+        .into_iter()
+        .map(|name| (name, Arc::default()))
+        .collect()
+}
+
+fn column_latencies() -> HashMap<&'static str, Arc<ColumnLatencies>> {
+    columns()
+        .into_iter()
+        .map(|name| (name, Arc::default()))
+        .collect()
+}
+
+fn column_rate_limiters(
+    options: &LedgerColumnOptions,
+) -> HashMap<&'static str, Arc<WriteRateLimiter>> {
+    options
+        .write_rate_limits
+        .iter()
+        .map(|(name, limit)| (*name, Arc::new(WriteRateLimiter::new(*limit))))
+        .collect()
+}
@@ -4,4 +4,16 @@ pub enum IteratorMode<Index> {
     Start,
     End,
     From(Index, IteratorDirection),
+    /// Only the keys starting with the given bytes, i.e. the leading fields
+    /// of a composite key, from the first or, in reverse, the last of them
+    FromPrefix(Vec<u8>, IteratorDirection),
+}
+
+/// Returns the smallest key that sorts after all the keys starting with
+/// `prefix`, `None` if there is none since it's made of `0xff`s only
+pub(crate) fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let last = prefix.iter().rposition(|byte| *byte != u8::MAX)?;
+    let mut upper_bound = prefix[..=last].to_vec();
+    upper_bound[last] += 1;
+    Some(upper_bound)
 }
@@ -1,24 +1,32 @@
 use std::{
+    io::{self, Read, Write},
     marker::PhantomData,
+    path::PathBuf,
     sync::{
-        atomic::{AtomicI64, Ordering},
-        Arc,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex,
     },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use bincode::{deserialize, serialize};
+use bincode::deserialize;
 use log::{error, warn};
 use prost::Message;
-use rocksdb::{properties as RocksProperties, ColumnFamily};
+use rocksdb::{properties as RocksProperties, DBRawIterator};
 use serde::de::DeserializeOwned;
 
 use super::{
+    cache::ColumnCache,
+    codec::ColumnCodec,
     columns::{
-        Column, ColumnIndexDeprecation, ColumnName, ProtobufColumn, TypedColumn,
+        Column, ColumnIndexDeprecation, ColumnName, MergeColumn,
+        ProtobufColumn, TypedColumn,
     },
     iterator::IteratorMode,
-    options::LedgerColumnOptions,
-    rocks_db::Rocks,
+    options::{LedgerColumnOptions, WriteDurability},
+    rate_limiter::WriteRateLimiter,
+    rocks_db::{CfHandle, Rocks},
 };
 use crate::{
     database::{columns::DIRTY_COUNT, write_batch::WriteBatch},
@@ -26,11 +34,149 @@ use crate::{
     metrics::{
         maybe_enable_rocksdb_perf, report_rocksdb_read_perf,
         report_rocksdb_write_perf, BlockstoreRocksDbColumnFamilyMetrics,
-        PerfSamplingStatus, BLOCKSTORE_METRICS_ERROR, PERF_METRIC_OP_NAME_GET,
+        ColumnLatencies, Percentiles, PerfSamplingStatus, RocksDbOpSpan,
+        BLOCKSTORE_METRICS_ERROR, PERF_METRIC_OP_NAME_GET,
         PERF_METRIC_OP_NAME_MULTI_GET, PERF_METRIC_OP_NAME_PUT,
     },
 };
 
+/// Decoded key and raw value of a column entry
+type ColumnEntry<C> = (<C as Column>::Index, Box<[u8]>);
+
+/// Undecoded key and value of a column entry
+type RawEntry = (Box<[u8]>, Box<[u8]>);
+
+/// Decoded entries of a page and the cursor to the next one,
+/// see [LedgerColumn::page]
+type Page<C> = (
+    Vec<(<C as Column>::Index, <C as TypedColumn>::Type)>,
+    Option<<C as Column>::Index>,
+);
+
+/// Write related state of a column as reported by RocksDB,
+/// see [LedgerColumn::write_health].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WriteHealth {
+    /// Writes are stopped, i.e. due to too many pending compactions
+    pub is_stopped: bool,
+    /// Rate (bytes/sec) writes are currently delayed to, 0 if not delayed
+    pub delayed_write_rate: i64,
+    /// Number of accumulated background errors
+    pub background_errors: i64,
+}
+
+/// Size of a column's SST files around a manual compaction,
+/// see [LedgerColumn::compact_range_blocking].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionStats {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub sst_files_before: usize,
+    pub sst_files_after: usize,
+    /// Time the compaction took
+    pub duration: Duration,
+}
+
+impl CompactionStats {
+    /// Number of bytes the compaction freed, 0 if the column grew
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Result of scanning a column for values that fail to decode,
+/// see [LedgerColumn::verify_integrity].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegrityReport<Index> {
+    /// Number of scanned entries
+    pub total: u64,
+    /// Number of entries whose value decoded successfully
+    pub ok: u64,
+    /// Keys of the entries whose value failed to decode
+    pub corrupt_keys: Vec<Index>,
+}
+
+/// Mismatch between the cached entry counter of a column and its actual
+/// number of entries, see [crate::Ledger::verify_counters].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CounterDiscrepancy {
+    pub column: &'static str,
+    /// Value of the entry counter before the check
+    pub cached: i64,
+    /// Number of entries found by scanning the column
+    pub actual: i64,
+}
+
+/// Distribution of the value sizes of a column,
+/// see [LedgerColumn::value_size_histogram].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SizeHistogram {
+    /// Number of values per size bucket, bucket `0` counts empty values
+    /// and bucket `i > 0` counts values of `2^(i-1)..2^i` bytes
+    pub buckets: Vec<u64>,
+    /// Number of values
+    pub count: u64,
+    /// Size of the smallest value, 0 if there are none
+    pub min: u64,
+    /// Size of the largest value
+    pub max: u64,
+    /// Sum of all value sizes
+    pub total_bytes: u64,
+}
+
+impl SizeHistogram {
+    fn record(&mut self, size: usize) {
+        let bucket = (usize::BITS - size.leading_zeros()) as usize;
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+
+        let size = size as u64;
+        self.min = if self.count == 0 {
+            size
+        } else {
+            self.min.min(size)
+        };
+        self.max = self.max.max(size);
+        self.total_bytes += size;
+        self.count += 1;
+    }
+
+    /// Average value size, 0 if there are no values
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_bytes as f64 / self.count as f64
+        }
+    }
+}
+
+/// Marks a write whose entry counter update is still pending, ends when
+/// dropped, see [LedgerColumn::begin_counted_write]
+#[must_use]
+pub struct CountedWrite<'a> {
+    entry_counter_generation: &'a AtomicU64,
+    counted_writes_in_flight: &'a AtomicU64,
+}
+
+impl Drop for CountedWrite<'_> {
+    fn drop(&mut self) {
+        // Bumped first, so that a recount seeing no write in flight also
+        // sees that one completed
+        self.entry_counter_generation.fetch_add(1, Ordering::SeqCst);
+        self.counted_writes_in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Number of entries [LedgerColumn::import] writes per batch
+const IMPORT_BATCH_SIZE: usize = 10_000;
+
+/// Number of entries [LedgerColumn::purge_deprecated_indexes] and
+/// [LedgerColumn::delete_if] delete per batch
+const DELETE_BATCH_SIZE: usize = 10_000;
+
 #[derive(Debug)]
 pub struct LedgerColumn<C>
 where
@@ -50,6 +196,34 @@ where
     //     // txs:  50,000 * 3600 * 24 * 365 * 100 =       157,680,000,000,000
     //     // i64::MAX                             = 9,223,372,036,854,775,807
     pub entry_counter: AtomicI64,
+    // Bumped on every counter update, allows a background recount to detect
+    // updates that raced with its scan, see [LedgerColumn::recount_async]
+    pub entry_counter_generation: AtomicU64,
+    // Number of writes whose counter update is still pending, see
+    // [LedgerColumn::begin_counted_write]
+    pub counted_writes_in_flight: AtomicU64,
+    // Serializes check-then-write sequences, see [LedgerColumn::get_or_insert_with].
+    // Shared by all handles of the same column
+    pub insert_lock: Arc<Mutex<()>>,
+    // Held while recomputing a dirty entry counter, so that concurrent
+    // callers of [LedgerColumn::count_column_using_cache] wait for a single
+    // scan instead of each running their own
+    pub recount_lock: Mutex<()>,
+    #[cfg(test)]
+    pub num_count_scans: AtomicU64,
+    // Deserialized values of hot typed columns, only set for the columns
+    // configured in [LedgerColumnOptions::cache_capacities].
+    // Kept in sync with writes and deletes going through this type or a
+    // [WriteBatch] written by [crate::database::db::Database::write], writes
+    // that bypass both (i.e. [Rocks::put_cf]) aren't reflected.
+    pub cache: Option<Arc<ColumnCache>>,
+    // Only set for the columns configured in
+    // [LedgerColumnOptions::write_rate_limits], applies to the writes going
+    // through this type but not to batches.
+    pub rate_limiter: Option<Arc<WriteRateLimiter>>,
+    // Latencies of the sampled operations, see [LedgerColumn::latency_percentiles].
+    // Shared by all handles of the same column
+    pub latencies: Arc<ColumnLatencies>,
 }
 
 impl<C: Column + ColumnName> LedgerColumn<C> {
@@ -106,6 +280,42 @@ impl<C: Column + ColumnName> LedgerColumn<C> {
         };
         cf_rocksdb_metrics.report_metrics(C::NAME, &self.column_options);
     }
+
+    /// Reads the write stall and background error state of the column,
+    /// properties that fail to be read are reported as
+    /// [BLOCKSTORE_METRICS_ERROR].
+    pub fn write_health(&self) -> WriteHealth {
+        let is_write_stopped = self
+            .get_int_property(RocksProperties::IS_WRITE_STOPPED)
+            .unwrap_or(BLOCKSTORE_METRICS_ERROR);
+        WriteHealth {
+            is_stopped: is_write_stopped > 0,
+            delayed_write_rate: self
+                .get_int_property(RocksProperties::ACTUAL_DELAYED_WRITE_RATE)
+                .unwrap_or(BLOCKSTORE_METRICS_ERROR),
+            background_errors: self
+                .get_int_property(RocksProperties::BACKGROUND_ERRORS)
+                .unwrap_or(BLOCKSTORE_METRICS_ERROR),
+        }
+    }
+
+    /// Returns [LedgerError::WriteStalled] if RocksDB stopped writes to
+    /// the column, allowing ingestion to back off until compaction catches up.
+    pub fn check_write_stalled(&self) -> LedgerResult<()> {
+        if self.write_health().is_stopped {
+            Err(LedgerError::WriteStalled(C::NAME))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the estimated latency percentiles of the `op` operations
+    /// (i.e. "get" or "put") on this column, or `None` if none were sampled.
+    /// Only operations sampled according to
+    /// [LedgerColumnOptions::rocks_perf_sample_interval] are accounted for.
+    pub fn latency_percentiles(&self, op: &str) -> Option<Percentiles> {
+        self.latencies.percentiles(op)
+    }
 }
 
 impl<C> LedgerColumn<C>
@@ -116,19 +326,27 @@ where
         &self,
         key: C::Index,
     ) -> std::result::Result<Option<Vec<u8>>, LedgerError> {
+        let cf = self.try_handle()?;
+        let key = C::key(key);
+        let span =
+            RocksDbOpSpan::enter(C::NAME, PERF_METRIC_OP_NAME_GET, key.len());
         let is_perf_enabled = maybe_enable_rocksdb_perf(
             self.column_options.rocks_perf_sample_interval,
             &self.read_perf_status,
         );
-        let result = self.backend.get_cf(self.handle(), &C::key(key));
+        let result = self.backend.get_cf(&cf, &key);
         if let Some(op_start_instant) = is_perf_enabled {
             report_rocksdb_read_perf(
                 C::NAME,
                 PERF_METRIC_OP_NAME_GET,
                 &op_start_instant.elapsed(),
                 &self.column_options,
+                &self.latencies,
             );
         }
+        if let Ok(Some(value)) = &result {
+            span.record_value_size(value.len());
+        }
         result
     }
 
@@ -136,22 +354,35 @@ where
         &self,
         keys: Vec<C::Index>,
     ) -> Vec<std::result::Result<Option<Vec<u8>>, LedgerError>> {
+        let Ok(cf) = self.try_handle() else {
+            return keys
+                .into_iter()
+                .map(|_| Err(LedgerError::ColumnNotOpened(C::NAME)))
+                .collect();
+        };
         let rocks_keys: Vec<_> =
             keys.into_iter().map(|key| C::key(key)).collect();
         {
             let ref_rocks_keys: Vec<_> =
                 rocks_keys.iter().map(|k| &k[..]).collect();
+            let span = RocksDbOpSpan::enter(
+                C::NAME,
+                PERF_METRIC_OP_NAME_MULTI_GET,
+                rocks_keys.iter().map(Vec::len).sum(),
+            );
+            let mut value_size = 0;
             let is_perf_enabled = maybe_enable_rocksdb_perf(
                 self.column_options.rocks_perf_sample_interval,
                 &self.read_perf_status,
             );
             let result = self
                 .backend
-                .multi_get_cf(self.handle(), ref_rocks_keys)
+                .multi_get_cf(&cf, ref_rocks_keys)
                 .into_iter()
                 .map(|r| match r {
                     Ok(opt) => match opt {
                         Some(pinnable_slice) => {
+                            value_size += pinnable_slice.len();
                             Ok(Some(pinnable_slice.as_ref().to_vec()))
                         }
                         None => Ok(None),
@@ -166,13 +397,59 @@ where
                     PERF_METRIC_OP_NAME_MULTI_GET,
                     &op_start_instant.elapsed(),
                     &self.column_options,
+                    &self.latencies,
                 );
             }
+            span.record_value_size(value_size);
 
             result
         }
     }
 
+    /// Checks which of the `keys` are present in the column using a single
+    /// multi-get, the result preserves the order of `keys`.
+    /// Keys that fail to be read are reported as absent.
+    pub fn multi_contains(&self, keys: Vec<C::Index>) -> Vec<bool> {
+        let cf = match self.try_handle() {
+            Ok(cf) => cf,
+            Err(err) => {
+                error!("Failed to check keys in {}: {}", C::NAME, err);
+                return vec![false; keys.len()];
+            }
+        };
+        let rocks_keys: Vec<_> =
+            keys.into_iter().map(|key| C::key(key)).collect();
+        let ref_rocks_keys: Vec<_> =
+            rocks_keys.iter().map(|k| &k[..]).collect();
+        let is_perf_enabled = maybe_enable_rocksdb_perf(
+            self.column_options.rocks_perf_sample_interval,
+            &self.read_perf_status,
+        );
+        let result = self
+            .backend
+            .multi_get_cf(&cf, ref_rocks_keys)
+            .into_iter()
+            .map(|r| match r {
+                Ok(opt) => opt.is_some(),
+                Err(err) => {
+                    error!("Failed to check key in {}: {}", C::NAME, err);
+                    false
+                }
+            })
+            .collect();
+        if let Some(op_start_instant) = is_perf_enabled {
+            report_rocksdb_read_perf(
+                C::NAME,
+                PERF_METRIC_OP_NAME_MULTI_GET,
+                &op_start_instant.elapsed(),
+                &self.column_options,
+                &self.latencies,
+            );
+        }
+
+        result
+    }
+
     pub fn iter(
         &self,
         iterator_mode: IteratorMode<C::Index>,
@@ -180,22 +457,108 @@ where
         impl Iterator<Item = (C::Index, Box<[u8]>)> + '_,
         LedgerError,
     > {
-        let cf = self.handle();
-        let iter = self.backend.iterator_cf::<C>(cf, iterator_mode);
+        let cf = self.try_handle()?;
+        let iterator_mode = match iterator_mode {
+            IteratorMode::From(index, direction) => {
+                IteratorMode::From(C::key(index), direction)
+            }
+            IteratorMode::FromPrefix(prefix, direction) => {
+                IteratorMode::FromPrefix(prefix, direction)
+            }
+            IteratorMode::Start => IteratorMode::Start,
+            IteratorMode::End => IteratorMode::End,
+        };
+        let key_size = match &iterator_mode {
+            IteratorMode::From(key, _) | IteratorMode::FromPrefix(key, _) => {
+                key.len()
+            }
+            IteratorMode::Start | IteratorMode::End => 0,
+        };
+        // Only covers creating the iterator, not consuming it
+        let _span = RocksDbOpSpan::enter(C::NAME, "iter", key_size);
+        let iter = self.backend.iterator_cf_raw_key(&cf, iterator_mode);
         Ok(iter.map(|pair| {
             let (key, value) = pair.unwrap();
             (C::index(&key), value)
         }))
     }
 
+    /// Iterates over the undecoded key and value bytes, i.e. to hash the
+    /// contents of a column without paying for decoding every index.
+    pub fn iter_raw(
+        &self,
+        iterator_mode: IteratorMode<C::Index>,
+    ) -> impl Iterator<Item = LedgerResult<RawEntry>> + '_ {
+        // Yields a single error if the column wasn't opened
+        let (iter, error) = match self.try_handle() {
+            Ok(cf) => (
+                Some(self.backend.iterator_cf::<C>(&cf, iterator_mode)),
+                None,
+            ),
+            Err(err) => (None, Some(err)),
+        };
+        error.into_iter().map(Err).chain(
+            iter.into_iter()
+                .flatten()
+                .map(|pair| pair.map_err(LedgerError::from)),
+        )
+    }
+
+    /// Returns the first entry whose key is at or after `key`, or `None`
+    /// if no such entry exists.
+    /// Uses a raw iterator seek, so gaps (i.e. truncated slots) are skipped
+    /// without scanning.
+    pub fn get_next_from(
+        &self,
+        key: C::Index,
+    ) -> LedgerResult<Option<ColumnEntry<C>>> {
+        let cf = self.try_handle()?;
+        let mut iter = self.backend.raw_iterator_cf(&cf);
+        iter.seek(C::key(key));
+        Self::current_entry(iter)
+    }
+
+    /// Returns the last entry whose key is at or before `key`, or `None`
+    /// if no such entry exists.
+    pub fn get_prev_from(
+        &self,
+        key: C::Index,
+    ) -> LedgerResult<Option<ColumnEntry<C>>> {
+        let cf = self.try_handle()?;
+        let mut iter = self.backend.raw_iterator_cf(&cf);
+        iter.seek_for_prev(C::key(key));
+        Self::current_entry(iter)
+    }
+
+    fn current_entry(
+        iter: DBRawIterator,
+    ) -> LedgerResult<Option<ColumnEntry<C>>> {
+        match (iter.key(), iter.value()) {
+            (Some(key), Some(value)) => Ok(Some((C::index(key), value.into()))),
+            _ => {
+                iter.status()?;
+                Ok(None)
+            }
+        }
+    }
+
     #[inline]
-    pub fn handle(&self) -> &ColumnFamily {
+    pub fn handle(&self) -> CfHandle {
         self.backend.cf_handle(C::NAME)
     }
 
+    /// Same as [LedgerColumn::handle], but fails with
+    /// [LedgerError::ColumnNotOpened] instead of panicking if the column
+    /// wasn't opened, see [crate::Ledger::open_columns]
+    #[inline]
+    pub fn try_handle(&self) -> LedgerResult<CfHandle> {
+        self.backend.try_cf_handle(C::NAME)
+    }
+
     #[cfg(test)]
     pub fn is_empty(&self) -> std::result::Result<bool, LedgerError> {
-        let mut iter = self.backend.raw_iterator_cf(self.handle());
+        let cf = self.try_handle()?;
+        let mut iter = self.backend.raw_iterator_cf(&cf);
         iter.seek_to_first();
         Ok(!iter.valid())
     }
@@ -205,19 +568,43 @@ where
         key: C::Index,
         value: &[u8],
     ) -> std::result::Result<(), LedgerError> {
+        self.put_bytes_with_opts(key, value, WriteDurability::default())
+    }
+
+    /// Same as [LedgerColumn::put_bytes] with the given `durability`,
+    /// see [WriteDurability] for the tradeoffs.
+    pub fn put_bytes_with_opts(
+        &self,
+        key: C::Index,
+        value: &[u8],
+        durability: WriteDurability,
+    ) -> std::result::Result<(), LedgerError> {
+        let cf = self.try_handle()?;
+        let key = C::key(key);
+        self.throttle_write(key.len() + value.len())?;
+        let span =
+            RocksDbOpSpan::enter(C::NAME, PERF_METRIC_OP_NAME_PUT, key.len());
+        span.record_value_size(value.len());
         let is_perf_enabled = maybe_enable_rocksdb_perf(
             self.column_options.rocks_perf_sample_interval,
             &self.write_perf_status,
         );
-        let result = self.backend.put_cf(self.handle(), &C::key(key), value);
+        let result = self.backend.put_cf_opt(
+            &cf,
+            &key,
+            value,
+            &durability.to_rocksdb_write_options(),
+        );
         if let Some(op_start_instant) = is_perf_enabled {
             report_rocksdb_write_perf(
                 C::NAME,
                 PERF_METRIC_OP_NAME_PUT,
                 &op_start_instant.elapsed(),
                 &self.column_options,
+                &self.latencies,
             );
         }
+        self.invalidate_cached(&key);
         result
     }
 
@@ -230,344 +617,1388 @@ where
         &self,
         name: &'static std::ffi::CStr,
     ) -> Result<i64, LedgerError> {
-        self.backend.get_int_property_cf(self.handle(), name)
+        let cf = self.try_handle()?;
+        self.backend.get_int_property_cf(&cf, name)
     }
 
     pub fn delete(&self, key: C::Index) -> Result<(), LedgerError> {
+        let cf = self.try_handle()?;
         let is_perf_enabled = maybe_enable_rocksdb_perf(
             self.column_options.rocks_perf_sample_interval,
             &self.write_perf_status,
         );
-        let result = self.backend.delete_cf(self.handle(), &C::key(key));
+        let key = C::key(key);
+        let result = self.backend.delete_cf(&cf, &key);
         if let Some(op_start_instant) = is_perf_enabled {
             report_rocksdb_write_perf(
                 C::NAME,
                 "delete",
                 &op_start_instant.elapsed(),
                 &self.column_options,
+                &self.latencies,
             );
         }
+        self.invalidate_cached(&key);
         result
     }
 
+    /// Removes all entries of the column with one atomic write of a range
+    /// delete spanning its keys and resets the entry counter to 0.
+    /// Entries written concurrently with this call may or may not survive it.
+    pub fn delete_all(&self) -> LedgerResult<()> {
+        let cf = self.try_handle()?;
+        let _write = self.begin_counted_write();
+        // The bounds are taken from the column itself rather than derived
+        // from the key bytes, so they hold for any [Column::comparator]
+        let mut iter = self.backend.raw_iterator_cf(&cf);
+        iter.seek_to_first();
+        let Some(first_key) = iter.key().map(<[u8]>::to_vec) else {
+            iter.status()?;
+            self.reset_entry_counter();
+            return Ok(());
+        };
+        iter.seek_to_last();
+        iter.status()?;
+        let last_key =
+            iter.key().map_or_else(|| first_key.clone(), <[u8]>::to_vec);
+        drop(iter);
+
+        // The range end is exclusive, so the last key is deleted on its own
+        let mut batch = self.backend.batch();
+        batch.delete_range_cf(&cf, &first_key, &last_key);
+        batch.delete_cf(&cf, &last_key);
+        let is_perf_enabled = maybe_enable_rocksdb_perf(
+            self.column_options.rocks_perf_sample_interval,
+            &self.write_perf_status,
+        );
+        let result = self.backend.write(batch);
+        if let Some(op_start_instant) = is_perf_enabled {
+            report_rocksdb_write_perf(
+                C::NAME,
+                "delete_range",
+                &op_start_instant.elapsed(),
+                &self.column_options,
+                &self.latencies,
+            );
+        }
+        self.clear_cache();
+        result?;
+
+        self.reset_entry_counter();
+        Ok(())
+    }
+
+    /// Leaves the entry counter [`DIRTY_COUNT`] to be recounted on the next
+    /// [LedgerColumn::count_column_using_cache], i.e. after a write whose
+    /// number of new entries is unknown
+    pub fn mark_counter_dirty(&self) {
+        self.entry_counter_generation.fetch_add(1, Ordering::SeqCst);
+        self.entry_counter.store(DIRTY_COUNT, Ordering::SeqCst);
+    }
+
+    fn reset_entry_counter(&self) {
+        self.entry_counter_generation.fetch_add(1, Ordering::SeqCst);
+        self.entry_counter.store(0, Ordering::SeqCst);
+    }
+
+    /// Queues the deletion of `key` in `write_batch`. The cached value is
+    /// dropped once the batch is written, see
+    /// [crate::database::db::Database::write], so it's still served until
+    /// then.
     pub fn delete_in_batch(&self, write_batch: &mut WriteBatch, key: C::Index) {
         write_batch.delete::<C>(key);
     }
 
+    /// Queues the deletion of \[`from`, `to`) in `write_batch`, the whole
+    /// column cache is cleared once the batch is written like
+    /// [LedgerColumn::delete_in_batch] does for a single key.
     pub fn delete_range_in_batch(
         &self,
         write_batch: &mut WriteBatch,
         from: C::Index,
         to: C::Index,
     ) {
-        write_batch.delete_range_cf::<C>(self.handle(), from, to);
+        write_batch.delete_range_cf::<C>(from, to);
+    }
+
+    /// Applies the configured [LedgerColumnOptions::write_rate_limits] to a
+    /// write of `bytes` bytes
+    fn throttle_write(&self, bytes: usize) -> LedgerResult<()> {
+        match &self.rate_limiter {
+            Some(rate_limiter) => rate_limiter.acquire(C::NAME, bytes as u64),
+            None => Ok(()),
+        }
+    }
+
+    fn invalidate_cached(&self, key: &[u8]) {
+        if let Some(cache) = &self.cache {
+            cache.remove(key);
+        }
+    }
+
+    fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
     }
 
     /// See [crate::database::rocks_db::Rocks::compact_range_cf] for documentation.
-    pub fn compact_range(&self, from: Option<C::Index>, to: Option<C::Index>) {
+    pub fn compact_range(
+        &self,
+        from: Option<C::Index>,
+        to: Option<C::Index>,
+    ) -> LedgerResult<()> {
         self.backend.compact_range_cf(
-            self.handle(),
+            &self.try_handle()?,
             from.map(|index| C::key(index)),
             to.map(|index| C::key(index)),
-        )
+        );
+        Ok(())
     }
 
-    /// See [crate::database::rocks_db::Rocks::flush_cf] for documentation.
-    pub fn flush(&self) -> LedgerResult<()> {
-        self.backend.flush_cf(self.handle())
+    /// Same as [LedgerColumn::compact_range], but waits for the compaction
+    /// to complete and reports the size of the column's SST files before
+    /// and after it, i.e. to tell how much space a truncation reclaimed.
+    /// Data still in memtables isn't accounted for.
+    pub fn compact_range_blocking(
+        &self,
+        from: Option<C::Index>,
+        to: Option<C::Index>,
+    ) -> LedgerResult<CompactionStats> {
+        let cf = self.try_handle()?;
+        let (bytes_before, sst_files_before) = self.sst_files_size()?;
+        let start = Instant::now();
+        // Manual compactions block until they're done unless configured
+        // otherwise, which the ledger doesn't do
+        self.backend.compact_range_cf(
+            &cf,
+            from.map(|index| C::key(index)),
+            to.map(|index| C::key(index)),
+        );
+        let duration = start.elapsed();
+        let (bytes_after, sst_files_after) = self.sst_files_size()?;
+
+        Ok(CompactionStats {
+            bytes_before,
+            bytes_after,
+            sst_files_before,
+            sst_files_after,
+            duration,
+        })
     }
 
-    pub fn count_column_using_cache(&self) -> LedgerResult<i64> {
-        let cached = self.entry_counter.load(Ordering::Relaxed);
-        if cached != DIRTY_COUNT {
-            return Ok(cached);
-        }
+    /// Total size of the column's SST files, data still in memtables isn't
+    /// accounted for
+    pub fn storage_size(&self) -> LedgerResult<u64> {
+        Ok(self.sst_files_size()?.0)
+    }
 
-        self
-            .iter(IteratorMode::Start)
-            .map(Iterator::count)
-            .map(|val| if val > i64::MAX as usize {
-                // NOTE: this value is only used for metrics/diagnostics and
-                // aside from the fact that we will never encounter this case,
-                // it is good enough to return i64::MAX
-                error!("Column {} count is too large: {} for metrics, returning max.", C::NAME, val);
-                i64::MAX
-            } else { val as i64 })
-            .inspect(|updated| self.entry_counter.store(*updated, Ordering::Relaxed))
+    /// Returns the total size and the number of the live SST files
+    fn sst_files_size(&self) -> LedgerResult<(u64, usize)> {
+        let live_files = self.backend.live_files_metadata()?;
+        let cf_name = self.backend.cf_name(C::NAME);
+        Ok(live_files
+            .iter()
+            .filter(|file| file.column_family_name == cf_name)
+            .fold((0, 0), |(bytes, files), file| {
+                (bytes + file.size as u64, files + 1)
+            }))
     }
 
-    /// Increases entries counter if it's not [`DIRTY_COUNT`]
-    /// Otherwise just skips it until it is set
-    #[inline(always)]
-    pub fn try_increase_entry_counter(&self, by: u64) {
-        try_increase_entry_counter(&self.entry_counter, by);
+    /// See [crate::database::rocks_db::Rocks::flush_cf] for documentation.
+    pub fn flush(&self) -> LedgerResult<()> {
+        let cf = self.try_handle()?;
+        self.backend.flush_cf(&cf)
     }
 
-    /// Decreases entries counter if it's not [`DIRTY_COUNT`]
-    /// Otherwise just skips it until it is set
-    #[inline(always)]
-    pub fn try_decrease_entry_counter(&self, by: u64) {
-        try_decrease_entry_counter(&self.entry_counter, by);
+    /// Streams all entries of the column to `out` as length-prefixed
+    /// key/value frames, see [LedgerColumn::import].
+    /// Returns the number of exported entries.
+    pub fn export<W: Write>(&self, mut out: W) -> LedgerResult<u64> {
+        let cf = self.try_handle()?;
+        let mut iter = self.backend.raw_iterator_cf(&cf);
+        iter.seek_to_first();
+
+        let mut num_exported = 0;
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+            write_frame(&mut out, key)?;
+            write_frame(&mut out, value)?;
+            num_exported += 1;
+            iter.next();
+        }
+        iter.status()?;
+        out.flush()?;
+
+        Ok(num_exported)
     }
-}
 
-impl<C> LedgerColumn<C>
-where
-    C: TypedColumn + ColumnName,
-{
-    pub fn multi_get(
-        &self,
-        keys: Vec<C::Index>,
-    ) -> Vec<std::result::Result<Option<C::Type>, LedgerError>> {
-        let rocks_keys: Vec<_> =
-            keys.into_iter().map(|key| C::key(key)).collect();
-        {
-            let ref_rocks_keys: Vec<_> =
-                rocks_keys.iter().map(|k| &k[..]).collect();
-            let is_perf_enabled = maybe_enable_rocksdb_perf(
-                self.column_options.rocks_perf_sample_interval,
-                &self.read_perf_status,
-            );
-            let result = self
-                .backend
-                .multi_get_cf(self.handle(), ref_rocks_keys)
-                .into_iter()
-                .map(|r| match r {
-                    Ok(opt) => match opt {
-                        Some(pinnable_slice) => {
-                            Ok(Some(deserialize(pinnable_slice.as_ref())?))
-                        }
-                        None => Ok(None),
-                    },
-                    Err(e) => Err(e),
-                })
-                .collect::<Vec<std::result::Result<Option<_>, LedgerError>>>();
-            if let Some(op_start_instant) = is_perf_enabled {
-                // use multi-get instead
-                report_rocksdb_read_perf(
-                    C::NAME,
-                    PERF_METRIC_OP_NAME_MULTI_GET,
-                    &op_start_instant.elapsed(),
-                    &self.column_options,
-                );
+    /// Loads entries produced by [LedgerColumn::export] of the matching
+    /// column, overwriting entries with the same key.
+    /// Returns the number of imported entries.
+    pub fn import<R: Read>(&self, mut input: R) -> LedgerResult<u64> {
+        let cf = self.try_handle()?;
+        let _write = self.begin_counted_write();
+        let mut num_imported = 0;
+        let mut num_new = 0;
+        let mut batch = self.backend.batch();
+        while let Some(key) = read_frame(&mut input)? {
+            let value = read_frame(&mut input)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "missing value of the last entry",
+                )
+            })?;
+            if self.backend.get_pinned_cf(&cf, &key)?.is_none() {
+                num_new += 1;
             }
+            batch.put_cf(&cf, key, value);
+            num_imported += 1;
 
-            result
+            if batch.len() >= IMPORT_BATCH_SIZE {
+                self.write_import_batch(batch, num_new)?;
+                batch = self.backend.batch();
+                num_new = 0;
+            }
         }
-    }
+        self.write_import_batch(batch, num_new)?;
 
-    pub fn get(
-        &self,
-        key: C::Index,
-    ) -> std::result::Result<Option<C::Type>, LedgerError> {
-        self.get_raw(&C::key(key))
+        Ok(num_imported)
     }
 
-    pub fn get_raw(
+    fn write_import_batch(
         &self,
-        key: &[u8],
-    ) -> std::result::Result<Option<C::Type>, LedgerError> {
-        let mut result = Ok(None);
+        batch: rocksdb::WriteBatch,
+        num_new: u64,
+    ) -> LedgerResult<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
         let is_perf_enabled = maybe_enable_rocksdb_perf(
             self.column_options.rocks_perf_sample_interval,
-            &self.read_perf_status,
+            &self.write_perf_status,
         );
-        if let Some(pinnable_slice) =
-            self.backend.get_pinned_cf(self.handle(), key)?
-        {
-            let value = deserialize(pinnable_slice.as_ref())?;
-            result = Ok(Some(value))
-        }
-
+        let result = self.backend.write(batch);
         if let Some(op_start_instant) = is_perf_enabled {
-            report_rocksdb_read_perf(
+            report_rocksdb_write_perf(
                 C::NAME,
-                PERF_METRIC_OP_NAME_GET,
+                "import",
                 &op_start_instant.elapsed(),
                 &self.column_options,
+                &self.latencies,
             );
         }
-        result
+        self.clear_cache();
+        result?;
+
+        self.try_increase_entry_counter(num_new);
+        Ok(())
     }
 
-    pub fn put(
-        &self,
-        key: C::Index,
-        value: &C::Type,
-    ) -> std::result::Result<(), LedgerError> {
+    /// Bulk loads the externally generated SST files at `paths`, i.e. to
+    /// restore the column from a backup much faster than replaying puts.
+    /// The files must have been written with [rocksdb::SstFileWriter] for
+    /// a column with the same key format and ordering.
+    ///
+    /// Ingested entries overwrite the live ones with the same key. Ideally
+    /// the key ranges of the files don't overlap the live data though,
+    /// otherwise RocksDB has to flush the memtable and can't place the
+    /// files at the bottommost level, which slows down the ingestion and
+    /// later compactions.
+    ///
+    /// The number of new keys is unknown, so the entry counter is left
+    /// [`DIRTY_COUNT`] to be recounted on the next
+    /// [LedgerColumn::count_column_using_cache].
+    pub fn ingest_sst_files(&self, paths: &[PathBuf]) -> LedgerResult<()> {
+        let cf = self.try_handle()?;
         let is_perf_enabled = maybe_enable_rocksdb_perf(
             self.column_options.rocks_perf_sample_interval,
             &self.write_perf_status,
         );
-        let serialized_value = serialize(value)?;
-
-        let result =
-            self.backend
-                .put_cf(self.handle(), &C::key(key), &serialized_value);
-
+        let result = self.backend.ingest_external_file_cf(&cf, paths);
         if let Some(op_start_instant) = is_perf_enabled {
             report_rocksdb_write_perf(
                 C::NAME,
-                PERF_METRIC_OP_NAME_PUT,
+                "ingest",
                 &op_start_instant.elapsed(),
                 &self.column_options,
+                &self.latencies,
             );
         }
-        result
+        self.clear_cache();
+        result?;
+
+        self.mark_counter_dirty();
+        Ok(())
+    }
+
+    pub fn count_column_using_cache(&self) -> LedgerResult<i64> {
+        let cached = self.entry_counter.load(Ordering::Relaxed);
+        if cached != DIRTY_COUNT {
+            return Ok(cached);
+        }
+
+        let _lock = self.recount_lock.lock().expect("recount lock poisoned");
+        // Counted by another caller while we were waiting for the lock
+        let cached = self.entry_counter.load(Ordering::Relaxed);
+        if cached != DIRTY_COUNT {
+            return Ok(cached);
+        }
+        let generation = self.entry_counter_generation.load(Ordering::SeqCst);
+        self.count_entries()
+            .inspect(|count| self.store_recount(generation, *count))
+    }
+
+    /// Returns RocksDB's estimate of the number of entries, which doesn't
+    /// scan the column, i.e. for metrics that don't need an exact count.
+    /// Overwritten and deleted keys are only accounted for once they're
+    /// compacted. Neither reads nor updates the counter of
+    /// [Self::count_column_using_cache].
+    pub fn count_column_approx(&self) -> LedgerResult<i64> {
+        self.get_int_property(RocksProperties::ESTIMATE_NUM_KEYS)
+    }
+
+    /// Recomputes the number of entries on a background thread and stores it
+    /// into the cached counter, so that [Self::count_column_using_cache]
+    /// doesn't have to scan the column in the foreground.
+    ///
+    /// Counter updates and counted writes (see
+    /// [Self::begin_counted_write]) that race with the scan might not be
+    /// reflected in it, in that case the counter is left [`DIRTY_COUNT`]
+    /// instead of storing a stale value, so it's recomputed on the next
+    /// count.
+    pub fn recount_async(self: Arc<Self>) -> JoinHandle<LedgerResult<i64>>
+    where
+        C: Send + Sync + 'static,
+    {
+        thread::spawn(move || {
+            let generation =
+                self.entry_counter_generation.load(Ordering::SeqCst);
+            let count = self.count_entries()?;
+            self.store_recount(generation, count);
+            Ok(count)
+        })
+    }
+
+    /// Marks the start of a write that is followed by an entry counter
+    /// update, it ends once the returned guard is dropped after the update.
+    /// A recount whose scan overlaps with the write doesn't store its
+    /// count, since the write might be both scanned and counted by the
+    /// update, see [Self::recount_async].
+    pub fn begin_counted_write(&self) -> CountedWrite<'_> {
+        self.counted_writes_in_flight.fetch_add(1, Ordering::SeqCst);
+        CountedWrite {
+            entry_counter_generation: &self.entry_counter_generation,
+            counted_writes_in_flight: &self.counted_writes_in_flight,
+        }
+    }
+
+    /// Whether no counter update or counted write happened since
+    /// `generation` was read and none is in flight
+    fn is_counter_settled(&self, generation: u64) -> bool {
+        self.counted_writes_in_flight.load(Ordering::SeqCst) == 0
+            && self.entry_counter_generation.load(Ordering::SeqCst)
+                == generation
+    }
+
+    /// Stores the `count` of a scan that started at `generation`, or leaves
+    /// the counter [`DIRTY_COUNT`] if a write raced with it
+    fn store_recount(&self, generation: u64, count: i64) {
+        if self.is_counter_settled(generation) {
+            self.entry_counter.store(count, Ordering::SeqCst);
+            // A write may have slipped in between the check and the store
+            if self.is_counter_settled(generation) {
+                return;
+            }
+        }
+        self.entry_counter.store(DIRTY_COUNT, Ordering::SeqCst);
+    }
+
+    /// Compares the cached entry counter against a fresh count of the
+    /// entries and returns the mismatch, if any. A [`DIRTY_COUNT`] counter
+    /// isn't checked since it's recounted on the next read anyway, neither
+    /// is a negative one, which is reset by [Self::validate_counter].
+    ///
+    /// The counter is left as is, unless `repair` is set in which case it's
+    /// replaced by the actual count.
+    /// Mismatches are only reported if no counter update or counted write
+    /// raced with the scan, see [Self::recount_async].
+    pub fn verify_counter(
+        &self,
+        repair: bool,
+    ) -> LedgerResult<Option<CounterDiscrepancy>> {
+        self.validate_counter();
+        let generation = self.entry_counter_generation.load(Ordering::SeqCst);
+        let cached = self.entry_counter.load(Ordering::SeqCst);
+        if cached == DIRTY_COUNT {
+            return Ok(None);
+        }
+        let actual = self.count_entries()?;
+        if cached == actual || !self.is_counter_settled(generation) {
+            return Ok(None);
+        }
+
+        warn!(
+            "Entry counter {} of column {} doesn't match the {} entries",
+            cached,
+            C::NAME,
+            actual
+        );
+        if repair
+            && self
+                .entry_counter
+                .compare_exchange(
+                    cached,
+                    actual,
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            && !self.is_counter_settled(generation)
+        {
+            // An update slipped in between the check and the exchange
+            self.entry_counter.store(DIRTY_COUNT, Ordering::SeqCst);
+        }
+        Ok(Some(CounterDiscrepancy {
+            column: C::NAME,
+            cached,
+            actual,
+        }))
+    }
+
+    fn verify_integrity_with(
+        &self,
+        is_valid: impl Fn(&[u8]) -> bool,
+    ) -> LedgerResult<IntegrityReport<C::Index>> {
+        let cf = self.try_handle()?;
+        let mut report = IntegrityReport {
+            total: 0,
+            ok: 0,
+            corrupt_keys: vec![],
+        };
+        let iter = self.backend.iterator_cf::<C>(&cf, IteratorMode::Start);
+        for pair in iter {
+            let (key, value) = pair?;
+            report.total += 1;
+            if is_valid(&value) {
+                report.ok += 1;
+            } else {
+                report.corrupt_keys.push(C::index(&key));
+            }
+        }
+        Ok(report)
+    }
+
+    /// Buckets the sizes of the values iterated according to `mode`,
+    /// values are not deserialized.
+    pub fn value_size_histogram(
+        &self,
+        mode: IteratorMode<C::Index>,
+    ) -> LedgerResult<SizeHistogram> {
+        let cf = self.try_handle()?;
+        let mut histogram = SizeHistogram::default();
+        for pair in self.backend.iterator_cf::<C>(&cf, mode) {
+            let (_, value) = pair?;
+            histogram.record(value.len());
+        }
+        Ok(histogram)
+    }
+
+    fn count_entries(&self) -> LedgerResult<i64> {
+        #[cfg(test)]
+        self.num_count_scans.fetch_add(1, Ordering::Relaxed);
+        self
+            .iter(IteratorMode::Start)
+            .map(Iterator::count)
+            .map(|val| if val > i64::MAX as usize {
+                // NOTE: this value is only used for metrics/diagnostics and
+                // aside from the fact that we will never encounter this case,
+                // it is good enough to return i64::MAX
+                error!("Column {} count is too large: {} for metrics, returning max.", C::NAME, val);
+                i64::MAX
+            } else { val as i64 })
+    }
+
+    /// Increases entries counter if it's not [`DIRTY_COUNT`]
+    /// Otherwise just skips it until it is set
+    #[inline(always)]
+    pub fn try_increase_entry_counter(&self, by: u64) {
+        self.entry_counter_generation.fetch_add(1, Ordering::SeqCst);
+        try_increase_entry_counter(&self.entry_counter, by);
+    }
+
+    /// Decreases entries counter if it's not [`DIRTY_COUNT`]
+    /// Otherwise just skips it until it is set
+    #[inline(always)]
+    pub fn try_decrease_entry_counter(&self, by: u64) {
+        self.entry_counter_generation.fetch_add(1, Ordering::SeqCst);
+        try_decrease_entry_counter(&self.entry_counter, by);
+    }
+
+    /// Resets the entries counter to [`DIRTY_COUNT`] if it holds a negative
+    /// value, i.e. left behind by an interrupted decrease, so that it gets
+    /// recomputed on the next count instead of being reported.
+    /// Returns whether the counter was reset.
+    ///
+    /// Runs when the column is constructed, for all the columns of a ledger
+    /// on [crate::Ledger::open], and before [Self::verify_counter] compares
+    /// the counter against the entries.
+    pub fn validate_counter(&self) -> bool {
+        let current = self.entry_counter.load(Ordering::Acquire);
+        if current >= 0 || current == DIRTY_COUNT {
+            return false;
+        }
+
+        warn!(
+            "Invalid entry counter {} of column {}, resetting it",
+            current,
+            C::NAME
+        );
+        // In case the counter was fixed in between
+        self.entry_counter
+            .compare_exchange(
+                current,
+                DIRTY_COUNT,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .is_ok()
     }
 }
 
 impl<C> LedgerColumn<C>
 where
-    C: ProtobufColumn + ColumnName,
+    C: TypedColumn + ColumnName,
 {
-    pub fn get_protobuf_or_bincode<T: DeserializeOwned + Into<C::Type>>(
+    /// Values found in the column cache are served from it, only the
+    /// remaining keys are read from RocksDB with a single multi-get.
+    pub fn multi_get(
         &self,
-        key: C::Index,
-    ) -> std::result::Result<Option<C::Type>, LedgerError> {
-        self.get_raw_protobuf_or_bincode::<T>(&C::key(key))
+        keys: Vec<C::Index>,
+    ) -> Vec<std::result::Result<Option<C::Type>, LedgerError>> {
+        let rocks_keys: Vec<_> =
+            keys.into_iter().map(|key| C::key(key)).collect();
+        self.multi_get_keys(&rocks_keys)
     }
 
-    pub(crate) fn get_raw_protobuf_or_bincode<
-        T: DeserializeOwned + Into<C::Type>,
-    >(
+    /// Same as [LedgerColumn::multi_get], but fails with the first error
+    /// instead of reporting errors per key
+    pub fn multi_get_all(
         &self,
-        key: &[u8],
-    ) -> std::result::Result<Option<C::Type>, LedgerError> {
+        keys: Vec<C::Index>,
+    ) -> LedgerResult<Vec<Option<C::Type>>> {
+        self.multi_get(keys).into_iter().collect()
+    }
+
+    /// Same as [LedgerColumn::multi_get_all], but only returns the entries
+    /// that were found along with their keys, in the order of `keys`
+    pub fn multi_get_present(
+        &self,
+        keys: Vec<C::Index>,
+    ) -> LedgerResult<Vec<(C::Index, C::Type)>> {
+        let rocks_keys: Vec<_> =
+            keys.into_iter().map(|key| C::key(key)).collect();
+        let mut present = Vec::new();
+        for (key, result) in
+            rocks_keys.iter().zip(self.multi_get_keys(&rocks_keys))
+        {
+            if let Some(value) = result? {
+                present.push((C::index(key), value));
+            }
+        }
+        Ok(present)
+    }
+
+    /// Returns up to `limit` entries strictly after `after`, or from the
+    /// start of the column if it's `None`, along with the cursor to pass as
+    /// `after` to get the next page. The cursor is the last returned index,
+    /// or `None` once the column is exhausted.
+    /// Each call seeks to the cursor, so no iterator is held in between
+    /// pages and entries written meanwhile are picked up.
+    pub fn page(
+        &self,
+        after: Option<C::Index>,
+        limit: usize,
+    ) -> LedgerResult<Page<C>> {
+        if limit == 0 {
+            return Ok((Vec::new(), after));
+        }
+        let cf = self.try_handle()?;
+        let mut iter = self.backend.raw_iterator_cf(&cf);
+        match after.map(C::key) {
+            Some(after) => {
+                iter.seek(&after);
+                if iter.key() == Some(after.as_slice()) {
+                    iter.next();
+                }
+            }
+            None => iter.seek_to_first(),
+        }
+
+        let mut entries = Vec::with_capacity(limit);
+        let mut last_key = None;
+        while entries.len() < limit {
+            let (Some(key), Some(value)) = (iter.key(), iter.value()) else {
+                break;
+            };
+            let value = C::Codec::decode(value)
+                .map_err(|err| LedgerError::deserialize(C::NAME, key, err))?;
+            entries.push((C::index(key), value));
+            last_key = Some(key.to_vec());
+            iter.next();
+        }
+        iter.status()?;
+
+        let cursor = if iter.valid() {
+            last_key.map(|key| C::index(&key))
+        } else {
+            None
+        };
+        Ok((entries, cursor))
+    }
+
+    /// Returns the entry with the lowest index, `None` if the column is empty
+    pub fn first(&self) -> LedgerResult<Option<(C::Index, C::Type)>> {
+        self.boundary_entry(|iter| iter.seek_to_first())
+    }
+
+    /// Returns the entry with the highest index, i.e. the most recent one of
+    /// slot keyed columns, `None` if the column is empty
+    pub fn last(&self) -> LedgerResult<Option<(C::Index, C::Type)>> {
+        self.boundary_entry(|iter| iter.seek_to_last())
+    }
+
+    fn boundary_entry(
+        &self,
+        seek: impl FnOnce(&mut DBRawIterator),
+    ) -> LedgerResult<Option<(C::Index, C::Type)>> {
+        let cf = self.try_handle()?;
+        let mut iter = self.backend.raw_iterator_cf(&cf);
+        seek(&mut iter);
+        let (Some(key), Some(value)) = (iter.key(), iter.value()) else {
+            iter.status()?;
+            return Ok(None);
+        };
+        let value = C::Codec::decode(value)
+            .map_err(|err| LedgerError::deserialize(C::NAME, key, err))?;
+        Ok(Some((C::index(key), value)))
+    }
+
+    fn multi_get_keys(
+        &self,
+        rocks_keys: &[Vec<u8>],
+    ) -> Vec<std::result::Result<Option<C::Type>, LedgerError>> {
+        let Ok(cf) = self.try_handle() else {
+            return rocks_keys
+                .iter()
+                .map(|_| Err(LedgerError::ColumnNotOpened(C::NAME)))
+                .collect();
+        };
+        let mut results: Vec<_> = rocks_keys
+            .iter()
+            .map(|key| self.get_cached(key).map(|value| Ok(Some(value))))
+            .collect();
+        let missing: Vec<_> = rocks_keys
+            .iter()
+            .zip(&results)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(key, _)| &key[..])
+            .collect();
+        if missing.is_empty() {
+            return results.into_iter().flatten().collect();
+        }
+
+        let versions: Vec<_> =
+            missing.iter().map(|key| self.cache_version(key)).collect();
+        let span = RocksDbOpSpan::enter(
+            C::NAME,
+            PERF_METRIC_OP_NAME_MULTI_GET,
+            missing.iter().map(|key| key.len()).sum(),
+        );
+        let mut value_size = 0;
         let is_perf_enabled = maybe_enable_rocksdb_perf(
             self.column_options.rocks_perf_sample_interval,
             &self.read_perf_status,
         );
-        let result = self.backend.get_pinned_cf(self.handle(), key);
+        let fetched = self
+            .backend
+            .multi_get_cf(&cf, missing.clone())
+            .into_iter()
+            .zip(missing.into_iter().zip(versions))
+            .map(|(r, (key, version))| match r {
+                Ok(opt) => match opt {
+                    Some(pinnable_slice) => {
+                        value_size += pinnable_slice.len();
+                        C::Codec::decode(pinnable_slice.as_ref())
+                            .map(|value| {
+                                self.fill_cache(key, version, &value);
+                                Some(value)
+                            })
+                            .map_err(|err| {
+                                LedgerError::deserialize(C::NAME, key, err)
+                            })
+                    }
+                    None => Ok(None),
+                },
+                Err(e) => Err(e),
+            })
+            .collect::<Vec<std::result::Result<Option<_>, LedgerError>>>();
         if let Some(op_start_instant) = is_perf_enabled {
+            // use multi-get instead
             report_rocksdb_read_perf(
                 C::NAME,
-                PERF_METRIC_OP_NAME_GET,
+                PERF_METRIC_OP_NAME_MULTI_GET,
                 &op_start_instant.elapsed(),
                 &self.column_options,
+                &self.latencies,
             );
         }
+        span.record_value_size(value_size);
 
-        if let Some(pinnable_slice) = result? {
-            let value = match C::Type::decode(pinnable_slice.as_ref()) {
-                Ok(value) => value,
-                Err(_) => deserialize::<T>(pinnable_slice.as_ref())?.into(),
-            };
-            Ok(Some(value))
-        } else {
-            Ok(None)
-        }
+        let mut fetched = fetched.into_iter();
+        results
+            .iter_mut()
+            .filter(|cached| cached.is_none())
+            .for_each(|entry| *entry = fetched.next());
+        results.into_iter().flatten().collect()
     }
 
-    pub fn get_protobuf(
+    pub fn get(
         &self,
         key: C::Index,
-    ) -> Result<Option<C::Type>, LedgerError> {
+    ) -> std::result::Result<Option<C::Type>, LedgerError> {
+        self.get_raw(&C::key(key))
+    }
+
+    pub fn get_raw(
+        &self,
+        key: &[u8],
+    ) -> std::result::Result<Option<C::Type>, LedgerError> {
+        let cf = self.try_handle()?;
+        if let Some(value) = self.get_cached(key) {
+            return Ok(Some(value));
+        }
+        let version = self.cache_version(key);
+        let mut result = Ok(None);
+        let span =
+            RocksDbOpSpan::enter(C::NAME, PERF_METRIC_OP_NAME_GET, key.len());
         let is_perf_enabled = maybe_enable_rocksdb_perf(
             self.column_options.rocks_perf_sample_interval,
             &self.read_perf_status,
         );
-        let result = self.backend.get_pinned_cf(self.handle(), &C::key(key));
+        if let Some(pinnable_slice) = self.backend.get_pinned_cf(&cf, key)? {
+            span.record_value_size(pinnable_slice.len());
+            let value = C::Codec::decode(pinnable_slice.as_ref())
+                .map_err(|err| LedgerError::deserialize(C::NAME, key, err))?;
+            self.fill_cache(key, version, &value);
+            result = Ok(Some(value))
+        }
+
         if let Some(op_start_instant) = is_perf_enabled {
             report_rocksdb_read_perf(
                 C::NAME,
                 PERF_METRIC_OP_NAME_GET,
                 &op_start_instant.elapsed(),
                 &self.column_options,
+                &self.latencies,
             );
         }
+        result
+    }
 
-        if let Some(pinnable_slice) = result? {
-            Ok(Some(C::Type::decode(pinnable_slice.as_ref())?))
-        } else {
-            Ok(None)
+    /// Scans the whole column and tries to deserialize every value, collecting
+    /// the keys of the values that fail instead of stopping at the first one.
+    pub fn verify_integrity(&self) -> LedgerResult<IntegrityReport<C::Index>> {
+        self.verify_integrity_with(|value| C::Codec::decode(value).is_ok())
+    }
+
+    /// Iterates the column starting at `iterator_mode` and returns the first
+    /// entry for which `pred` returns `true`.
+    /// Values are deserialized one at a time and iteration stops at the
+    /// first match, so the rest of the column is neither read nor decoded.
+    pub fn find<F>(
+        &self,
+        iterator_mode: IteratorMode<C::Index>,
+        mut pred: F,
+    ) -> LedgerResult<Option<(C::Index, C::Type)>>
+    where
+        F: FnMut(&C::Index, &C::Type) -> bool,
+    {
+        let cf = self.try_handle()?;
+        let iter = self.backend.iterator_cf::<C>(&cf, iterator_mode);
+        for pair in iter {
+            let (key, value) = pair?;
+            let index = C::index(&key);
+            let value = C::Codec::decode(&value)
+                .map_err(|err| LedgerError::deserialize(C::NAME, &key, err))?;
+            if pred(&index, &value) {
+                return Ok(Some((index, value)));
+            }
         }
+        Ok(None)
     }
 
-    pub fn put_protobuf(
+    /// Iterates the column starting at `iterator_mode` and sums `f` applied
+    /// to every value, i.e. to aggregate a field over a slot range.
+    /// Values are streamed without collecting them, the first one that fails
+    /// to deserialize aborts the sum with an error.
+    pub fn sum_by<T, F>(
         &self,
-        key: C::Index,
-        value: &C::Type,
-    ) -> std::result::Result<(), LedgerError> {
-        let mut buf = Vec::with_capacity(value.encoded_len());
-        value.encode(&mut buf)?;
+        iterator_mode: IteratorMode<C::Index>,
+        f: F,
+    ) -> LedgerResult<T>
+    where
+        T: std::iter::Sum,
+        F: Fn(&C::Type) -> T,
+    {
+        let cf = self.try_handle()?;
+        self.backend
+            .iterator_cf::<C>(&cf, iterator_mode)
+            .map(|pair| {
+                let (key, value) = pair?;
+                let value = C::Codec::decode(&value).map_err(|err| {
+                    LedgerError::deserialize(C::NAME, &key, err)
+                })?;
+                Ok(f(&value))
+            })
+            .sum()
+    }
+
+    /// Iterates the column starting at `iterator_mode` and deletes the
+    /// entries matching `pred`, returns the number of deleted entries.
+    /// Matches are deleted in batches while streaming, so if a value fails
+    /// to deserialize, the error is returned but the matches before it are
+    /// already deleted.
+    pub fn delete_if<F>(
+        &self,
+        iterator_mode: IteratorMode<C::Index>,
+        pred: F,
+    ) -> LedgerResult<u64>
+    where
+        F: Fn(&C::Index, &C::Type) -> bool,
+    {
+        let cf = self.try_handle()?;
+        let mut num_deleted = 0;
+        let mut keys = Vec::new();
+        for pair in self.backend.iterator_cf::<C>(&cf, iterator_mode) {
+            let (key, value) = pair?;
+            let index = C::index(&key);
+            let value = C::Codec::decode(&value)
+                .map_err(|err| LedgerError::deserialize(C::NAME, &key, err))?;
+            if !pred(&index, &value) {
+                continue;
+            }
+            keys.push(key);
+            if keys.len() == DELETE_BATCH_SIZE {
+                num_deleted +=
+                    self.delete_keys(&cf, std::mem::take(&mut keys))?;
+            }
+        }
+        num_deleted += self.delete_keys(&cf, keys)?;
+        Ok(num_deleted)
+    }
+
+    /// Deletes `keys` in a single batch and accounts for them
+    fn delete_keys(
+        &self,
+        cf: &CfHandle,
+        keys: Vec<Box<[u8]>>,
+    ) -> LedgerResult<u64> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        let _write = self.begin_counted_write();
+        let mut batch = self.backend.batch();
+        for key in &keys {
+            batch.delete_cf(cf, key);
+        }
 
         let is_perf_enabled = maybe_enable_rocksdb_perf(
             self.column_options.rocks_perf_sample_interval,
             &self.write_perf_status,
         );
-        let result = self.backend.put_cf(self.handle(), &C::key(key), &buf);
+        let result = self.backend.write(batch);
         if let Some(op_start_instant) = is_perf_enabled {
             report_rocksdb_write_perf(
                 C::NAME,
-                PERF_METRIC_OP_NAME_PUT,
+                "delete_if",
                 &op_start_instant.elapsed(),
                 &self.column_options,
+                &self.latencies,
             );
         }
+        result?;
 
-        result
+        for key in &keys {
+            self.invalidate_cached(key);
+        }
+        let num_deleted = keys.len() as u64;
+        self.try_decrease_entry_counter(num_deleted);
+        Ok(num_deleted)
     }
 
-    pub fn iter_protobuf(
+    pub fn put(
         &self,
-        iterator_mode: IteratorMode<C::Index>,
-    ) -> impl Iterator<Item = LedgerResult<(C::Index, C::Type)>> + '_ {
-        let cf = self.handle();
-        let iter = self.backend.iterator_cf::<C>(cf, iterator_mode);
-        iter.map(|pair| {
-            let (key, value) = pair?;
-            let decoded = C::Type::decode(value.as_ref())?;
-            Ok((C::index(&key), decoded))
-        })
+        key: C::Index,
+        value: &C::Type,
+    ) -> std::result::Result<(), LedgerError> {
+        self.put_with_opts(key, value, WriteDurability::default())
     }
-}
 
-impl<C> LedgerColumn<C>
-where
-    C: ColumnIndexDeprecation + ColumnName,
-{
-    pub(crate) fn iter_current_index_filtered(
+    /// Same as [LedgerColumn::put] with the given `durability`,
+    /// see [WriteDurability] for the tradeoffs.
+    pub fn put_with_opts(
         &self,
-        iterator_mode: IteratorMode<C::Index>,
-    ) -> impl Iterator<Item = (C::Index, Box<[u8]>)> + '_ {
-        let cf = self.handle();
-        let iter = self.backend.iterator_cf::<C>(cf, iterator_mode);
-        iter.filter_map(|pair| {
-            let (key, value) = pair.unwrap();
-            C::try_current_index(&key).ok().map(|index| (index, value))
-        })
+        key: C::Index,
+        value: &C::Type,
+        durability: WriteDurability,
+    ) -> std::result::Result<(), LedgerError> {
+        self.put_raw(&C::key(key), value, durability).map(|_| ())
     }
-}
 
-/// Increases entries counter if it's not [`DIRTY_COUNT`]
-/// Otherwise just skips it until it is set
-pub fn try_increase_entry_counter(entry_counter: &AtomicI64, by: u64) {
-    loop {
-        let prev = entry_counter.load(Ordering::Acquire);
-        if prev == DIRTY_COUNT {
-            return;
+    /// Returns the value stored under `key`, or computes it with `f`,
+    /// stores it and increases the entry counter if there is none.
+    /// The returned value is the one stored in the column.
+    ///
+    /// Concurrent callers on the same column are serialized, also across
+    /// handles of the column, so the value inserted first wins and `f` is
+    /// only called if the key is absent.
+    /// Writes that bypass this method (i.e. [LedgerColumn::put]) are not
+    /// guarded and may still overwrite the value.
+    pub fn get_or_insert_with<F>(
+        &self,
+        key: C::Index,
+        f: F,
+    ) -> LedgerResult<C::Type>
+    where
+        F: FnOnce() -> C::Type,
+    {
+        let key = C::key(key);
+        let _lock = self.insert_lock.lock().expect("insert lock poisoned");
+        if let Some(value) = self.get_raw(&key)? {
+            return Ok(value);
         }
 
-        // In case value changed to [`DIRTY_COUNT`] in between
-        if entry_counter
-            .compare_exchange(
-                prev,
-                prev + by as i64,
-                Ordering::AcqRel,
-                Ordering::Relaxed,
-            )
-            .is_ok()
-        {
-            return;
-        }
+        let value = f();
+        let _write = self.begin_counted_write();
+        self.put_raw(&key, &value, WriteDurability::default())?;
+        self.try_increase_entry_counter(1);
+        Ok(value)
     }
-}
 
-/// Decreases entries counter if it's not [`DIRTY_COUNT`]
-/// Otherwise just skips it until it is set
-pub fn try_decrease_entry_counter(entry_counter: &AtomicI64, by: u64) {
-    loop {
+    /// Stores `value` under `key` unless the stored value has the same or a
+    /// higher version per `version_of`, i.e. to drop stale values arriving
+    /// out of order. Returns whether `value` was written, increases the
+    /// entry counter if the key was absent.
+    ///
+    /// The read and the write are serialized with the other conditional
+    /// writes on the same column through any of its handles, like
+    /// [LedgerColumn::get_or_insert_with]. Writes that bypass them
+    /// (i.e. [LedgerColumn::put]) are not guarded.
+    pub fn put_if_newer<F>(
+        &self,
+        key: C::Index,
+        value: &C::Type,
+        version_of: F,
+    ) -> LedgerResult<bool>
+    where
+        F: Fn(&C::Type) -> u64,
+    {
+        let key = C::key(key);
+        let _lock = self.insert_lock.lock().expect("insert lock poisoned");
+        let existing = self.get_raw(&key)?;
+        if let Some(existing) = &existing {
+            if version_of(existing) >= version_of(value) {
+                return Ok(false);
+            }
+        }
+
+        let _write = self.begin_counted_write();
+        self.put_raw(&key, value, WriteDurability::default())?;
+        if existing.is_none() {
+            self.try_increase_entry_counter(1);
+        }
+        Ok(true)
+    }
+
+    /// Same as [LedgerColumn::put], returns the size of the written
+    /// serialized value.
+    pub fn put_accounted(
+        &self,
+        key: C::Index,
+        value: &C::Type,
+    ) -> LedgerResult<usize> {
+        self.put_raw(&C::key(key), value, WriteDurability::default())
+    }
+
+    /// Writes all `entries` within a single batch, returns the sizes of the
+    /// written serialized values in the order of `entries`.
+    pub fn multi_put_accounted(
+        &self,
+        entries: Vec<(C::Index, C::Type)>,
+    ) -> LedgerResult<Vec<usize>> {
+        let cf = self.try_handle()?;
+        let mut batch = self.backend.batch();
+        let mut sizes = Vec::with_capacity(entries.len());
+        let mut written = Vec::with_capacity(entries.len());
+        let mut total_bytes = 0;
+        for (key, value) in entries {
+            let key = C::key(key);
+            let serialized_value = C::Codec::encode(&value)?;
+            sizes.push(serialized_value.len());
+            total_bytes += key.len() + serialized_value.len();
+            batch.put_cf(&cf, &key, serialized_value);
+            let version = self.cache_version(&key);
+            written.push((key, version, value));
+        }
+        self.throttle_write(total_bytes)?;
+
+        let is_perf_enabled = maybe_enable_rocksdb_perf(
+            self.column_options.rocks_perf_sample_interval,
+            &self.write_perf_status,
+        );
+        let result = self.backend.write(batch);
+        if let Some(op_start_instant) = is_perf_enabled {
+            report_rocksdb_write_perf(
+                C::NAME,
+                "multi_put",
+                &op_start_instant.elapsed(),
+                &self.column_options,
+                &self.latencies,
+            );
+        }
+        result?;
+
+        for (key, version, value) in written {
+            self.fill_written_cache(&key, version, &value);
+        }
+        Ok(sizes)
+    }
+
+    /// Returns the size of the written serialized value
+    fn put_raw(
+        &self,
+        key: &[u8],
+        value: &C::Type,
+        durability: WriteDurability,
+    ) -> LedgerResult<usize> {
+        let cf = self.try_handle()?;
+        let serialized_value = C::Codec::encode(value)?;
+        self.throttle_write(key.len() + serialized_value.len())?;
+        let span =
+            RocksDbOpSpan::enter(C::NAME, PERF_METRIC_OP_NAME_PUT, key.len());
+        span.record_value_size(serialized_value.len());
+        let is_perf_enabled = maybe_enable_rocksdb_perf(
+            self.column_options.rocks_perf_sample_interval,
+            &self.write_perf_status,
+        );
+
+        let version = self.cache_version(key);
+        let result = self.backend.put_cf_opt(
+            &cf,
+            key,
+            &serialized_value,
+            &durability.to_rocksdb_write_options(),
+        );
+
+        if let Some(op_start_instant) = is_perf_enabled {
+            report_rocksdb_write_perf(
+                C::NAME,
+                PERF_METRIC_OP_NAME_PUT,
+                &op_start_instant.elapsed(),
+                &self.column_options,
+                &self.latencies,
+            );
+        }
+        match result {
+            Ok(()) => self.fill_written_cache(key, version, value),
+            Err(_) => self.invalidate_cached(key),
+        }
+        result.map(|_| serialized_value.len())
+    }
+
+    fn get_cached(&self, key: &[u8]) -> Option<C::Type> {
+        self.cache.as_ref()?.get(key)
+    }
+
+    /// Version of the cache entry of `key`, see [ColumnCache::version]
+    fn cache_version(&self, key: &[u8]) -> Option<u64> {
+        self.cache.as_ref().map(|cache| cache.version(key))
+    }
+
+    /// Caches `value` read from the column, see [ColumnCache::fill]
+    fn fill_cache(&self, key: &[u8], version: Option<u64>, value: &C::Type) {
+        if let (Some(cache), Some(version)) = (&self.cache, version) {
+            cache.fill(key, version, value.clone());
+        }
+    }
+
+    /// Caches `value` written to the column, see [ColumnCache::fill_written]
+    fn fill_written_cache(
+        &self,
+        key: &[u8],
+        version: Option<u64>,
+        value: &C::Type,
+    ) {
+        if let (Some(cache), Some(version)) = (&self.cache, version) {
+            cache.fill_written(key, version, value.clone());
+        }
+    }
+}
+
+impl<C> LedgerColumn<C>
+where
+    C: ProtobufColumn + ColumnName,
+{
+    pub fn get_protobuf_or_bincode<T: DeserializeOwned + Into<C::Type>>(
+        &self,
+        key: C::Index,
+    ) -> std::result::Result<Option<C::Type>, LedgerError> {
+        self.get_raw_protobuf_or_bincode::<T>(&C::key(key))
+    }
+
+    pub(crate) fn get_raw_protobuf_or_bincode<
+        T: DeserializeOwned + Into<C::Type>,
+    >(
+        &self,
+        key: &[u8],
+    ) -> std::result::Result<Option<C::Type>, LedgerError> {
+        let cf = self.try_handle()?;
+        let is_perf_enabled = maybe_enable_rocksdb_perf(
+            self.column_options.rocks_perf_sample_interval,
+            &self.read_perf_status,
+        );
+        let result = self.backend.get_pinned_cf(&cf, key);
+        if let Some(op_start_instant) = is_perf_enabled {
+            report_rocksdb_read_perf(
+                C::NAME,
+                PERF_METRIC_OP_NAME_GET,
+                &op_start_instant.elapsed(),
+                &self.column_options,
+                &self.latencies,
+            );
+        }
+
+        if let Some(pinnable_slice) = result? {
+            let value = match C::Type::decode(pinnable_slice.as_ref()) {
+                Ok(value) => value,
+                Err(_) => deserialize::<T>(pinnable_slice.as_ref())?.into(),
+            };
+            Ok(Some(value))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get_protobuf(
+        &self,
+        key: C::Index,
+    ) -> Result<Option<C::Type>, LedgerError> {
+        let cf = self.try_handle()?;
+        let is_perf_enabled = maybe_enable_rocksdb_perf(
+            self.column_options.rocks_perf_sample_interval,
+            &self.read_perf_status,
+        );
+        let result = self.backend.get_pinned_cf(&cf, &C::key(key));
+        if let Some(op_start_instant) = is_perf_enabled {
+            report_rocksdb_read_perf(
+                C::NAME,
+                PERF_METRIC_OP_NAME_GET,
+                &op_start_instant.elapsed(),
+                &self.column_options,
+                &self.latencies,
+            );
+        }
+
+        if let Some(pinnable_slice) = result? {
+            Ok(Some(C::Type::decode(pinnable_slice.as_ref())?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn put_protobuf(
+        &self,
+        key: C::Index,
+        value: &C::Type,
+    ) -> std::result::Result<(), LedgerError> {
+        let cf = self.try_handle()?;
+        let mut buf = Vec::with_capacity(value.encoded_len());
+        value.encode(&mut buf)?;
+        let key = C::key(key);
+        self.throttle_write(key.len() + buf.len())?;
+
+        let is_perf_enabled = maybe_enable_rocksdb_perf(
+            self.column_options.rocks_perf_sample_interval,
+            &self.write_perf_status,
+        );
+        let result = self.backend.put_cf(&cf, &key, &buf);
+        if let Some(op_start_instant) = is_perf_enabled {
+            report_rocksdb_write_perf(
+                C::NAME,
+                PERF_METRIC_OP_NAME_PUT,
+                &op_start_instant.elapsed(),
+                &self.column_options,
+                &self.latencies,
+            );
+        }
+
+        result
+    }
+
+    /// Same as [LedgerColumn::verify_integrity] for protobuf encoded values
+    pub fn verify_integrity_protobuf(
+        &self,
+    ) -> LedgerResult<IntegrityReport<C::Index>> {
+        self.verify_integrity_with(|value| C::Type::decode(value).is_ok())
+    }
+
+    pub fn iter_protobuf(
+        &self,
+        iterator_mode: IteratorMode<C::Index>,
+    ) -> impl Iterator<Item = LedgerResult<(C::Index, C::Type)>> + '_ {
+        // Yields a single error if the column wasn't opened
+        let (iter, error) = match self.try_handle() {
+            Ok(cf) => (
+                Some(self.backend.iterator_cf::<C>(&cf, iterator_mode)),
+                None,
+            ),
+            Err(err) => (None, Some(err)),
+        };
+        error
+            .into_iter()
+            .map(Err)
+            .chain(iter.into_iter().flatten().map(|pair| {
+                let (key, value) = pair?;
+                let decoded = C::Type::decode(value.as_ref())?;
+                Ok((C::index(&key), decoded))
+            }))
+    }
+}
+
+impl<C> LedgerColumn<C>
+where
+    C: MergeColumn + ColumnName,
+{
+    /// Atomically adds `delta` to the value stored at `key` (treated as 0
+    /// if missing) via a RocksDB merge, so concurrent updates aren't lost.
+    /// The result is read with [LedgerColumn::get].
+    pub fn merge(&self, key: C::Index, delta: i64) -> LedgerResult<()> {
+        let cf = self.try_handle()?;
+        let is_perf_enabled = maybe_enable_rocksdb_perf(
+            self.column_options.rocks_perf_sample_interval,
+            &self.write_perf_status,
+        );
+        let key = C::key(key);
+        let result = self.backend.merge_cf(&cf, &key, &delta.to_le_bytes());
+        if let Some(op_start_instant) = is_perf_enabled {
+            report_rocksdb_write_perf(
+                C::NAME,
+                "merge",
+                &op_start_instant.elapsed(),
+                &self.column_options,
+                &self.latencies,
+            );
+        }
+        self.invalidate_cached(&key);
+        result
+    }
+}
+
+impl<C> LedgerColumn<C>
+where
+    C: ColumnIndexDeprecation + ColumnName,
+{
+    pub(crate) fn iter_current_index_filtered(
+        &self,
+        iterator_mode: IteratorMode<C::Index>,
+    ) -> LedgerResult<impl Iterator<Item = (C::Index, Box<[u8]>)> + '_> {
+        let cf = self.try_handle()?;
+        let iter = self.backend.iterator_cf::<C>(&cf, iterator_mode);
+        Ok(iter.filter_map(|pair| {
+            let (key, value) = pair.unwrap();
+            C::try_current_index(&key).ok().map(|index| (index, value))
+        }))
+    }
+
+    /// Deletes the entries whose key isn't in the current index format,
+    /// i.e. to finish a migration away from the deprecated one, and compacts
+    /// the range they spanned. Returns the number of deleted entries.
+    pub fn purge_deprecated_indexes(&self) -> LedgerResult<u64> {
+        let cf = self.try_handle()?;
+        let _write = self.begin_counted_write();
+        let mut num_purged = 0;
+        // Keys are iterated in order, so the last one is the largest
+        let mut first_key = None;
+        let mut last_key = None;
+        let mut batch = self.backend.batch();
+        let mut batch_len = 0;
+        for pair in self.backend.iterator_cf::<C>(&cf, IteratorMode::Start) {
+            let (key, _) = pair?;
+            if C::try_current_index(&key).is_ok() {
+                continue;
+            }
+            batch.delete_cf(&cf, &key);
+            batch_len += 1;
+            if first_key.is_none() {
+                first_key = Some(key.clone());
+            }
+            last_key = Some(key);
+
+            if batch_len == DELETE_BATCH_SIZE {
+                let full_batch =
+                    std::mem::replace(&mut batch, self.backend.batch());
+                self.backend.write(full_batch)?;
+                // Accounted per batch, so that a failing write doesn't leave
+                // the deletes of the earlier batches uncounted
+                self.try_decrease_entry_counter(batch_len as u64);
+                num_purged += batch_len as u64;
+                batch_len = 0;
+            }
+        }
+        if batch_len > 0 {
+            self.backend.write(batch)?;
+            self.try_decrease_entry_counter(batch_len as u64);
+            num_purged += batch_len as u64;
+        }
+        // Cached values are keyed by current format keys, so they are not
+        // affected
+
+        if num_purged > 0 {
+            self.backend.compact_range_cf(&cf, first_key, last_key);
+        }
+        Ok(num_purged)
+    }
+}
+
+/// Increases entries counter if it's not [`DIRTY_COUNT`]
+/// Otherwise just skips it until it is set
+pub fn try_increase_entry_counter(entry_counter: &AtomicI64, by: u64) {
+    loop {
+        let prev = entry_counter.load(Ordering::Acquire);
+        if prev == DIRTY_COUNT {
+            return;
+        }
+
+        // In case value changed to [`DIRTY_COUNT`] in between
+        if entry_counter
+            .compare_exchange(
+                prev,
+                prev + by as i64,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+        {
+            return;
+        }
+    }
+}
+
+/// Decreases entries counter if it's not [`DIRTY_COUNT`]
+/// Otherwise just skips it until it is set
+pub fn try_decrease_entry_counter(entry_counter: &AtomicI64, by: u64) {
+    loop {
         let prev = entry_counter.load(Ordering::Acquire);
         if prev == DIRTY_COUNT {
             return;
@@ -604,3 +2035,1542 @@ pub fn try_decrease_entry_counter(entry_counter: &AtomicI64, by: u64) {
         }
     }
 }
+
+fn write_frame<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len()).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "frame is too large")
+    })?;
+    out.write_all(&len.to_le_bytes())?;
+    out.write_all(bytes)
+}
+
+/// Reads a single frame written by [write_frame],
+/// returns `None` if `input` ended right before it
+fn read_frame<R: Read>(input: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        match input.read(&mut len[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+    input.read_exact(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bincode::serialize;
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Signature};
+    use solana_storage_proto::convert::generated;
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+    use crate::{
+        database::{
+            codec::Borsh,
+            columns::{self as cf, SlotColumn},
+            db::Database,
+            iterator::IteratorDirection,
+            meta::AddressSignatureMeta,
+            options::{
+                LedgerColumnOptions, LedgerCompactionStyle, LedgerOptions,
+                RateLimitMode, WriteRateLimit,
+            },
+            rocks_db::Rocks,
+            write_batch::BatchOp,
+        },
+        errors::CodecError,
+    };
+
+    /// Opens a database in a temporary directory, which is removed once the
+    /// returned [TempDir] is dropped
+    fn open_db(options: LedgerOptions) -> (TempDir, Database) {
+        let temp_dir = tempdir().unwrap();
+        let db = Database::open(temp_dir.path(), options).unwrap();
+        (temp_dir, db)
+    }
+
+    /// Opens a column of a database with the default options, see [open_db]
+    fn open_column<C: Column + ColumnName>() -> (TempDir, LedgerColumn<C>) {
+        let (temp_dir, db) = open_db(LedgerOptions::default());
+        (temp_dir, db.column::<C>())
+    }
+
+    #[test]
+    fn test_get_next_and_prev_from() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+
+        // Empty column
+        assert!(column.get_next_from(0).unwrap().is_none());
+        assert!(column.get_prev_from(u64::MAX).unwrap().is_none());
+
+        // Leave gaps between slots as truncation would
+        for slot in [2, 5, 9] {
+            column.put(slot, &(slot as i64)).unwrap();
+        }
+
+        let next = |slot| column.get_next_from(slot).unwrap().map(|(s, _)| s);
+        let prev = |slot| column.get_prev_from(slot).unwrap().map(|(s, _)| s);
+
+        assert_eq!(next(0), Some(2));
+        assert_eq!(next(2), Some(2));
+        assert_eq!(next(3), Some(5));
+        assert_eq!(next(9), Some(9));
+        assert_eq!(next(10), None);
+
+        assert_eq!(prev(1), None);
+        assert_eq!(prev(2), Some(2));
+        assert_eq!(prev(8), Some(5));
+        assert_eq!(prev(u64::MAX), Some(9));
+
+        let (slot, value) = column.get_next_from(6).unwrap().unwrap();
+        assert_eq!(slot, 9);
+        assert_eq!(deserialize::<i64>(&value).unwrap(), 9);
+    }
+
+    #[test]
+    fn test_iter_raw() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+
+        let slots = [3, 7, 300];
+        for slot in slots {
+            column.put(slot, &(slot as i64)).unwrap();
+        }
+
+        let entries = column
+            .iter_raw(IteratorMode::Start)
+            .collect::<LedgerResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(entries.len(), slots.len());
+        for (slot, (key, value)) in slots.into_iter().zip(entries) {
+            assert_eq!(&*key, cf::Blocktime::key(slot).as_slice());
+            assert_eq!(deserialize::<i64>(&value).unwrap(), slot as i64);
+        }
+
+        let keys = column
+            .iter_raw(IteratorMode::From(7, IteratorDirection::Reverse))
+            .map(|entry| entry.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                cf::Blocktime::key(7).into_boxed_slice(),
+                cf::Blocktime::key(3).into_boxed_slice()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_iter_from_prefix() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = db.column::<cf::AddressSignatures>();
+
+        let address = Pubkey::new_from_array([1; 32]);
+        let other = Pubkey::new_from_array([2; 32]);
+        let last = Pubkey::new_from_array([u8::MAX; 32]);
+        let entries = [(address, 1), (address, 2), (address, 3), (other, 1)]
+            .into_iter()
+            .chain([(last, 5), (last, 6)]);
+        for (pubkey, slot) in entries {
+            column
+                .put(
+                    (pubkey, slot, 0, Signature::new_unique()),
+                    &AddressSignatureMeta::default(),
+                )
+                .unwrap();
+        }
+        let slots = |prefix: &Pubkey, direction| {
+            column
+                .iter(IteratorMode::FromPrefix(
+                    prefix.to_bytes().to_vec(),
+                    direction,
+                ))
+                .unwrap()
+                .map(|((pubkey, slot, _, _), _)| {
+                    assert_eq!(&pubkey, prefix);
+                    slot
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(slots(&address, IteratorDirection::Forward), vec![1, 2, 3]);
+        assert_eq!(slots(&address, IteratorDirection::Reverse), vec![3, 2, 1]);
+        assert_eq!(slots(&other, IteratorDirection::Reverse), vec![1]);
+        // No upper bound for a prefix of 0xff bytes
+        assert_eq!(slots(&last, IteratorDirection::Reverse), vec![6, 5]);
+        let missing = Pubkey::new_from_array([3; 32]);
+        assert!(slots(&missing, IteratorDirection::Forward).is_empty());
+        assert!(slots(&missing, IteratorDirection::Reverse).is_empty());
+
+        let statuses = db.column::<cf::TransactionStatus>();
+        let signature = Signature::new_unique();
+        for index in
+            [(signature, 1), (signature, 2), (Signature::new_unique(), 1)]
+        {
+            statuses
+                .put_protobuf(
+                    index,
+                    &generated::TransactionStatusMeta::default(),
+                )
+                .unwrap();
+        }
+        let indexes = statuses
+            .iter_protobuf(IteratorMode::FromPrefix(
+                signature.as_ref().to_vec(),
+                IteratorDirection::Reverse,
+            ))
+            .map(|entry| entry.unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(indexes, vec![(signature, 2), (signature, 1)]);
+    }
+
+    #[test]
+    fn test_multi_get_all_and_present() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        for slot in [1, 2, 4] {
+            column.put(slot, &(slot as i64 * 10)).unwrap();
+        }
+
+        // All present
+        assert_eq!(
+            column.multi_get_all(vec![4, 1]).unwrap(),
+            vec![Some(40), Some(10)]
+        );
+        assert_eq!(
+            column.multi_get_present(vec![4, 1]).unwrap(),
+            vec![(4, 40), (1, 10)]
+        );
+
+        // Some missing
+        assert_eq!(
+            column.multi_get_all(vec![1, 3, 4]).unwrap(),
+            vec![Some(10), None, Some(40)]
+        );
+        assert_eq!(
+            column.multi_get_present(vec![1, 3, 4, 5]).unwrap(),
+            vec![(1, 10), (4, 40)]
+        );
+        assert!(column.multi_get_present(vec![3, 5]).unwrap().is_empty());
+
+        // A single failure fails the whole read
+        column.put_bytes(3, &[1, 2]).unwrap();
+        assert!(matches!(
+            column.multi_get_all(vec![1, 3, 4]),
+            Err(LedgerError::Deserialize {
+                column: "blocktime",
+                ..
+            })
+        ));
+        assert!(matches!(
+            column.multi_get_present(vec![1, 3]),
+            Err(LedgerError::Deserialize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_get_corrupt_value_reports_column() {
+        let (_temp_dir, column) = open_column::<cf::Blockhash>();
+
+        column.put_bytes(7, &[1, 2, 3]).unwrap();
+        assert!(column.get(8).unwrap().is_none());
+
+        let err = column.get(7).unwrap_err();
+        assert!(matches!(err, LedgerError::Deserialize { .. }));
+        let message = err.to_string();
+        assert!(message.contains(cf::Blockhash::NAME), "{message}");
+        assert!(message.contains("0000000000000007"), "{message}");
+
+        let results = column.multi_get(vec![7, 8]);
+        assert!(matches!(results[0], Err(LedgerError::Deserialize { .. })));
+        assert!(matches!(results[1], Ok(None)));
+    }
+
+    #[test]
+    fn test_multi_contains() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+
+        assert!(column.multi_contains(vec![]).is_empty());
+
+        column.put(1, &1).unwrap();
+        column.put(3, &3).unwrap();
+        assert_eq!(
+            column.multi_contains(vec![3, 2, 1, 4, 3]),
+            vec![true, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_find_stops_at_first_match() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        for slot in 0..100 {
+            column.put(slot, &(slot as i64 * 10)).unwrap();
+        }
+
+        let decoded = AtomicU64::new(0);
+        let found = column
+            .find(IteratorMode::Start, |_, value| {
+                decoded.fetch_add(1, Ordering::Relaxed);
+                *value == 500
+            })
+            .unwrap();
+        assert_eq!(found, Some((50, 500)));
+        assert_eq!(decoded.swap(0, Ordering::Relaxed), 51);
+
+        let found = column
+            .find(IteratorMode::End, |slot, _| {
+                decoded.fetch_add(1, Ordering::Relaxed);
+                slot % 7 == 0
+            })
+            .unwrap();
+        assert_eq!(found, Some((98, 980)));
+        assert_eq!(decoded.swap(0, Ordering::Relaxed), 2);
+
+        let found = column
+            .find(
+                IteratorMode::From(90, IteratorDirection::Forward),
+                |_, value| {
+                    decoded.fetch_add(1, Ordering::Relaxed);
+                    *value < 0
+                },
+            )
+            .unwrap();
+        assert_eq!(found, None);
+        assert_eq!(decoded.load(Ordering::Relaxed), 10);
+    }
+
+    #[test]
+    fn test_sum_by() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+
+        let column = db.column::<cf::AddressSignatures>();
+        let address = Pubkey::new_unique();
+        for slot in 0..10 {
+            column
+                .put(
+                    (address, slot, 0, Signature::new_unique()),
+                    &AddressSignatureMeta {
+                        writeable: slot % 3 == 0,
+                    },
+                )
+                .unwrap();
+        }
+        let num_writeable: u64 = column
+            .sum_by(IteratorMode::Start, |meta| meta.writeable as u64)
+            .unwrap();
+        assert_eq!(num_writeable, 4);
+
+        let column = db.column::<cf::Blocktime>();
+        for slot in 0..10 {
+            column.put(slot, &(slot as i64 * 10)).unwrap();
+        }
+        let sum: i64 = column
+            .sum_by(IteratorMode::From(5, IteratorDirection::Forward), |time| {
+                *time
+            })
+            .unwrap();
+        assert_eq!(sum, 50 + 60 + 70 + 80 + 90);
+
+        // Decode failures short-circuit
+        column.put_bytes(10, &[1, 2, 3]).unwrap();
+        assert!(matches!(
+            column.sum_by::<i64, _>(IteratorMode::Start, |time| *time),
+            Err(LedgerError::Deserialize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_counter() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        for slot in 0..3 {
+            column.put(slot, &0).unwrap();
+        }
+
+        // Valid values are kept
+        assert!(!column.validate_counter());
+        assert_eq!(column.count_column_using_cache().unwrap(), 3);
+        assert!(!column.validate_counter());
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), 3);
+
+        column.entry_counter.store(-7, Ordering::Relaxed);
+        assert!(column.validate_counter());
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), DIRTY_COUNT);
+        assert_eq!(column.count_column_using_cache().unwrap(), 3);
+
+        // Verifying the counters resets negative ones instead of reporting
+        // them as a discrepancy
+        column.entry_counter.store(-7, Ordering::Relaxed);
+        assert_eq!(column.verify_counter(true).unwrap(), None);
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), DIRTY_COUNT);
+    }
+
+    #[derive(Clone, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+    struct BorshValue {
+        slot: u64,
+        label: String,
+    }
+
+    struct BorshColumn;
+
+    impl SlotColumn for BorshColumn {}
+
+    impl ColumnName for BorshColumn {
+        const NAME: &'static str = "borsh_test";
+    }
+
+    impl TypedColumn for BorshColumn {
+        type Type = BorshValue;
+        type Codec = Borsh;
+    }
+
+    #[test]
+    fn test_column_codecs() {
+        let temp_dir = tempdir().unwrap();
+        // Opened as an unknown column from then on
+        {
+            let rocks =
+                Rocks::open(temp_dir.path(), LedgerOptions::default()).unwrap();
+            rocks
+                .db
+                .create_cf(BorshColumn::NAME, &rocksdb::Options::default())
+                .unwrap();
+        }
+        let db =
+            Database::open(temp_dir.path(), LedgerOptions::default()).unwrap();
+
+        let column = db.column::<BorshColumn>();
+        let value = |slot: u64| BorshValue {
+            slot,
+            label: format!("slot {slot}"),
+        };
+        column.put(1, &value(1)).unwrap();
+        column.multi_put_accounted(vec![(2, value(2))]).unwrap();
+
+        assert_eq!(column.get(1).unwrap(), Some(value(1)));
+        let values = column
+            .multi_get(vec![1, 2, 3])
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![Some(value(1)), Some(value(2)), None]);
+        assert_eq!(
+            column.get_bytes(2).unwrap(),
+            Some(borsh::to_vec(&value(2)).unwrap())
+        );
+
+        column.put_bytes(3, &[1]).unwrap();
+        assert!(matches!(
+            column.get(3),
+            Err(LedgerError::Deserialize {
+                source: CodecError::Borsh(_),
+                ..
+            })
+        ));
+
+        // Bincode columns are encoded exactly as before
+        let memos = db.column::<cf::TransactionMemos>();
+        let index = (Signature::new_unique(), 1);
+        memos.put(index, &"memo".to_string()).unwrap();
+        assert_eq!(
+            memos.get_bytes(index).unwrap(),
+            Some(serialize(&"memo".to_string()).unwrap())
+        );
+        assert_eq!(memos.get(index).unwrap(), Some("memo".to_string()));
+    }
+
+    #[test]
+    fn test_purge_deprecated_indexes() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = db.column::<cf::SlotSignatures>();
+        assert_eq!(column.purge_deprecated_indexes().unwrap(), 0);
+
+        let signatures =
+            (0..10).map(|_| Signature::new_unique()).collect::<Vec<_>>();
+        let current_slots = [0, 3, 6, 9];
+        for (slot, signature) in signatures.iter().enumerate() {
+            let slot = slot as u64;
+            if current_slots.contains(&slot) {
+                column.put((slot, 0), signature).unwrap();
+            } else {
+                db.backend
+                    .put_cf(
+                        &column.handle(),
+                        &cf::SlotSignatures::deprecated_key((0, slot, 0)),
+                        &serialize(signature).unwrap(),
+                    )
+                    .unwrap();
+            }
+        }
+        assert_eq!(column.iter(IteratorMode::Start).unwrap().count(), 10);
+
+        assert_eq!(column.purge_deprecated_indexes().unwrap(), 6);
+        let remaining = column
+            .iter(IteratorMode::Start)
+            .unwrap()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, current_slots.map(|slot| (slot, 0)).to_vec());
+        for slot in current_slots {
+            assert_eq!(
+                column.get((slot, 0)).unwrap(),
+                Some(signatures[slot as usize])
+            );
+        }
+        assert_eq!(column.purge_deprecated_indexes().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_compact_range_blocking() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = db.column::<cf::Blockhash>();
+        let stats = column.compact_range_blocking(None, None).unwrap();
+        assert_eq!(stats.bytes_before, 0);
+        assert_eq!(stats.sst_files_after, 0);
+
+        // Spread the entries over several SST files
+        for chunk in 0..4 {
+            for slot in chunk * 1_000..(chunk + 1) * 1_000 {
+                column.put(slot, &Hash::new_unique()).unwrap();
+            }
+            column.flush().unwrap();
+        }
+        let mut batch = db.batch();
+        column.delete_range_in_batch(&mut batch, 0, 3_000);
+        db.write(batch).unwrap();
+        column.flush().unwrap();
+
+        let stats = column.compact_range_blocking(None, None).unwrap();
+        assert!(stats.sst_files_before > 1);
+        assert!(stats.bytes_after <= stats.bytes_before, "{stats:?}");
+        assert!(stats.bytes_reclaimed() > 0);
+        assert_eq!(stats.sst_files_after, 1);
+        assert!(column.get(2_999).unwrap().is_none());
+        assert!(column.get(3_000).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_write_batch_introspection() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let mut batch = db.batch();
+        assert!(batch.is_empty());
+        let empty_size = batch.size_in_bytes();
+
+        batch.put::<cf::Blocktime>(1, &100).unwrap();
+        batch.put_bytes::<cf::Blockhash>(2, &[7; 32]);
+        batch.delete::<cf::Blocktime>(3);
+        batch.delete_range_cf::<cf::Blockhash>(4, 8);
+
+        assert_eq!(batch.len(), 4);
+        // The values and the keys are accounted for
+        assert!(batch.size_in_bytes() > empty_size + 32 + 5 * 8);
+        let ops: Vec<_> = batch.iter_ops().cloned().collect();
+        assert_eq!(
+            ops,
+            [
+                BatchOp::Put {
+                    cf: cf::Blocktime::NAME,
+                    key: cf::Blocktime::key(1),
+                },
+                BatchOp::Put {
+                    cf: cf::Blockhash::NAME,
+                    key: cf::Blockhash::key(2),
+                },
+                BatchOp::Delete {
+                    cf: cf::Blocktime::NAME,
+                    key: cf::Blocktime::key(3),
+                },
+                BatchOp::DeleteRange {
+                    cf: cf::Blockhash::NAME,
+                    from: cf::Blockhash::key(4),
+                    to: cf::Blockhash::key(8),
+                },
+            ]
+        );
+
+        // Inspecting doesn't affect what's written
+        db.write(batch).unwrap();
+        assert_eq!(db.column::<cf::Blocktime>().get(1).unwrap(), Some(100));
+        assert!(db.column::<cf::Blockhash>().get(2).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_ingest_sst_files() {
+        let (temp_dir, db) = open_db(LedgerOptions::default());
+        let column = db.column::<cf::Blocktime>();
+        column.put(1, &1).unwrap();
+        assert_eq!(column.count_column_using_cache().unwrap(), 1);
+
+        let options = rocksdb::Options::default();
+        let mut paths = vec![];
+        for (file, slots) in [10..20, 20..30].into_iter().enumerate() {
+            let path = temp_dir.path().join(format!("{file}.sst"));
+            let mut writer = rocksdb::SstFileWriter::create(&options);
+            writer.open(&path).unwrap();
+            // Keys must be added in the column's order
+            for slot in slots {
+                let value = bincode::serialize(&(slot as i64 * 10)).unwrap();
+                writer.put(cf::Blocktime::key(slot), value).unwrap();
+            }
+            writer.finish().unwrap();
+            paths.push(path);
+        }
+
+        column.ingest_sst_files(&paths).unwrap();
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), DIRTY_COUNT);
+        assert_eq!(column.get(1).unwrap(), Some(1));
+        for slot in 10..30 {
+            assert_eq!(column.get(slot).unwrap(), Some(slot as i64 * 10));
+        }
+        assert_eq!(column.count_column_using_cache().unwrap(), 21);
+
+        // Files that don't exist are rejected without touching the column
+        let missing = vec![temp_dir.path().join("missing.sst")];
+        assert!(column.ingest_sst_files(&missing).is_err());
+        assert_eq!(column.count_column_using_cache().unwrap(), 21);
+    }
+
+    #[test]
+    fn test_first_last() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        assert_eq!(column.first().unwrap(), None);
+        assert_eq!(column.last().unwrap(), None);
+
+        for slot in [7, 3, 42, 10] {
+            column.put(slot, &(slot as i64 * 100)).unwrap();
+        }
+        assert_eq!(column.first().unwrap(), Some((3, 300)));
+        assert_eq!(column.last().unwrap(), Some((42, 4_200)));
+
+        column.delete(42).unwrap();
+        assert_eq!(column.last().unwrap(), Some((10, 1_000)));
+        column.delete_all().unwrap();
+        assert_eq!(column.first().unwrap(), None);
+        assert_eq!(column.last().unwrap(), None);
+    }
+
+    #[test]
+    fn test_count_column_approx() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        assert_eq!(column.count_column_approx().unwrap(), 0);
+
+        for slot in 0..1_000 {
+            column.put(slot, &100).unwrap();
+        }
+        let in_tolerance = |estimate: i64| (900..=1_100).contains(&estimate);
+        assert!(in_tolerance(column.count_column_approx().unwrap()));
+        column.flush().unwrap();
+        assert!(in_tolerance(column.count_column_approx().unwrap()));
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), DIRTY_COUNT);
+    }
+
+    #[test]
+    fn test_delete_if() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        for slot in 0..20 {
+            column.put(slot, &(slot as i64 * 10)).unwrap();
+        }
+        assert_eq!(column.count_column_using_cache().unwrap(), 20);
+
+        let is_even = |slot: &u64, _: &i64| slot & 1 == 0;
+        assert_eq!(column.delete_if(IteratorMode::Start, is_even).unwrap(), 10);
+        let survivors = column
+            .iter(IteratorMode::Start)
+            .unwrap()
+            .map(|(slot, _)| slot)
+            .collect::<Vec<_>>();
+        assert_eq!(survivors, (1..20).step_by(2).collect::<Vec<_>>());
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), 10);
+        assert_eq!(column.get(3).unwrap(), Some(30));
+
+        // Only the entries after the start are considered
+        let from_ten = IteratorMode::From(10, IteratorDirection::Forward);
+        assert_eq!(column.delete_if(from_ten, |_, _| true).unwrap(), 5);
+        assert_eq!(column.count_column_using_cache().unwrap(), 5);
+
+        // Values that don't decode abort the deletion
+        column.put_bytes(0, &[1]).unwrap();
+        assert!(matches!(
+            column.delete_if(IteratorMode::Start, |_, _| true),
+            Err(LedgerError::Deserialize { .. })
+        ));
+        assert_eq!(column.get(1).unwrap(), Some(10));
+    }
+
+    #[test]
+    fn test_put_if_newer() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = db.column::<cf::Blocktime>();
+        assert_eq!(column.count_column_using_cache().unwrap(), 0);
+        let version_of = |value: &i64| *value as u64;
+
+        assert!(column.put_if_newer(1, &5, version_of).unwrap());
+        assert!(column.put_if_newer(1, &9, version_of).unwrap());
+        assert!(!column.put_if_newer(1, &7, version_of).unwrap());
+        assert!(!column.put_if_newer(1, &9, version_of).unwrap());
+        assert_eq!(column.get(1).unwrap(), Some(9));
+        assert_eq!(column.count_column_using_cache().unwrap(), 1);
+
+        // Racing writers leave the newest value behind, also when writing
+        // through separate handles of the column
+        thread::scope(|scope| {
+            for offset in 0..8 {
+                let db = &db;
+                scope.spawn(move || {
+                    let column = db.column::<cf::Blocktime>();
+                    for value in (offset..200).step_by(8) {
+                        column.put_if_newer(2, &value, version_of).unwrap();
+                    }
+                });
+            }
+        });
+        assert_eq!(column.get(2).unwrap(), Some(199));
+        assert_eq!(column.iter(IteratorMode::Start).unwrap().count(), 2);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_get_emits_tracing_span() {
+        use std::collections::HashMap;
+
+        use tracing::{
+            field::{Field, Visit},
+            span::{Attributes, Id, Record},
+            Subscriber,
+        };
+        use tracing_subscriber::{
+            layer::{Context, SubscriberExt},
+            registry::LookupSpan,
+            Layer,
+        };
+
+        type SpanFields = HashMap<String, String>;
+
+        struct FieldsVisitor<'a>(&'a mut SpanFields);
+        impl Visit for FieldsVisitor<'_> {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.insert(field.name().to_string(), value.to_string());
+            }
+            fn record_debug(
+                &mut self,
+                field: &Field,
+                value: &dyn std::fmt::Debug,
+            ) {
+                self.0
+                    .insert(field.name().to_string(), format!("{value:?}"));
+            }
+        }
+
+        #[derive(Clone, Default)]
+        struct CapturingLayer(Arc<Mutex<HashMap<Id, SpanFields>>>);
+        impl<S> Layer<S> for CapturingLayer
+        where
+            S: Subscriber + for<'a> LookupSpan<'a>,
+        {
+            fn on_new_span(
+                &self,
+                attrs: &Attributes<'_>,
+                id: &Id,
+                _ctx: Context<'_, S>,
+            ) {
+                let mut spans = self.0.lock().unwrap();
+                let fields = spans.entry(id.clone()).or_default();
+                attrs.record(&mut FieldsVisitor(fields));
+            }
+            fn on_record(
+                &self,
+                id: &Id,
+                values: &Record<'_>,
+                _: Context<'_, S>,
+            ) {
+                let mut spans = self.0.lock().unwrap();
+                let fields = spans.entry(id.clone()).or_default();
+                values.record(&mut FieldsVisitor(fields));
+            }
+        }
+
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        column.put(7, &1_700_000_000).unwrap();
+
+        let layer = CapturingLayer::default();
+        let subscriber = tracing_subscriber::registry().with(layer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(column.get_bytes(7).unwrap().unwrap().len(), 8);
+            assert_eq!(column.get_bytes(8).unwrap(), None);
+        });
+
+        let spans = layer.0.lock().unwrap();
+        let mut gets: Vec<_> = spans
+            .values()
+            .filter(|fields| {
+                fields.get("op").map(String::as_str) == Some("get")
+            })
+            .collect();
+        gets.sort_by_key(|fields| fields.contains_key("value_size"));
+        assert_eq!(gets.len(), 2);
+        for fields in &gets {
+            assert_eq!(fields["column"], cf::Blocktime::NAME);
+            assert_eq!(fields["key_size"], "8");
+        }
+        // The missing key leaves the value size empty
+        assert!(!gets[0].contains_key("value_size"));
+        assert_eq!(gets[1]["value_size"], "8");
+    }
+
+    #[test]
+    fn test_verify_counter() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        // Dirty counters aren't checked
+        assert_eq!(column.verify_counter(false).unwrap(), None);
+
+        for slot in 0..5 {
+            column.put(slot, &(slot as i64)).unwrap();
+        }
+        assert_eq!(column.count_column_using_cache().unwrap(), 5);
+        assert_eq!(column.verify_counter(false).unwrap(), None);
+
+        column.try_increase_entry_counter(2);
+        let discrepancy = CounterDiscrepancy {
+            column: cf::Blocktime::NAME,
+            cached: 7,
+            actual: 5,
+        };
+        assert_eq!(
+            column.verify_counter(false).unwrap(),
+            Some(discrepancy.clone())
+        );
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), 7);
+
+        assert_eq!(column.verify_counter(true).unwrap(), Some(discrepancy));
+        assert_eq!(column.count_column_using_cache().unwrap(), 5);
+        assert_eq!(column.verify_counter(false).unwrap(), None);
+    }
+
+    #[test]
+    fn test_page() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        assert_eq!(column.page(None, 10).unwrap(), (vec![], None));
+
+        // Gaps in the keys don't matter
+        let slots = (0..25).map(|i| i * 3).collect::<Vec<_>>();
+        for slot in &slots {
+            column.put(*slot, &(*slot as i64)).unwrap();
+        }
+
+        let mut paged = vec![];
+        let mut cursor = None;
+        let mut num_pages = 0;
+        loop {
+            let (entries, next) = column.page(cursor, 10).unwrap();
+            assert!(entries.len() <= 10);
+            num_pages += 1;
+            paged.extend(entries);
+            match next {
+                Some(next) => {
+                    assert_eq!(Some(&next), paged.last().map(|(slot, _)| slot));
+                    cursor = Some(next);
+                }
+                None => break,
+            }
+        }
+        assert_eq!(num_pages, 3);
+        let expected = slots
+            .iter()
+            .map(|slot| (*slot, *slot as i64))
+            .collect::<Vec<_>>();
+        assert_eq!(paged, expected);
+
+        // The cursor doesn't have to exist, entries written after it are
+        // picked up
+        let (entries, cursor) = column.page(Some(4), 2).unwrap();
+        assert_eq!(entries, vec![(6, 6), (9, 9)]);
+        assert_eq!(cursor, Some(9));
+        column.put(10, &10).unwrap();
+        let (entries, _) = column.page(cursor, 2).unwrap();
+        assert_eq!(entries, vec![(10, 10), (12, 12)]);
+
+        // An exactly filled last page is reported as exhausted
+        assert_eq!(column.page(Some(69), 2).unwrap(), (vec![(72, 72)], None));
+        assert_eq!(column.page(Some(66), 2).unwrap().1, None);
+        assert_eq!(column.page(Some(72), 2).unwrap(), (vec![], None));
+        assert_eq!(column.page(Some(3), 0).unwrap(), (vec![], Some(3)));
+    }
+
+    #[test]
+    fn test_write_rate_limit() {
+        // Each Blocktime entry is 16 bytes (8 byte key and value), a single
+        // one takes 16 seconds to refill, see the rate limiter's tests for
+        // the pacing
+        let limit = WriteRateLimit {
+            bytes_per_sec: 1,
+            burst_bytes: 32,
+            mode: RateLimitMode::Reject,
+        };
+        let options = LedgerOptions {
+            column_options: LedgerColumnOptions {
+                write_rate_limits: [(cf::Blocktime::NAME, limit)].into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (_temp_dir, db) = open_db(options);
+        let column = db.column::<cf::Blocktime>();
+
+        // Writes exceeding the burst are rejected without being written
+        column.put(0, &10).unwrap();
+        column.put_bytes(1, &serialize(&10i64).unwrap()).unwrap();
+        assert!(matches!(
+            column.put(2, &10),
+            Err(LedgerError::RateLimited(cf::Blocktime::NAME))
+        ));
+        assert!(matches!(
+            column.multi_put_accounted(vec![(3, 10), (4, 10)]),
+            Err(LedgerError::RateLimited(cf::Blocktime::NAME))
+        ));
+        assert_eq!(column.get(2).unwrap(), None);
+        assert_eq!(column.get(3).unwrap(), None);
+        // Batches aren't limited
+        let mut batch = db.batch();
+        batch.put::<cf::Blocktime>(2, &10).unwrap();
+        db.write(batch).unwrap();
+        assert_eq!(column.get(2).unwrap(), Some(10));
+    }
+
+    #[test]
+    fn test_column_cache() {
+        let options = LedgerOptions {
+            column_options: LedgerColumnOptions {
+                cache_capacities: [(cf::Blocktime::NAME, 1)].into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (_temp_dir, db) = open_db(options);
+        let column = db.column::<cf::Blocktime>();
+        let delete_underlying = |slot: u64| {
+            db.backend
+                .delete_cf(&column.handle(), &cf::Blocktime::key(slot))
+                .unwrap()
+        };
+
+        // Cache hits don't touch RocksDB
+        column.put(1, &10).unwrap();
+        delete_underlying(1);
+        assert_eq!(column.get(1).unwrap(), Some(10));
+        assert_eq!(column.multi_get(vec![1, 2]).pop().unwrap().unwrap(), None);
+        assert_eq!(column.multi_get(vec![1]).pop().unwrap().unwrap(), Some(10));
+        // Shared by all handles of the column
+        assert_eq!(db.column::<cf::Blocktime>().get(1).unwrap(), Some(10));
+
+        // Deletes invalidate the cached value
+        column.delete(1).unwrap();
+        assert_eq!(column.get(1).unwrap(), None);
+
+        // Reads populate the cache, evicting the least recently used value
+        column.put(1, &10).unwrap();
+        db.backend
+            .put_cf(
+                &column.handle(),
+                &cf::Blocktime::key(2),
+                &serialize(&20i64).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(column.get(2).unwrap(), Some(20));
+        delete_underlying(1);
+        delete_underlying(2);
+        assert_eq!(column.get(1).unwrap(), None);
+        assert_eq!(column.get(2).unwrap(), Some(20));
+
+        // Batched deletes invalidate the cache once the batch is written
+        let mut batch = db.batch();
+        column.delete_range_in_batch(&mut batch, 0, 10);
+        assert_eq!(column.get(2).unwrap(), Some(20));
+        db.write(batch).unwrap();
+        assert_eq!(column.get(2).unwrap(), None);
+
+        // Values read back while the batch is pending aren't served after it
+        column.put(3, &30).unwrap();
+        let mut batch = db.batch();
+        column.delete_in_batch(&mut batch, 3);
+        column.cache.as_ref().unwrap().clear();
+        assert_eq!(column.get(3).unwrap(), Some(30));
+        db.write(batch).unwrap();
+        assert_eq!(column.get(3).unwrap(), None);
+
+        // Columns without a configured capacity aren't cached
+        let blockhash = db.column::<cf::Blockhash>();
+        assert!(blockhash.cache.is_none());
+        blockhash.put(1, &Hash::new_unique()).unwrap();
+        db.backend
+            .delete_cf(&blockhash.handle(), &cf::Blockhash::key(1))
+            .unwrap();
+        assert_eq!(blockhash.get(1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_column_cache_concurrent_put_get() {
+        let options = LedgerOptions {
+            column_options: LedgerColumnOptions {
+                cache_capacities: [(cf::Blocktime::NAME, 16)].into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (_temp_dir, db) = open_db(options);
+        let column = db.column::<cf::Blocktime>();
+        let stored = || {
+            db.backend
+                .get_cf(&column.handle(), &cf::Blocktime::key(1))
+                .unwrap()
+                .map(|value| deserialize::<i64>(&value).unwrap())
+        };
+
+        // Neither a racing read nor the put that reached RocksDB first may
+        // leave its value cached over the one RocksDB ended up with
+        for round in 1..=500i64 {
+            column.cache.as_ref().unwrap().clear();
+            thread::scope(|scope| {
+                scope.spawn(|| db.column::<cf::Blocktime>().get(1).unwrap());
+                scope.spawn(|| column.put(1, &round).unwrap());
+                scope.spawn(|| {
+                    db.column::<cf::Blocktime>().put(1, &-round).unwrap()
+                });
+                scope.spawn(|| column.get(1).unwrap());
+            });
+            assert_eq!(column.get(1).unwrap(), stored(), "round {round}");
+        }
+    }
+
+    #[test]
+    fn test_universal_compaction_column() {
+        let options = LedgerOptions {
+            column_options: LedgerColumnOptions {
+                compaction_styles: [(
+                    cf::Blocktime::NAME,
+                    LedgerCompactionStyle::Universal,
+                )]
+                .into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (_temp_dir, db) = open_db(options);
+        let universal = db.column::<cf::Blocktime>();
+        let level = db.column::<cf::Blockhash>();
+
+        for slot in 0..100 {
+            universal.put(slot, &(slot as i64)).unwrap();
+        }
+        universal.flush().unwrap();
+        universal.compact_range(None, None).unwrap();
+        level.put(1, &Hash::new_unique()).unwrap();
+
+        assert_eq!(universal.get(42).unwrap(), Some(42));
+        assert_eq!(universal.count_column_using_cache().unwrap(), 100);
+        assert!(level.get(1).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_delete_all() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = db.column::<cf::SlotSignatures>();
+        let other = db.column::<cf::Blocktime>();
+
+        // Deleting from an empty column is a no-op
+        column.delete_all().unwrap();
+        assert!(column.is_empty().unwrap());
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), 0);
+
+        for slot in [0, 1, u64::MAX] {
+            for index in [0, u32::MAX] {
+                column.put((slot, index), &Signature::new_unique()).unwrap();
+            }
+        }
+        column.try_increase_entry_counter(6);
+        other.put(1, &1).unwrap();
+        assert_eq!(column.count_column_using_cache().unwrap(), 6);
+
+        column.delete_all().unwrap();
+        assert!(column.is_empty().unwrap());
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), 0);
+        assert_eq!(column.count_column_using_cache().unwrap(), 0);
+        assert_eq!(other.get(1).unwrap(), Some(1));
+
+        column.put((2, 0), &Signature::new_unique()).unwrap();
+        column.try_increase_entry_counter(1);
+        assert_eq!(column.count_column_using_cache().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let (_source_dir, source) = open_db(LedgerOptions::default());
+        let (_target_dir, target) = open_db(LedgerOptions::default());
+
+        let exported = source.column::<cf::TransactionStatus>();
+        for slot in 0..100 {
+            let status = generated::TransactionStatusMeta {
+                fee: slot,
+                ..Default::default()
+            };
+            exported
+                .put_protobuf((Signature::new_unique(), slot), &status)
+                .unwrap();
+        }
+
+        let mut buf = Vec::new();
+        assert_eq!(exported.export(&mut buf).unwrap(), 100);
+
+        let imported = target.column::<cf::TransactionStatus>();
+        imported.reset_entry_counter();
+        assert_eq!(imported.import(buf.as_slice()).unwrap(), 100);
+        assert_eq!(imported.count_column_using_cache().unwrap(), 100);
+
+        let source_entries = exported
+            .iter(IteratorMode::Start)
+            .unwrap()
+            .collect::<Vec<_>>();
+        let target_entries = imported
+            .iter(IteratorMode::Start)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(source_entries, target_entries);
+
+        // Importing again overwrites the entries without recounting them
+        assert_eq!(imported.import(buf.as_slice()).unwrap(), 100);
+        assert_eq!(imported.count_column_using_cache().unwrap(), 100);
+
+        // Truncated input is rejected
+        assert!(imported.import(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_value_size_histogram() {
+        let (_temp_dir, column) = open_column::<cf::TransactionMemos>();
+
+        let histogram = column.value_size_histogram(IteratorMode::Start);
+        assert_eq!(histogram.unwrap(), SizeHistogram::default());
+
+        // Sizes 0, 1, 2, 3, 4 and 1000 on slots 0..6
+        for (slot, size) in [0, 1, 2, 3, 4, 1000].into_iter().enumerate() {
+            column
+                .put_bytes((Signature::default(), slot as u64), &vec![7; size])
+                .unwrap();
+        }
+
+        let histogram =
+            column.value_size_histogram(IteratorMode::Start).unwrap();
+        let mut expected_buckets = vec![0; 11];
+        expected_buckets[0] = 1; // 0
+        expected_buckets[1] = 1; // 1
+        expected_buckets[2] = 2; // 2..4
+        expected_buckets[3] = 1; // 4..8
+        expected_buckets[10] = 1; // 512..1024
+        assert_eq!(histogram.buckets, expected_buckets);
+        assert_eq!(histogram.count, 6);
+        assert_eq!(histogram.min, 0);
+        assert_eq!(histogram.max, 1000);
+        assert_eq!(histogram.total_bytes, 1010);
+        assert!((histogram.mean() - 1010.0 / 6.0).abs() < f64::EPSILON);
+
+        // Only the values from slot 4 on
+        let histogram = column
+            .value_size_histogram(IteratorMode::From(
+                (Signature::default(), 4),
+                IteratorDirection::Forward,
+            ))
+            .unwrap();
+        assert_eq!(histogram.count, 2);
+        assert_eq!(histogram.min, 4);
+        assert_eq!(histogram.total_bytes, 1004);
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = db.column::<cf::Blocktime>();
+        column.reset_entry_counter();
+
+        column.put(1, &10).unwrap();
+        column.try_increase_entry_counter(1);
+        let value = column
+            .get_or_insert_with(1, || panic!("value exists already"))
+            .unwrap();
+        assert_eq!(value, 10);
+
+        assert_eq!(column.get_or_insert_with(2, || 20).unwrap(), 20);
+        assert_eq!(column.get(2).unwrap(), Some(20));
+        assert_eq!(column.count_column_using_cache().unwrap(), 2);
+
+        // Only the first of the concurrent inserts is stored
+        let column = Arc::new(column);
+        let values = (0..8)
+            .map(|i| {
+                let column = column.clone();
+                thread::spawn(move || {
+                    column.get_or_insert_with(3, || 30 + i).unwrap()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>();
+        let stored = column.get(3).unwrap().unwrap();
+        assert!(values.iter().all(|value| *value == stored));
+        assert_eq!(column.count_column_using_cache().unwrap(), 3);
+
+        // Separate handles of the column share the lock, so `f` runs once
+        let calls = AtomicU64::new(0);
+        thread::scope(|scope| {
+            for i in 0..8 {
+                let (db, calls) = (&db, &calls);
+                scope.spawn(move || {
+                    db.column::<cf::Blocktime>()
+                        .get_or_insert_with(4, || {
+                            calls.fetch_add(1, Ordering::Relaxed);
+                            40 + i
+                        })
+                        .unwrap()
+                });
+            }
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = db.column::<cf::PerfSamples>();
+        const OP: &str = "test_latency_percentiles";
+        assert!(column.latency_percentiles(OP).is_none());
+
+        // 1..=1000 micros, evenly distributed
+        for micros in 1..=1000 {
+            column.latencies.record(OP, Duration::from_micros(micros));
+        }
+
+        let percentiles = column.latency_percentiles(OP).unwrap();
+        assert_eq!(percentiles.count, 1000);
+        let within = |estimate: Duration, expected_micros: u64| {
+            let expected = Duration::from_micros(expected_micros);
+            estimate.abs_diff(expected) <= expected / 8
+        };
+        assert!(within(percentiles.p50, 500), "{percentiles:?}");
+        assert!(within(percentiles.p95, 950), "{percentiles:?}");
+        assert!(within(percentiles.p99, 990), "{percentiles:?}");
+        assert!(column.latency_percentiles("unknown_op").is_none());
+
+        // Shared by the handles of the column, but not across databases
+        let other_handle = db.column::<cf::PerfSamples>();
+        assert_eq!(other_handle.latency_percentiles(OP), Some(percentiles));
+        let (_other_dir, other_column) = open_column::<cf::PerfSamples>();
+        assert!(other_column.latency_percentiles(OP).is_none());
+    }
+
+    #[test]
+    fn test_put_accounted() {
+        let (_temp_dir, column) = open_column::<cf::TransactionMemos>();
+        let signature = Signature::new_unique();
+
+        let memo = "memo".to_string();
+        let size = column.put_accounted((signature, 0), &memo).unwrap();
+        assert_eq!(size, serialize(&memo).unwrap().len());
+
+        let memos =
+            (1..5).map(|slot| "m".repeat(slot * 10)).collect::<Vec<_>>();
+        let expected = memos
+            .iter()
+            .map(|memo| serialize(memo).unwrap().len())
+            .collect::<Vec<_>>();
+        let entries = memos
+            .iter()
+            .enumerate()
+            .map(|(i, memo)| ((signature, i as u64 + 1), memo.clone()))
+            .collect();
+        assert_eq!(column.multi_put_accounted(entries).unwrap(), expected);
+        for (i, memo) in memos.into_iter().enumerate() {
+            let slot = i as u64 + 1;
+            assert_eq!(column.get((signature, slot)).unwrap(), Some(memo));
+        }
+    }
+
+    #[test]
+    fn test_write_health() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+
+        for slot in 0..10 {
+            column.put(slot, &(slot as i64)).unwrap();
+        }
+        assert_eq!(column.write_health(), WriteHealth::default());
+        assert!(column.check_write_stalled().is_ok());
+    }
+
+    #[test]
+    fn test_verify_integrity() {
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+
+        let column = db.column::<cf::Blockhash>();
+        assert_eq!(column.verify_integrity().unwrap().total, 0);
+        for slot in 0..10 {
+            if slot == 5 || slot == 9 {
+                column.put_bytes(slot, &[1, 2, 3]).unwrap();
+            } else {
+                column.put(slot, &Hash::new_unique()).unwrap();
+            }
+        }
+        assert_eq!(
+            column.verify_integrity().unwrap(),
+            IntegrityReport {
+                total: 10,
+                ok: 8,
+                corrupt_keys: vec![5, 9],
+            }
+        );
+
+        let column = db.column::<cf::TransactionStatus>();
+        let valid = (Signature::new_unique(), 1);
+        let corrupt = (Signature::new_unique(), 2);
+        column.put_protobuf(valid, &Default::default()).unwrap();
+        column.put_bytes(corrupt, &[0xff; 4]).unwrap();
+        assert_eq!(
+            column.verify_integrity_protobuf().unwrap(),
+            IntegrityReport {
+                total: 2,
+                ok: 1,
+                corrupt_keys: vec![corrupt],
+            }
+        );
+    }
+
+    #[test]
+    fn test_put_with_durability() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+
+        let synced = WriteDurability {
+            sync: true,
+            disable_wal: false,
+        };
+        let no_wal = WriteDurability {
+            sync: false,
+            disable_wal: true,
+        };
+        column.put_with_opts(1, &1, synced).unwrap();
+        column.put_with_opts(2, &2, no_wal).unwrap();
+        column
+            .put_bytes_with_opts(3, &serialize(&3i64).unwrap(), synced)
+            .unwrap();
+        column
+            .put_bytes_with_opts(4, &serialize(&4i64).unwrap(), no_wal)
+            .unwrap();
+
+        for slot in 1..=4 {
+            assert_eq!(column.get(slot).unwrap(), Some(slot as i64));
+        }
+        // Writes that skipped the WAL are persisted once flushed
+        column.flush().unwrap();
+        assert_eq!(column.get(4).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn test_merge_concurrently() {
+        const NUM_THREADS: i64 = 8;
+        const NUM_MERGES: i64 = 1_000;
+
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = Arc::new(db.column::<cf::ProgramTransactionCounts>());
+        let program = Pubkey::new_unique();
+        let other_program = Pubkey::new_unique();
+
+        assert_eq!(column.get(program).unwrap(), None);
+        column.put(other_program, &10).unwrap();
+
+        let handles = (1..=NUM_THREADS)
+            .map(|delta| {
+                let column = column.clone();
+                thread::spawn(move || {
+                    for _ in 0..NUM_MERGES {
+                        column.merge(program, delta).unwrap();
+                    }
+                    column.merge(other_program, -delta).unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let sum_of_deltas = (1..=NUM_THREADS).sum::<i64>();
+        assert_eq!(
+            column.get(program).unwrap(),
+            Some(sum_of_deltas * NUM_MERGES)
+        );
+        assert_eq!(
+            column.get(other_program).unwrap(),
+            Some(10 - sum_of_deltas)
+        );
+
+        // Merged values survive flush and compaction
+        column.flush().unwrap();
+        column.compact_range(None, None).unwrap();
+        assert_eq!(
+            column
+                .multi_get(vec![program, other_program])
+                .into_iter()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>(),
+            vec![Some(sum_of_deltas * NUM_MERGES), Some(10 - sum_of_deltas)]
+        );
+    }
+
+    #[test]
+    fn test_iter_from_with_direction() {
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        for slot in (0..20).step_by(2) {
+            column.put(slot, &(slot as i64)).unwrap();
+        }
+
+        let slots = |mode| {
+            column
+                .iter(mode)
+                .unwrap()
+                .map(|(slot, _)| slot)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            slots(IteratorMode::From(14, IteratorDirection::Forward)),
+            vec![14, 16, 18]
+        );
+        assert_eq!(
+            slots(IteratorMode::From(13, IteratorDirection::Forward)),
+            vec![14, 16, 18]
+        );
+        assert_eq!(
+            slots(IteratorMode::From(4, IteratorDirection::Reverse)),
+            vec![4, 2, 0]
+        );
+        // Reverse starts at the closest key before a missing one
+        assert_eq!(
+            slots(IteratorMode::From(5, IteratorDirection::Reverse)),
+            vec![4, 2, 0]
+        );
+        assert_eq!(
+            slots(IteratorMode::From(100, IteratorDirection::Reverse)),
+            (0..=18).rev().step_by(2).collect::<Vec<_>>()
+        );
+        assert!(slots(IteratorMode::From(19, IteratorDirection::Forward))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_iter_protobuf_from_with_direction() {
+        let (_temp_dir, column) = open_column::<cf::TransactionStatus>();
+        let signature = Signature::new_unique();
+        for slot in 0..10 {
+            let status = generated::TransactionStatusMeta {
+                fee: slot,
+                ..Default::default()
+            };
+            column.put_protobuf((signature, slot), &status).unwrap();
+        }
+
+        let entries = |mode| {
+            column
+                .iter_protobuf(mode)
+                .map(|entry| {
+                    let ((entry_signature, slot), status) = entry.unwrap();
+                    assert_eq!(entry_signature, signature);
+                    assert_eq!(status.fee, slot);
+                    slot
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            entries(IteratorMode::From(
+                (signature, 6),
+                IteratorDirection::Reverse
+            )),
+            vec![6, 5, 4, 3, 2, 1, 0]
+        );
+        assert_eq!(
+            entries(IteratorMode::From(
+                (signature, 6),
+                IteratorDirection::Forward
+            )),
+            vec![6, 7, 8, 9]
+        );
+    }
+
+    #[test]
+    fn test_count_column_single_scan() {
+        const NUM_THREADS: usize = 8;
+
+        let (_temp_dir, column) = open_column::<cf::Blocktime>();
+        for slot in 0..10_000 {
+            column.put(slot, &(slot as i64)).unwrap();
+        }
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), DIRTY_COUNT);
+
+        let barrier = std::sync::Barrier::new(NUM_THREADS);
+        let counts = thread::scope(|scope| {
+            let handles = (0..NUM_THREADS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        column.count_column_using_cache().unwrap()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(counts, vec![10_000; NUM_THREADS]);
+        assert_eq!(column.num_count_scans.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_recount_async_while_writing() {
+        const NUM_SLOTS: u64 = 2_000;
+
+        let (_temp_dir, db) = open_db(LedgerOptions::default());
+        let column = Arc::new(db.column::<cf::Blocktime>());
+
+        let writer = thread::spawn({
+            let column = column.clone();
+            move || {
+                for slot in 0..NUM_SLOTS {
+                    column.get_or_insert_with(slot, || slot as i64).unwrap();
+                }
+            }
+        });
+        let recounts = (0..4)
+            .map(|_| column.clone().recount_async())
+            .collect::<Vec<_>>();
+
+        writer.join().unwrap();
+        for recount in recounts {
+            let count = recount.join().unwrap().unwrap();
+            assert!(count <= NUM_SLOTS as i64);
+        }
+        // Either a recount won and increments were applied on top of it,
+        // or the counter was left dirty and gets recomputed here
+        assert_eq!(
+            column.count_column_using_cache().unwrap(),
+            NUM_SLOTS as i64
+        );
+
+        // Without concurrent writes the recount result is cached
+        column.entry_counter.store(DIRTY_COUNT, Ordering::Relaxed);
+        let count = column.clone().recount_async().join().unwrap().unwrap();
+        assert_eq!(count, NUM_SLOTS as i64);
+        assert_eq!(column.entry_counter.load(Ordering::Relaxed), count);
+    }
+}
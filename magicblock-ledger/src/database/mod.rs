@@ -1,4 +1,7 @@
+pub mod async_ledger_column;
+pub mod cache;
 pub mod cf_descriptors;
+pub mod codec;
 pub mod columns;
 mod consts;
 pub mod db;
@@ -6,6 +9,7 @@ pub mod iterator;
 pub mod ledger_column;
 pub mod meta;
 pub mod options;
+pub mod rate_limiter;
 mod rocks_db;
 mod rocksdb_options;
 pub mod write_batch;
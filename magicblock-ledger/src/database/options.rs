@@ -1,4 +1,12 @@
-use rocksdb::DBCompressionType as RocksCompressionType;
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicU64, Arc},
+};
+
+use rocksdb::{
+    DBCompactionStyle as RocksCompactionStyle,
+    DBCompressionType as RocksCompressionType, Env, WriteOptions,
+};
 
 // -----------------
 // AccessType
@@ -15,6 +23,10 @@ pub enum AccessType {
     /// already has Primary access.
     #[allow(unused)]
     Secondary,
+    /// Read only access to a subset of the columns, see
+    /// [crate::Ledger::open_columns]. Doesn't lock the database, so it can
+    /// be obtained while another process has Primary access.
+    ReadOnly,
 }
 
 // -----------------
@@ -27,6 +39,42 @@ pub struct LedgerOptions {
     // desired open file descriptor limit cannot be configured. Default: true.
     pub enforce_ulimit_nofile: bool,
     pub column_options: LedgerColumnOptions,
+    // Max number of files RocksDB keeps open, -1 keeps all of them open.
+    // Secondary access requires -1. Default: -1
+    pub max_open_files: i32,
+    // Limits the disk write rate of flushes and compactions, so that they
+    // don't starve foreground I/O on shared disks. Default: None (unlimited)
+    pub rate_limiter_bytes_per_sec: Option<i64>,
+    // Max number of concurrent background flushes and compactions.
+    // Default: None, i.e. derived from the number of cores
+    pub max_background_jobs: Option<i32>,
+    // Once the WAL files exceed this size, the column families backed by the
+    // oldest of them are flushed so that it can be released. Bounds the WAL
+    // replayed when recovering from a crash. Default: 4GB
+    pub max_total_wal_size: u64,
+    // Max size of the archived WAL files in MB, 0 means no limit. Default: 0
+    pub wal_size_limit_mb: u64,
+    // Number of WAL files kept around to be reused instead of creating new
+    // ones. Default: 0 (no recycling)
+    pub recycle_log_file_num: usize,
+    // Drops the entries of slot keyed columns at or below the lowest cleanup
+    // slot whenever they are compacted, see
+    // [crate::Ledger::advance_lowest_cleanup_slot]. Default: false
+    pub cleanup_compaction_filter: bool,
+    // Lowest cleanup slot the compaction filter compares against,
+    // kept up to date by the ledger
+    pub cleanup_slot_watermark: Arc<AtomicU64>,
+    // Prefixes the names of all column families, so that ledgers of
+    // different namespaces can share one database, see
+    // [crate::Ledger::open_with_namespace]. Default: None
+    pub namespace: Option<String>,
+    // Collects RocksDB statistics, see [crate::Ledger::statistics_snapshot].
+    // Adds a small overhead to every operation. Default: false
+    pub enable_statistics: bool,
+    // Environment RocksDB does its file I/O and background work through,
+    // i.e. an in-memory or an encrypting one, see
+    // [crate::Ledger::open_with_env]. Default: None (the filesystem)
+    pub env: Option<Env>,
 }
 
 impl Default for LedgerOptions {
@@ -38,6 +86,17 @@ impl Default for LedgerOptions {
             access_type: AccessType::Primary,
             enforce_ulimit_nofile: true,
             column_options: LedgerColumnOptions::default(),
+            max_open_files: -1,
+            rate_limiter_bytes_per_sec: None,
+            max_background_jobs: None,
+            max_total_wal_size: 4 * 1024 * 1024 * 1024,
+            wal_size_limit_mb: 0,
+            recycle_log_file_num: 0,
+            cleanup_compaction_filter: false,
+            cleanup_slot_watermark: Arc::default(),
+            namespace: None,
+            enable_statistics: false,
+            env: None,
         }
     }
 }
@@ -61,6 +120,18 @@ pub struct LedgerColumnOptions {
     // If the value is greater than 0, then RocksDB read/write perf sample
     // will be collected once for every `rocks_perf_sample_interval` ops.
     pub rocks_perf_sample_interval: usize,
+
+    // Compaction style per column, keyed by column name. Columns that aren't
+    // listed keep RocksDB's default (level) compaction.
+    pub compaction_styles: HashMap<&'static str, LedgerCompactionStyle>,
+
+    // Max number of deserialized values cached in memory per typed column,
+    // keyed by column name. Columns that aren't listed aren't cached.
+    pub cache_capacities: HashMap<&'static str, usize>,
+
+    // Caps the rate the caller writes to a column with, keyed by column
+    // name. Columns that aren't listed are unlimited.
+    pub write_rate_limits: HashMap<&'static str, WriteRateLimit>,
 }
 
 impl Default for LedgerColumnOptions {
@@ -69,6 +140,9 @@ impl Default for LedgerColumnOptions {
             shred_storage_type: ShredStorageType::RocksLevel,
             compression_type: LedgerCompressionType::default(),
             rocks_perf_sample_interval: 0,
+            compaction_styles: HashMap::new(),
+            cache_capacities: HashMap::new(),
+            write_rate_limits: HashMap::new(),
         }
     }
 }
@@ -134,3 +208,76 @@ impl LedgerCompressionType {
         }
     }
 }
+
+// -----------------
+// WriteDurability
+// -----------------
+/// Durability of a single write, the default matches RocksDB's default
+/// write options.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WriteDurability {
+    /// Sync the WAL to disk before the write returns. Slower, but the write
+    /// survives a machine crash and not only a process crash.
+    pub sync: bool,
+    /// Skip the WAL entirely. Faster for bulk ingestion, but recent writes
+    /// that weren't flushed yet are lost on crash.
+    /// RocksDB rejects writes that combine it with `sync`.
+    pub disable_wal: bool,
+}
+
+impl WriteDurability {
+    pub(crate) fn to_rocksdb_write_options(self) -> WriteOptions {
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(self.sync);
+        write_options.disable_wal(self.disable_wal);
+        write_options
+    }
+}
+
+// -----------------
+// WriteRateLimit
+// -----------------
+/// Token bucket limiting the bytes (keys and values) written to a column
+/// through [crate::database::ledger_column::LedgerColumn].
+/// Unlike [LedgerOptions::rate_limiter_bytes_per_sec], which paces RocksDB's
+/// flushes and compactions, this applies backpressure to the writer itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteRateLimit {
+    /// Rate the bucket is refilled with
+    pub bytes_per_sec: u64,
+    /// Capacity of the bucket, i.e. the max size of a burst of writes
+    pub burst_bytes: u64,
+    /// What happens to writes that exceed the limit
+    pub mode: RateLimitMode,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitMode {
+    /// Blocks the writer until the bucket refilled enough
+    #[default]
+    Block,
+    /// Fails the write with [crate::errors::LedgerError::RateLimited]
+    Reject,
+}
+
+// -----------------
+// LedgerCompactionStyle
+// -----------------
+/// Append-heavy columns that get truncated benefit from universal compaction
+/// due to its lower write amplification, while columns used for point lookups
+/// are better off with level compaction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerCompactionStyle {
+    #[default]
+    Level,
+    Universal,
+}
+
+impl LedgerCompactionStyle {
+    pub(crate) fn to_rocksdb_compaction_style(self) -> RocksCompactionStyle {
+        match self {
+            Self::Level => RocksCompactionStyle::Level,
+            Self::Universal => RocksCompactionStyle::Universal,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::options::{RateLimitMode, WriteRateLimit};
+use crate::errors::{LedgerError, LedgerResult};
+
+// -----------------
+// WriteRateLimiter
+// -----------------
+/// Token bucket enforcing a [WriteRateLimit] on the writes to a column
+#[derive(Debug)]
+pub struct WriteRateLimiter {
+    limit: WriteRateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl WriteRateLimiter {
+    /// Creates a limiter with a full bucket, `limit.bytes_per_sec` must be
+    /// greater than 0
+    pub fn new(limit: WriteRateLimit) -> Self {
+        assert!(limit.bytes_per_sec > 0, "write rate limit must be positive");
+        Self {
+            limit,
+            bucket: Mutex::new(Bucket {
+                tokens: limit.burst_bytes as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `bytes` tokens out of the bucket, blocking until enough of them
+    /// are available or failing with [LedgerError::RateLimited] depending on
+    /// the [RateLimitMode].
+    /// Writes larger than the burst size pass once the bucket is full and
+    /// leave it in debt, pacing the following writes.
+    pub fn acquire(
+        &self,
+        column: &'static str,
+        bytes: u64,
+    ) -> LedgerResult<()> {
+        let wait = self.reserve(column, bytes, Instant::now())?;
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+        Ok(())
+    }
+
+    /// Refills the bucket up to `now` and takes the tokens of
+    /// [Self::acquire] out of it, returning how long the writer has to wait
+    /// for them
+    fn reserve(
+        &self,
+        column: &'static str,
+        bytes: u64,
+        now: Instant,
+    ) -> LedgerResult<Duration> {
+        let mut bucket = self.bucket.lock().expect("rate limiter poisoned");
+        let refill = now
+            .saturating_duration_since(bucket.refilled_at)
+            .as_secs_f64()
+            * self.limit.bytes_per_sec as f64;
+        bucket.tokens =
+            (bucket.tokens + refill).min(self.limit.burst_bytes as f64);
+        bucket.refilled_at = bucket.refilled_at.max(now);
+
+        let needed = bytes.min(self.limit.burst_bytes) as f64;
+        let deficit = needed - bucket.tokens;
+        if deficit > 0.0 && self.limit.mode == RateLimitMode::Reject {
+            return Err(LedgerError::RateLimited(column));
+        }
+        // Blocked writers reserve their tokens upfront, so that they are
+        // served in order
+        bucket.tokens -= bytes as f64;
+        Ok(Duration::from_secs_f64(
+            deficit.max(0.0) / self.limit.bytes_per_sec as f64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLUMN: &str = "test";
+
+    fn limiter(mode: RateLimitMode) -> (WriteRateLimiter, Instant) {
+        // Powers of two, so that the waits are exact
+        let limiter = WriteRateLimiter::new(WriteRateLimit {
+            bytes_per_sec: 128,
+            burst_bytes: 32,
+            mode,
+        });
+        (limiter, Instant::now())
+    }
+
+    #[test]
+    fn test_reserve_block() {
+        let (limiter, start) = limiter(RateLimitMode::Block);
+        let wait = |bytes, millis| {
+            let now = start + Duration::from_millis(millis);
+            limiter.reserve(COLUMN, bytes, now).unwrap()
+        };
+
+        // The burst passes right away, the rest is paced at 8 puts/sec
+        assert_eq!(wait(16, 0), Duration::ZERO);
+        assert_eq!(wait(16, 0), Duration::ZERO);
+        assert_eq!(wait(16, 0), Duration::from_millis(125));
+        assert_eq!(wait(16, 0), Duration::from_millis(250));
+        // Refilled the tokens the blocked writers reserved
+        assert_eq!(wait(16, 250), Duration::from_millis(125));
+
+        // Writes larger than the burst wait for a full bucket only
+        assert_eq!(wait(64, 1000), Duration::ZERO);
+        assert_eq!(wait(16, 1000), Duration::from_millis(375));
+    }
+
+    #[test]
+    fn test_reserve_reject() {
+        let (limiter, start) = limiter(RateLimitMode::Reject);
+        let reserve = |bytes, millis| {
+            let now = start + Duration::from_millis(millis);
+            limiter.reserve(COLUMN, bytes, now)
+        };
+
+        assert_eq!(reserve(16, 0).unwrap(), Duration::ZERO);
+        assert_eq!(reserve(16, 0).unwrap(), Duration::ZERO);
+        assert!(matches!(
+            reserve(16, 0),
+            Err(LedgerError::RateLimited(COLUMN))
+        ));
+        // Rejected writes don't take tokens
+        assert!(matches!(
+            reserve(16, 0),
+            Err(LedgerError::RateLimited(COLUMN))
+        ));
+        assert_eq!(reserve(16, 125).unwrap(), Duration::ZERO);
+    }
+}
@@ -1,27 +1,80 @@
-use std::{fs, path::Path};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, Weak},
+};
 
 use rocksdb::{
-    AsColumnFamilyRef, ColumnFamily, DBIterator, DBPinnableSlice,
-    DBRawIterator, FlushOptions, IteratorMode as RocksIteratorMode, LiveFile,
-    Options, WriteBatch as RWriteBatch, DB,
+    BoundColumnFamily, DBIterator, DBPinnableSlice, DBRawIterator, Direction,
+    FlushOptions, IteratorMode as RocksIteratorMode, LiveFile, Options,
+    ReadOptions, Snapshot, WriteBatch as RWriteBatch, WriteOptions, DB,
 };
 
 use super::{
-    cf_descriptors::cf_descriptors,
-    columns::Column,
-    iterator::IteratorMode,
+    cf_descriptors::{cf_descriptors, namespace_cf_options},
+    columns::{cf_name, Column},
+    iterator::{prefix_upper_bound, IteratorMode},
     options::{AccessType, LedgerOptions},
     rocksdb_options::get_rocksdb_options,
 };
 use crate::errors::{LedgerError, LedgerResult};
 
+/// Handle of an opened column family, see [Rocks::cf_handle]
+pub type CfHandle<'a> = Arc<BoundColumnFamily<'a>>;
+
+/// Databases opened by namespaced ledgers, keyed by their canonical path,
+/// so that the ledgers of other namespaces can share them instead of
+/// failing to acquire the lock of the database
+static SHARED_DATABASES: LazyLock<Mutex<HashMap<PathBuf, SharedDatabase>>> =
+    LazyLock::new(Default::default);
+
+struct SharedDatabase {
+    db: Weak<DB>,
+    options: DatabaseOptions,
+}
+
+/// The [LedgerOptions] that apply to the database as a whole rather than to
+/// its column families. Ledgers sharing a database must agree on them, since
+/// only the ones of the ledger opening it take effect.
+/// Envs can't be compared, so only whether a custom one is used is.
+#[derive(Debug, PartialEq)]
+struct DatabaseOptions {
+    max_open_files: i32,
+    rate_limiter_bytes_per_sec: Option<i64>,
+    max_background_jobs: Option<i32>,
+    max_total_wal_size: u64,
+    wal_size_limit_mb: u64,
+    recycle_log_file_num: usize,
+    enable_statistics: bool,
+    custom_env: bool,
+}
+
+impl From<&LedgerOptions> for DatabaseOptions {
+    fn from(options: &LedgerOptions) -> Self {
+        Self {
+            max_open_files: options.max_open_files,
+            rate_limiter_bytes_per_sec: options.rate_limiter_bytes_per_sec,
+            max_background_jobs: options.max_background_jobs,
+            max_total_wal_size: options.max_total_wal_size,
+            wal_size_limit_mb: options.wal_size_limit_mb,
+            recycle_log_file_num: options.recycle_log_file_num,
+            enable_statistics: options.enable_statistics,
+            custom_env: options.env.is_some(),
+        }
+    }
+}
+
 // -----------------
 // Rocks
 // -----------------
 #[derive(Debug)]
 pub struct Rocks {
-    pub db: DB,
+    pub db: Arc<DB>,
     access_type: AccessType,
+    // Prefix of the column family names, see [LedgerOptions::namespace]
+    namespace: Option<String>,
 }
 
 impl Rocks {
@@ -29,17 +82,112 @@ impl Rocks {
         let access_type = options.access_type.clone();
         fs::create_dir_all(path)?;
 
-        let db_options = get_rocksdb_options(&access_type);
-        let descriptors = cf_descriptors(path, &options);
-
         let db = match access_type {
+            AccessType::Primary if options.namespace.is_some() => {
+                Self::open_shared(path, &options)?
+            }
             AccessType::Primary => {
-                DB::open_cf_descriptors(&db_options, path, descriptors)?
+                let db_options = get_rocksdb_options(&options);
+                let descriptors = cf_descriptors(path, &options);
+                Arc::new(DB::open_cf_descriptors(
+                    &db_options,
+                    path,
+                    descriptors,
+                )?)
             }
             _ => unreachable!("Only primary access is supported"),
         };
 
-        Ok(Self { db, access_type })
+        Ok(Self {
+            db,
+            access_type,
+            namespace: options.namespace,
+        })
+    }
+
+    /// Reuses the database if a ledger of another namespace already opened
+    /// it, creating the column families of a new namespace in it, otherwise
+    /// opens it with the columns of all the namespaces that exist at this
+    /// point.
+    /// Fails with [LedgerError::SharedDatabaseOptionsMismatch] if the
+    /// database level options differ from the ones it was opened with.
+    fn open_shared(
+        path: &Path,
+        options: &LedgerOptions,
+    ) -> LedgerResult<Arc<DB>> {
+        let mut shared_databases =
+            SHARED_DATABASES.lock().expect("shared databases poisoned");
+        shared_databases.retain(|_, shared| shared.db.strong_count() > 0);
+
+        let canonical_path = fs::canonicalize(path)?;
+        let database_options = DatabaseOptions::from(options);
+        if let Some((db, shared)) = shared_databases
+            .get(&canonical_path)
+            .and_then(|shared| Some((shared.db.upgrade()?, shared)))
+        {
+            if shared.options != database_options {
+                return Err(LedgerError::SharedDatabaseOptionsMismatch(
+                    canonical_path,
+                ));
+            }
+            let namespace = options.namespace.as_deref();
+            for (name, cf_options) in namespace_cf_options(
+                namespace,
+                options,
+                options.cleanup_compaction_filter,
+            ) {
+                if db.cf_handle(&name).is_none() {
+                    db.create_cf(&name, &cf_options)?;
+                }
+            }
+            return Ok(db);
+        }
+
+        let db_options = get_rocksdb_options(options);
+        let descriptors = cf_descriptors(path, options);
+        let db =
+            Arc::new(DB::open_cf_descriptors(&db_options, path, descriptors)?);
+        shared_databases.insert(
+            canonical_path,
+            SharedDatabase {
+                db: Arc::downgrade(&db),
+                options: database_options,
+            },
+        );
+        Ok(db)
+    }
+
+    /// Opens only the `columns` of an existing database in read only mode,
+    /// the remaining columns are not loaded at all
+    pub fn open_read_only(
+        path: &Path,
+        mut options: LedgerOptions,
+        columns: &[&'static str],
+    ) -> LedgerResult<Self> {
+        options.access_type = AccessType::ReadOnly;
+        let namespace = options.namespace.as_deref();
+        let db_options = get_rocksdb_options(&options);
+        let descriptors =
+            cf_descriptors(path, &options)
+                .into_iter()
+                .filter(|descriptor| {
+                    columns.iter().any(|column| {
+                        cf_name(namespace, column) == descriptor.name()
+                    })
+                });
+
+        let db = DB::open_cf_descriptors_read_only(
+            &db_options,
+            path,
+            descriptors,
+            false,
+        )?;
+
+        Ok(Self {
+            db: Arc::new(db),
+            access_type: options.access_type,
+            namespace: options.namespace,
+        })
     }
 
     pub fn destroy(path: &Path) -> LedgerResult<()> {
@@ -48,15 +196,27 @@ impl Rocks {
         Ok(())
     }
 
-    pub fn cf_handle(&self, cf: &str) -> &ColumnFamily {
+    /// Name of the column family backing the column `cf` in the namespace
+    /// of this database
+    pub fn cf_name<'a>(&self, cf: &'a str) -> Cow<'a, str> {
+        cf_name(self.namespace.as_deref(), cf)
+    }
+
+    pub fn cf_handle(&self, cf: &str) -> CfHandle {
         self.db
-            .cf_handle(cf)
+            .cf_handle(&self.cf_name(cf))
             .expect("should never get an unknown column")
     }
 
+    pub fn try_cf_handle(&self, cf: &'static str) -> LedgerResult<CfHandle> {
+        self.db
+            .cf_handle(&self.cf_name(cf))
+            .ok_or(LedgerError::ColumnNotOpened(cf))
+    }
+
     pub fn get_cf(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         key: &[u8],
     ) -> LedgerResult<Option<Vec<u8>>> {
         let opt = self.db.get_cf(cf, key)?;
@@ -65,7 +225,7 @@ impl Rocks {
 
     pub fn get_pinned_cf(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         key: &[u8],
     ) -> LedgerResult<Option<DBPinnableSlice>> {
         let opt = self.db.get_pinned_cf(cf, key)?;
@@ -74,7 +234,7 @@ impl Rocks {
 
     pub fn put_cf(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         key: &[u8],
         value: &[u8],
     ) -> LedgerResult<()> {
@@ -82,9 +242,30 @@ impl Rocks {
         Ok(())
     }
 
+    pub fn put_cf_opt(
+        &self,
+        cf: &CfHandle,
+        key: &[u8],
+        value: &[u8],
+        write_options: &WriteOptions,
+    ) -> LedgerResult<()> {
+        self.db.put_cf_opt(cf, key, value, write_options)?;
+        Ok(())
+    }
+
+    pub fn merge_cf(
+        &self,
+        cf: &CfHandle,
+        key: &[u8],
+        value: &[u8],
+    ) -> LedgerResult<()> {
+        self.db.merge_cf(cf, key, value)?;
+        Ok(())
+    }
+
     pub fn multi_get_cf(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         keys: Vec<&[u8]>,
     ) -> Vec<LedgerResult<Option<DBPinnableSlice>>> {
         let values = self
@@ -99,15 +280,26 @@ impl Rocks {
         values
     }
 
-    pub fn delete_cf(&self, cf: &ColumnFamily, key: &[u8]) -> LedgerResult<()> {
+    pub fn delete_cf(&self, cf: &CfHandle, key: &[u8]) -> LedgerResult<()> {
         self.db.delete_cf(cf, key)?;
         Ok(())
     }
 
+    /// Deletes keys in range \[`from`, `to`).
+    pub fn delete_range_cf(
+        &self,
+        cf: &CfHandle,
+        from_key: &[u8],
+        to_key: &[u8],
+    ) -> LedgerResult<()> {
+        self.db.delete_range_cf(cf, from_key, to_key)?;
+        Ok(())
+    }
+
     /// Delete files whose slot range is within \[`from`, `to`\].
     pub fn delete_file_in_range_cf(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         from_key: &[u8],
         to_key: &[u8],
     ) -> LedgerResult<()> {
@@ -121,7 +313,7 @@ impl Rocks {
     /// https://github.com/facebook/rocksdb/wiki/Manual-Compaction#compactrange
     pub fn compact_range_cf<S: AsRef<[u8]>, E: AsRef<[u8]>>(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         from_key: Option<S>,
         to_key: Option<E>,
     ) {
@@ -129,22 +321,28 @@ impl Rocks {
     }
 
     /// Flushes column family
-    pub fn flush_cf(&self, cf: &ColumnFamily) -> LedgerResult<()> {
+    pub fn flush_cf(&self, cf: &CfHandle) -> LedgerResult<()> {
         Ok(self.db.flush_cf(cf)?)
     }
 
+    /// Flushes the WAL buffer to its file, `sync` additionally fsyncs it
+    pub fn flush_wal(&self, sync: bool) -> LedgerResult<()> {
+        Ok(self.db.flush_wal(sync)?)
+    }
+
     /// Flushed column families
     pub fn flush_cfs_opt(
         &self,
-        cfs: &[&impl AsColumnFamilyRef],
+        cfs: &[CfHandle],
         options: &FlushOptions,
     ) -> LedgerResult<()> {
-        Ok(self.db.flush_cfs_opt(cfs, options)?)
+        let cfs: Vec<_> = cfs.iter().collect();
+        Ok(self.db.flush_cfs_opt(&cfs, options)?)
     }
 
     pub fn iterator_cf<C>(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         iterator_mode: IteratorMode<C::Index>,
     ) -> DBIterator
     where
@@ -156,6 +354,9 @@ impl Rocks {
                 start_key = C::key(start_from);
                 RocksIteratorMode::From(&start_key, direction)
             }
+            IteratorMode::FromPrefix(prefix, direction) => {
+                return self.prefix_iterator_cf(cf, prefix, direction);
+            }
             IteratorMode::Start => RocksIteratorMode::Start,
             IteratorMode::End => RocksIteratorMode::End,
         };
@@ -164,7 +365,7 @@ impl Rocks {
 
     pub fn iterator_cf_raw_key(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         iterator_mode: IteratorMode<Vec<u8>>,
     ) -> DBIterator {
         let start_key;
@@ -173,16 +374,45 @@ impl Rocks {
                 start_key = start_from;
                 RocksIteratorMode::From(&start_key, direction)
             }
+            IteratorMode::FromPrefix(prefix, direction) => {
+                return self.prefix_iterator_cf(cf, prefix, direction);
+            }
             IteratorMode::Start => RocksIteratorMode::Start,
             IteratorMode::End => RocksIteratorMode::End,
         };
         self.db.iterator_cf(cf, iterator_mode)
     }
 
-    pub fn raw_iterator_cf(&self, cf: &ColumnFamily) -> DBRawIterator {
+    /// Iterates over the keys starting with `prefix` only, bounding the
+    /// iterator so that it stops at the end of the prefix in both directions
+    fn prefix_iterator_cf(
+        &self,
+        cf: &CfHandle,
+        prefix: Vec<u8>,
+        direction: Direction,
+    ) -> DBIterator {
+        let mut read_options = ReadOptions::default();
+        if let Some(upper_bound) = prefix_upper_bound(&prefix) {
+            read_options.set_iterate_upper_bound(upper_bound);
+        }
+        read_options.set_iterate_lower_bound(prefix);
+        let iterator_mode = match direction {
+            Direction::Forward => RocksIteratorMode::Start,
+            Direction::Reverse => RocksIteratorMode::End,
+        };
+        self.db.iterator_cf_opt(cf, read_options, iterator_mode)
+    }
+
+    pub fn raw_iterator_cf(&self, cf: &CfHandle) -> DBRawIterator {
         self.db.raw_iterator_cf(cf)
     }
 
+    /// Takes a snapshot of the current state of the database,
+    /// it's released when dropped
+    pub fn snapshot(&self) -> Snapshot {
+        self.db.snapshot()
+    }
+
     pub fn batch(&self) -> RWriteBatch {
         RWriteBatch::default()
     }
@@ -219,7 +449,7 @@ impl Rocks {
     /// [here](https://github.com/facebook/rocksdb/blob/08809f5e6cd9cc4bc3958dd4d59457ae78c76660/include/rocksdb/db.h#L654-L689).
     pub fn get_int_property_cf(
         &self,
-        cf: &ColumnFamily,
+        cf: &CfHandle,
         name: &'static std::ffi::CStr,
     ) -> LedgerResult<i64> {
         match self.db.property_int_value_cf(cf, name) {
@@ -229,6 +459,26 @@ impl Rocks {
         }
     }
 
+    /// Loads SST files created with [rocksdb::SstFileWriter] into the
+    /// column family, bypassing the memtables and the WAL
+    pub fn ingest_external_file_cf(
+        &self,
+        cf: &CfHandle,
+        paths: &[PathBuf],
+    ) -> LedgerResult<()> {
+        self.db.ingest_external_file_cf(cf, paths.to_vec())?;
+        Ok(())
+    }
+
+    /// Retrieves the specified RocksDB string property of the database,
+    /// `None` if it's not available
+    pub fn get_property(
+        &self,
+        name: &'static std::ffi::CStr,
+    ) -> LedgerResult<Option<String>> {
+        Ok(self.db.property_value(name)?)
+    }
+
     pub fn live_files_metadata(&self) -> LedgerResult<Vec<LiveFile>> {
         match self.db.live_files() {
             Ok(live_files) => Ok(live_files),
@@ -271,8 +521,8 @@ mod tests {
             let mut rocks = Rocks::open(db_path, options).unwrap();
 
             // Introduce a new column that will not be known
-            rocks
-                .db
+            Arc::get_mut(&mut rocks.db)
+                .unwrap()
                 .create_cf("new_column", &Options::default())
                 .unwrap();
         }
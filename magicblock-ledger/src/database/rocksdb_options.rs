@@ -1,8 +1,9 @@
 use rocksdb::Options;
 
-use super::options::AccessType;
+use super::options::{AccessType, LedgerOptions};
 
-pub fn get_rocksdb_options(access_type: &AccessType) -> Options {
+pub fn get_rocksdb_options(ledger_options: &LedgerOptions) -> Options {
+    let access_type = &ledger_options.access_type;
     let mut options = Options::default();
 
     // Create missing items to support a clean start
@@ -11,8 +12,14 @@ pub fn get_rocksdb_options(access_type: &AccessType) -> Options {
 
     // Per the docs, a good value for this is the number of cores on the machine
     options.increase_parallelism(num_cpus::get() as i32);
+    if let Some(max_background_jobs) = ledger_options.max_background_jobs {
+        options.set_max_background_jobs(max_background_jobs);
+    }
 
-    let mut env = rocksdb::Env::new().unwrap();
+    let mut env = match &ledger_options.env {
+        Some(env) => env.clone(),
+        None => rocksdb::Env::new().unwrap(),
+    };
     // While a compaction is ongoing, all the background threads
     // could be used by the compaction. This can stall writes which
     // need to flush the memtable. Add some high-priority background threads
@@ -20,17 +27,27 @@ pub fn get_rocksdb_options(access_type: &AccessType) -> Options {
     env.set_high_priority_background_threads(4);
     options.set_env(&env);
 
-    // Set max total wal size to 4G.
-    options.set_max_total_wal_size(4 * 1024 * 1024 * 1024);
+    options.set_max_total_wal_size(ledger_options.max_total_wal_size);
+    options.set_wal_size_limit_mb(ledger_options.wal_size_limit_mb);
+    options.set_recycle_log_file_num(ledger_options.recycle_log_file_num);
 
     if should_disable_auto_compactions(access_type) {
         options.set_disable_auto_compactions(true);
     }
 
-    // Allow Rocks to open/keep open as many files as it needs for performance;
-    // however, this is also explicitly required for a secondary instance.
+    // Allow Rocks to open/keep open as many files as it needs for performance
+    // by default; however, this is also explicitly required for a secondary instance.
     // See https://github.com/facebook/rocksdb/wiki/Secondary-instance
-    options.set_max_open_files(-1);
+    options.set_max_open_files(ledger_options.max_open_files);
+
+    if let Some(bytes_per_sec) = ledger_options.rate_limiter_bytes_per_sec {
+        // Same refill period and fairness as RocksDB's defaults
+        options.set_ratelimiter(bytes_per_sec, 100_000, 10);
+    }
+
+    if ledger_options.enable_statistics {
+        options.enable_statistics();
+    }
 
     options
 }
@@ -1,24 +1,91 @@
 use std::collections::HashMap;
 
-use bincode::serialize;
-use rocksdb::{ColumnFamily, WriteBatch as RWriteBatch};
+use prost::Message;
+use rocksdb::WriteBatch as RWriteBatch;
+use solana_sdk::clock::Slot;
 
-use super::columns::{Column, ColumnName, TypedColumn};
+use super::{
+    codec::ColumnCodec,
+    columns::{is_slot_keyed, Column, ColumnName, ProtobufColumn, TypedColumn},
+    rocks_db::CfHandle,
+};
 use crate::errors::LedgerError;
 
+/// Operation queued in a [WriteBatch], see [WriteBatch::iter_ops].
+/// Values aren't kept, only the keys they're written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOp {
+    Put {
+        cf: &'static str,
+        key: Vec<u8>,
+    },
+    Delete {
+        cf: &'static str,
+        key: Vec<u8>,
+    },
+    /// Deletes the \[`from`, `to`) range
+    DeleteRange {
+        cf: &'static str,
+        from: Vec<u8>,
+        to: Vec<u8>,
+    },
+}
+
+impl BatchOp {
+    /// Name of the column the operation modifies
+    pub fn cf(&self) -> &'static str {
+        match self {
+            BatchOp::Put { cf, .. }
+            | BatchOp::Delete { cf, .. }
+            | BatchOp::DeleteRange { cf, .. } => cf,
+        }
+    }
+}
+
 pub struct WriteBatch<'a> {
     pub write_batch: RWriteBatch,
-    pub map: HashMap<&'static str, &'a ColumnFamily>,
+    pub map: HashMap<&'static str, CfHandle<'a>>,
+    /// First column the batch tried to modify that wasn't opened,
+    /// writing the batch fails if set
+    pub column_not_opened: Option<&'static str>,
+    /// Operations queued so far, in order
+    pub ops: Vec<BatchOp>,
+    /// Lowest slot the puts to slot keyed columns target, see
+    /// [is_slot_keyed]
+    pub min_put_slot: Option<Slot>,
 }
 
 impl<'a> WriteBatch<'a> {
+    /// Number of queued operations
+    pub fn len(&self) -> usize {
+        self.write_batch.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.write_batch.is_empty()
+    }
+
+    /// Size of the serialized batch, including the values
+    pub fn size_in_bytes(&self) -> usize {
+        self.write_batch.size_in_bytes()
+    }
+
+    /// Describes the queued operations in the order they are applied,
+    /// i.e. to log or validate a batch before writing it
+    pub fn iter_ops(&self) -> impl Iterator<Item = &BatchOp> {
+        self.ops.iter()
+    }
+
     pub fn put_bytes<C: Column + ColumnName>(
         &mut self,
         key: C::Index,
         bytes: &[u8],
     ) {
-        self.write_batch
-            .put_cf(self.get_cf::<C>(), C::key(key), bytes);
+        if let Some(cf) = self.get_cf::<C>() {
+            let key = C::key(key);
+            self.write_batch.put_cf(&cf, &key, bytes);
+            self.record_put::<C>(key);
+        }
     }
 
     pub fn delete<C: Column + ColumnName>(&mut self, key: C::Index) {
@@ -26,7 +93,13 @@ impl<'a> WriteBatch<'a> {
     }
 
     pub(crate) fn delete_raw<C: Column + ColumnName>(&mut self, key: &[u8]) {
-        self.write_batch.delete_cf(self.get_cf::<C>(), key);
+        if let Some(cf) = self.get_cf::<C>() {
+            self.write_batch.delete_cf(&cf, key);
+            self.ops.push(BatchOp::Delete {
+                cf: C::NAME,
+                key: key.to_vec(),
+            });
+        }
     }
 
     pub fn put<C: TypedColumn + ColumnName>(
@@ -34,18 +107,48 @@ impl<'a> WriteBatch<'a> {
         key: C::Index,
         value: &C::Type,
     ) -> Result<(), LedgerError> {
-        let serialized_value = serialize(&value)?;
-        self.write_batch.put_cf(
-            self.get_cf::<C>(),
-            C::key(key),
-            serialized_value,
-        );
+        let serialized_value = C::Codec::encode(value)?;
+        if let Some(cf) = self.get_cf::<C>() {
+            let key = C::key(key);
+            self.write_batch.put_cf(&cf, &key, serialized_value);
+            self.record_put::<C>(key);
+        }
         Ok(())
     }
 
+    pub fn put_protobuf<C: ProtobufColumn + ColumnName>(
+        &mut self,
+        key: C::Index,
+        value: &C::Type,
+    ) {
+        let serialized_value = value.encode_to_vec();
+        if let Some(cf) = self.get_cf::<C>() {
+            let key = C::key(key);
+            self.write_batch.put_cf(&cf, &key, serialized_value);
+            self.record_put::<C>(key);
+        }
+    }
+
+    fn record_put<C: Column + ColumnName>(&mut self, key: Vec<u8>) {
+        if is_slot_keyed::<C>() {
+            let slot = C::slot(C::index(&key));
+            self.min_put_slot = Some(
+                self.min_put_slot
+                    .map_or(slot, |min_slot| min_slot.min(slot)),
+            );
+        }
+        self.ops.push(BatchOp::Put { cf: C::NAME, key });
+    }
+
+    /// Returns the handle of the column, or records it in
+    /// [WriteBatch::column_not_opened] if it wasn't opened
     #[inline]
-    pub fn get_cf<C: Column + ColumnName>(&self) -> &'a ColumnFamily {
-        self.map[C::NAME]
+    fn get_cf<C: Column + ColumnName>(&mut self) -> Option<CfHandle<'a>> {
+        let cf = self.map.get(C::NAME).cloned();
+        if cf.is_none() {
+            self.column_not_opened.get_or_insert(C::NAME);
+        }
+        cf
     }
 
     /// Adds a \[`from`, `to`) range deletion entry to the batch.
@@ -54,13 +157,19 @@ impl<'a> WriteBatch<'a> {
     /// is different from \[`from`, `to`\] of Database::delete_range_cf as we makes
     /// the semantics of Database::delete_range_cf matches the blockstore purge
     /// logic.
-    pub fn delete_range_cf<C: Column>(
+    pub fn delete_range_cf<C: Column + ColumnName>(
         &mut self,
-        cf: &ColumnFamily,
         from: C::Index,
         to: C::Index, // exclusive
     ) {
-        self.write_batch
-            .delete_range_cf(cf, C::key(from), C::key(to));
+        if let Some(cf) = self.get_cf::<C>() {
+            let (from, to) = (C::key(from), C::key(to));
+            self.write_batch.delete_range_cf(&cf, &from, &to);
+            self.ops.push(BatchOp::DeleteRange {
+                cf: C::NAME,
+                from,
+                to,
+            });
+        }
     }
 }
@@ -1,8 +1,19 @@
 use magicblock_accounts_db::error::AccountsDbError;
+use solana_sdk::hash::Hash;
 use thiserror::Error;
 
 pub type LedgerResult<T> = Result<T, LedgerError>;
 
+/// Failure to encode or decode a value with a
+/// [crate::database::codec::ColumnCodec]
+#[derive(Error, Debug)]
+pub enum CodecError {
+    #[error(transparent)]
+    Bincode(#[from] Box<bincode::ErrorKind>),
+    #[error(transparent)]
+    Borsh(#[from] std::io::Error),
+}
+
 #[derive(Error, Debug)]
 pub enum LedgerError {
     #[error("RocksDB error: {0}")]
@@ -13,6 +24,14 @@ pub enum LedgerError {
     FsExtraError(#[from] fs_extra::error::Error),
     #[error("serialization error: {0}")]
     Serialize(#[from] Box<bincode::ErrorKind>),
+    #[error("failed to encode value: {0}")]
+    Encode(CodecError),
+    #[error("failed to deserialize {column} value at key {key_hex}: {source}")]
+    Deserialize {
+        column: &'static str,
+        key_hex: String,
+        source: CodecError,
+    },
     #[error("protobuf encode error: {0}")]
     ProtobufEncodeError(#[from] prost::EncodeError),
     #[error("protobuf decode error: {0}")]
@@ -31,10 +50,243 @@ pub enum LedgerError {
     TransactionIndexOverflow,
     #[error("Failed to convert transaction {0}")]
     TransactionConversionError(String),
-    #[error("slot cleaned up")]
-    SlotCleanedUp,
+    #[error("slot {slot} cleaned up, lowest cleanup slot is {lowest_cleanup}")]
+    SlotCleanedUp { slot: u64, lowest_cleanup: u64 },
+    #[error("block of slot {slot} has hash {existing}, refusing to overwrite it with {provided}")]
+    BlockHashMismatch {
+        slot: u64,
+        existing: Hash,
+        provided: Hash,
+    },
     #[error("try from slice error: {0}")]
     TryFromSliceError(#[from] std::array::TryFromSliceError),
     #[error("BlockstoreProcessorError: {0}")]
     BlockStoreProcessor(String),
+    #[error("writes to column {0} are stalled")]
+    WriteStalled(&'static str),
+    #[error("writes to column {0} exceed its rate limit")]
+    RateLimited(&'static str),
+    #[error("column {0} was not opened")]
+    ColumnNotOpened(&'static str),
+    #[error("unknown column {0}")]
+    UnknownColumn(String),
+    #[error("ledger is still locked by another process after {attempts} attempts: {source}")]
+    LedgerLocked {
+        attempts: u32,
+        source: rocksdb::Error,
+    },
+    #[error("ledger is still used by {0} other handles")]
+    LedgerInUse(usize),
+    #[error("database {0:?} is already open with different database options")]
+    SharedDatabaseOptionsMismatch(std::path::PathBuf),
+    #[error("blocking ledger task failed: {0}")]
+    BlockingTaskFailed(#[from] tokio::task::JoinError),
+}
+
+/// Coarse classification of a [LedgerError], i.e. to map it to an RPC
+/// status code, see [LedgerError::kind]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerErrorKind {
+    /// The requested data doesn't exist or was cleaned up
+    NotFound,
+    /// Stored data is inconsistent or fails to decode
+    Corruption,
+    /// Reading from or writing to the filesystem failed
+    Io,
+    /// Encoding a value or converting it for the caller failed
+    Serialization,
+    /// The operation isn't available since the ledger or the column was
+    /// opened read only
+    ReadOnly,
+    /// Any other failure of the ledger or of RocksDB
+    Backend,
+}
+
+impl LedgerError {
+    /// Max number of key bytes included in [LedgerError::Deserialize]
+    const MAX_KEY_HEX_BYTES: usize = 32;
+
+    pub fn kind(&self) -> LedgerErrorKind {
+        use LedgerErrorKind as Kind;
+        match self {
+            Self::RocksDb(err) => rocksdb_error_kind(err),
+            Self::Io(_)
+            | Self::FsExtraError(_)
+            | Self::UnableToSetOpenFileDescriptorLimit => Kind::Io,
+            Self::Serialize(_)
+            | Self::Encode(_)
+            | Self::ProtobufEncodeError(_)
+            | Self::ProtobufDecodeError(_)
+            | Self::TryFromSliceError(_)
+            | Self::TransactionConversionError(_) => Kind::Serialization,
+            Self::Deserialize { .. }
+            | Self::TransactionStatusSlotMismatch
+            | Self::BlockHashMismatch { .. } => Kind::Corruption,
+            Self::TransactionNotFound
+            | Self::TransactionStatusMetaNotFound
+            | Self::SlotCleanedUp { .. }
+            | Self::UnknownColumn(_) => Kind::NotFound,
+            // Columns are only left unopened by read only opens
+            Self::ColumnNotOpened(_) => Kind::ReadOnly,
+            Self::AccountsDbError(_)
+            | Self::TransactionIndexOverflow
+            | Self::BlockStoreProcessor(_)
+            | Self::WriteStalled(_)
+            | Self::RateLimited(_)
+            | Self::LedgerLocked { .. }
+            | Self::LedgerInUse(_)
+            | Self::SharedDatabaseOptionsMismatch(_)
+            | Self::BlockingTaskFailed(_) => Kind::Backend,
+        }
+    }
+
+    /// Whether RocksDB failed to acquire the LOCK file of the database,
+    /// i.e. since another process still has it open
+    pub fn is_lock_contention(&self) -> bool {
+        match self {
+            Self::RocksDb(err) => {
+                // RocksDB reports the path of the LOCK file either way,
+                // whether it's held by another process or this one
+                err.kind() == rocksdb::ErrorKind::IOError
+                    && err.as_ref().contains("LOCK:")
+            }
+            _ => false,
+        }
+    }
+
+    /// Wraps a codec error hit while decoding the value stored under `key`
+    /// in `column`, keeping a hex-encoded snippet of the key for debugging.
+    pub(crate) fn deserialize(
+        column: &'static str,
+        key: &[u8],
+        source: CodecError,
+    ) -> Self {
+        let mut key_hex = key
+            .iter()
+            .take(Self::MAX_KEY_HEX_BYTES)
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        if key.len() > Self::MAX_KEY_HEX_BYTES {
+            key_hex.push_str("..");
+        }
+        Self::Deserialize {
+            column,
+            key_hex,
+            source,
+        }
+    }
+}
+
+fn rocksdb_error_kind(err: &rocksdb::Error) -> LedgerErrorKind {
+    match err.kind() {
+        rocksdb::ErrorKind::NotFound => LedgerErrorKind::NotFound,
+        rocksdb::ErrorKind::Corruption => LedgerErrorKind::Corruption,
+        rocksdb::ErrorKind::IOError => LedgerErrorKind::Io,
+        // Writes to read only and secondary instances are reported as
+        // "Not supported operation in read only/secondary mode."
+        rocksdb::ErrorKind::NotSupported
+            if err.as_ref().contains("read only mode")
+                || err.as_ref().contains("secondary mode") =>
+        {
+            LedgerErrorKind::ReadOnly
+        }
+        _ => LedgerErrorKind::Backend,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::database::{
+        columns::{self as cf, ColumnName},
+        db::Database,
+        options::LedgerOptions,
+    };
+
+    #[test]
+    fn test_error_kind() {
+        use LedgerErrorKind as Kind;
+
+        let temp_dir = tempdir().unwrap();
+        let db =
+            Database::open(temp_dir.path(), LedgerOptions::default()).unwrap();
+        db.column::<cf::Blocktime>().put(1, &1).unwrap();
+        // RocksDB errors are classified by their kind
+        let locked = Database::open(temp_dir.path(), LedgerOptions::default())
+            .unwrap_err();
+        assert_eq!(locked.kind(), Kind::Io);
+        let read_only = Database::open_columns(
+            temp_dir.path(),
+            LedgerOptions::default(),
+            &[cf::Blocktime::NAME],
+        )
+        .unwrap();
+        let err = read_only.column::<cf::Blocktime>().put(2, &2).unwrap_err();
+        assert!(matches!(err, LedgerError::RocksDb(_)), "{err}");
+        assert_eq!(err.kind(), Kind::ReadOnly);
+        let err = read_only.column::<cf::Blockhash>().get(1).unwrap_err();
+        assert!(matches!(err, LedgerError::ColumnNotOpened(_)));
+        assert_eq!(err.kind(), Kind::ReadOnly);
+
+        let io_err = || std::io::Error::other("io");
+        let cases = [
+            (LedgerError::Io(io_err()), Kind::Io),
+            (LedgerError::UnableToSetOpenFileDescriptorLimit, Kind::Io),
+            (
+                LedgerError::Serialize(Box::new(bincode::ErrorKind::SizeLimit)),
+                Kind::Serialization,
+            ),
+            (
+                LedgerError::Encode(CodecError::Borsh(io_err())),
+                Kind::Serialization,
+            ),
+            (
+                LedgerError::TransactionConversionError("tx".to_string()),
+                Kind::Serialization,
+            ),
+            (
+                LedgerError::deserialize(
+                    cf::Blocktime::NAME,
+                    &[1],
+                    CodecError::Borsh(io_err()),
+                ),
+                Kind::Corruption,
+            ),
+            (LedgerError::TransactionStatusSlotMismatch, Kind::Corruption),
+            (
+                LedgerError::BlockHashMismatch {
+                    slot: 1,
+                    existing: Hash::new_unique(),
+                    provided: Hash::new_unique(),
+                },
+                Kind::Corruption,
+            ),
+            (LedgerError::TransactionNotFound, Kind::NotFound),
+            (LedgerError::TransactionStatusMetaNotFound, Kind::NotFound),
+            (
+                LedgerError::SlotCleanedUp {
+                    slot: 1,
+                    lowest_cleanup: 2,
+                },
+                Kind::NotFound,
+            ),
+            (LedgerError::UnknownColumn("x".to_string()), Kind::NotFound),
+            (LedgerError::TransactionIndexOverflow, Kind::Backend),
+            (
+                LedgerError::BlockStoreProcessor("x".to_string()),
+                Kind::Backend,
+            ),
+            (
+                LedgerError::WriteStalled(cf::Blocktime::NAME),
+                Kind::Backend,
+            ),
+            (LedgerError::RateLimited(cf::Blocktime::NAME), Kind::Backend),
+            (LedgerError::LedgerInUse(1), Kind::Backend),
+        ];
+        for (err, kind) in cases {
+            assert_eq!(err.kind(), kind, "{err}");
+        }
+    }
 }
@@ -1,91 +1,265 @@
-use std::{cmp::min, sync::Arc, time::Duration};
+use std::{
+    cmp::min, future::Future, io, path::Path, sync::Arc, time::Duration,
+};
 
 use log::{error, info, warn};
 use magicblock_core::traits::FinalityProvider;
+use solana_sdk::clock::Slot;
 use tokio::{
     task::{JoinError, JoinHandle, JoinSet},
-    time::interval,
+    time::{interval, MissedTickBehavior},
 };
 use tokio_util::sync::CancellationToken;
 
 use crate::{
     database::columns::{
-        AddressSignatures, Blockhash, Blocktime, PerfSamples, SlotSignatures,
-        Transaction, TransactionMemos, TransactionStatus,
+        AddressSignatures, Transaction, TransactionMemos, TransactionStatus,
     },
-    errors::LedgerResult,
-    Ledger,
+    errors::{LedgerError, LedgerResult},
+    ColumnGroup, Ledger,
 };
 
 pub const DEFAULT_TRUNCATION_TIME_INTERVAL: Duration =
     Duration::from_secs(2 * 60);
 const PERCENTAGE_TO_TRUNCATE: u8 = 10;
 
+/// Decides how long each [ColumnGroup] is retained by the [LedgerTruncator]
+pub trait RetentionPolicy: Send + Sync + 'static {
+    /// Returns the highest slot of `group` that may be purged, given that
+    /// `purgeable_slot` is the highest slot the truncator may purge at all.
+    /// Returned values above `purgeable_slot` are ignored.
+    fn effective_purge_slot(
+        &self,
+        group: ColumnGroup,
+        purgeable_slot: Slot,
+    ) -> Slot;
+}
+
+/// Truncates all columns identically
+#[derive(Debug, Default)]
+pub struct UniformRetentionPolicy;
+
+impl RetentionPolicy for UniformRetentionPolicy {
+    fn effective_purge_slot(
+        &self,
+        _group: ColumnGroup,
+        purgeable_slot: Slot,
+    ) -> Slot {
+        purgeable_slot
+    }
+}
+
+/// Reports the free space of the volume a path is stored on, see
+/// [LedgerTruncator::with_min_free_space]
+pub trait FreeSpaceProvider: Send + Sync + 'static {
+    /// Returns the number of bytes available to unprivileged users on the
+    /// volume of `path`
+    fn free_space(&self, path: &Path) -> io::Result<u64>;
+}
+
+/// Queries the filesystem via `statvfs`
+#[derive(Debug, Default)]
+pub struct StatvfsFreeSpaceProvider;
+
+impl FreeSpaceProvider for StatvfsFreeSpaceProvider {
+    #[cfg(unix)]
+    fn free_space(&self, path: &Path) -> io::Result<u64> {
+        use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+        if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // statvfs succeeded, so it initialized `stat`
+        let stat = unsafe { stat.assume_init() };
+        // The field types differ across platforms
+        #[allow(clippy::unnecessary_cast)]
+        Ok((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+    }
+
+    #[cfg(not(unix))]
+    fn free_space(&self, _path: &Path) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "statvfs is only available on unix",
+        ))
+    }
+}
+
 struct LedgerTrunctationWorker<T> {
     finality_provider: Arc<T>,
+    retention_policy: Arc<dyn RetentionPolicy>,
     ledger: Arc<Ledger>,
     truncation_time_interval: Duration,
     ledger_size: u64,
+    min_free_space: Option<u64>,
+    free_space_provider: Arc<dyn FreeSpaceProvider>,
+    compact_tombstones: bool,
+    catch_up_on_start: bool,
     cancellation_token: CancellationToken,
 }
 
 impl<T: FinalityProvider> LedgerTrunctationWorker<T> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         ledger: Arc<Ledger>,
         finality_provider: Arc<T>,
+        retention_policy: Arc<dyn RetentionPolicy>,
         truncation_time_interval: Duration,
         ledger_size: u64,
+        min_free_space: Option<u64>,
+        free_space_provider: Arc<dyn FreeSpaceProvider>,
+        compact_tombstones: bool,
+        catch_up_on_start: bool,
         cancellation_token: CancellationToken,
     ) -> Self {
         Self {
             ledger,
             finality_provider,
+            retention_policy,
             truncation_time_interval,
             ledger_size,
+            min_free_space,
+            free_space_provider,
+            compact_tombstones,
+            catch_up_on_start,
             cancellation_token,
         }
     }
 
-    pub async fn run(self) {
+    /// Runs a cycle every `truncation_time_interval` as measured by tokio's
+    /// clock, so tests can drive the worker with `tokio::time::pause` and
+    /// `tokio::time::advance` instead of waiting in real time.
+    pub async fn run(self) -> TruncatorSummary {
+        let mut summary = TruncatorSummary::default();
         let mut interval = interval(self.truncation_time_interval);
+        // Cycles that overran the interval or a clock that jumped ahead
+        // result in a single cycle rather than a burst of them
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        if self.catch_up_on_start {
+            self.catch_up(&mut summary).await;
+            // The next cycle is due one interval after catching up
+            interval.reset();
+        }
         loop {
+            // Cancellation is only observed in between cycles,
+            // a started cycle always runs to completion.
+            // No cycle starts once cancelled, even if one is due
             tokio::select! {
+                biased;
                 _ = self.cancellation_token.cancelled() => {
-                    return;
+                    return summary;
                 }
                 _ = interval.tick() => {
-                    // Note: since we clean 10%, tomstones will take around 10% as well
-                    const FILLED_PERCENTAGE_LIMIT: u8 = 100 - PERCENTAGE_TO_TRUNCATE;
-
-                    let current_size = match self.ledger.storage_size() {
-                        Ok(value) => value,
-                        Err(err) => {
-                            error!("Failed to check truncation condition: {err}");
-                            continue;
-                        }
-                    };
-
-                    // Check if we should truncate
-                    if current_size < (self.ledger_size / 100) * FILLED_PERCENTAGE_LIMIT as u64 {
-                        continue;
-                    }
-
-                    info!("Ledger size: {current_size}");
-                    match self.estimate_truncation_range(current_size) {
-                        Ok(Some((from_slot, to_slot))) => Self::truncate_slot_range(&self.ledger, from_slot, to_slot).await,
-                        Ok(None) => warn!("Could not estimate truncation range"),
-                        Err(err) => error!("Failed to estimate truncation range: {:?}", err),
+                    summary.total_cycles += 1;
+                    if let Err(err) = self.run_cycle(&mut summary).await {
+                        error!("LedgerTruncator cycle failed: {err}");
+                        summary.record_error(err);
                     }
                 }
             }
         }
     }
 
-    /// Returns range to truncate [from_slot, to_slot]
+    /// Runs cycles back to back until one doesn't purge anything, i.e.
+    /// because the ledger is below the size limit or there are no more
+    /// final slots to purge, or fails
+    async fn catch_up(&self, summary: &mut TruncatorSummary) {
+        info!("LedgerTruncator: catching up");
+        while !self.cancellation_token.is_cancelled() {
+            summary.total_cycles += 1;
+            let slots_purged = summary.total_slots_purged;
+            if let Err(err) = self.run_cycle(summary).await {
+                error!("LedgerTruncator catch-up cycle failed: {err}");
+                summary.record_error(err);
+                return;
+            }
+            if summary.total_slots_purged == slots_purged {
+                return;
+            }
+        }
+    }
+
+    /// Truncates the ledger if it exceeds the size limit or the volume is
+    /// short of free space, accumulating the outcome into `summary`
+    async fn run_cycle(
+        &self,
+        summary: &mut TruncatorSummary,
+    ) -> LedgerResult<()> {
+        let current_size = self.ledger.sample_storage_size()?;
+
+        // Check if we should truncate
+        let Some(size_to_truncate) = self.size_to_truncate(current_size)?
+        else {
+            return Ok(());
+        };
+
+        info!("Ledger size: {current_size}");
+        let Some((from_slot, to_slot, purgeable_slot)) =
+            self.estimate_truncation_range(current_size, size_to_truncate)?
+        else {
+            warn!("Could not estimate truncation range");
+            return Ok(());
+        };
+
+        let num_purged = Self::truncate_slot_range(
+            &self.ledger,
+            self.retention_policy.as_ref(),
+            from_slot,
+            to_slot,
+            purgeable_slot,
+            self.compact_tombstones,
+        )
+        .await?;
+        summary.total_slots_purged += num_purged;
+
+        let new_size = self.ledger.storage_size()?;
+        summary.total_bytes_reclaimed += current_size.saturating_sub(new_size);
+
+        Ok(())
+    }
+
+    /// Returns the number of bytes worth of slots to truncate, `None` if
+    /// the ledger is within its size limit and the volume has the
+    /// configured free space.
+    ///
+    /// Purged slots only free up space once they're compacted, so the
+    /// free space check may request the same deficit for a few cycles.
+    fn size_to_truncate(&self, current_size: u64) -> LedgerResult<Option<u64>> {
+        // Note: since we clean 10%, tomstones will take around 10% as well
+        const FILLED_PERCENTAGE_LIMIT: u8 = 100 - PERCENTAGE_TO_TRUNCATE;
+
+        let mut size_to_truncate = None;
+        if current_size
+            >= (self.ledger_size / 100) * FILLED_PERCENTAGE_LIMIT as u64
+        {
+            size_to_truncate =
+                Some((current_size / 100) * PERCENTAGE_TO_TRUNCATE as u64);
+        }
+
+        if let Some(min_free_space) = self.min_free_space {
+            let free_space = self
+                .free_space_provider
+                .free_space(self.ledger.ledger_path())?;
+            if free_space < min_free_space {
+                info!("Free space: {free_space}, required: {min_free_space}");
+                let deficit = min_free_space - free_space;
+                size_to_truncate = Some(
+                    size_to_truncate.map_or(deficit, |size| size.max(deficit)),
+                );
+            }
+        }
+        Ok(size_to_truncate)
+    }
+
+    /// Returns range to truncate [from_slot, to_slot] along with
+    /// the highest slot that is safe to truncate
     fn estimate_truncation_range(
         &self,
         current_ledger_size: u64,
-    ) -> LedgerResult<Option<(u64, u64)>> {
+        size_to_truncate: u64,
+    ) -> LedgerResult<Option<(u64, u64, u64)>> {
         let (from_slot, to_slot) =
             if let Some(val) = self.available_truncation_range() {
                 val
@@ -99,13 +273,15 @@ impl<T: FinalityProvider> LedgerTrunctationWorker<T> {
             return Ok(None);
         }
 
-        let slot_size = current_ledger_size / num_slots as u64;
-        let size_to_truncate =
-            (current_ledger_size / 100) * PERCENTAGE_TO_TRUNCATE as u64;
+        // Less than a byte per slot if the size isn't accounted yet,
+        // i.e. while most of the ledger is still in the memtables
+        let slot_size = (current_ledger_size / num_slots as u64).max(1);
         let num_slots_to_truncate = size_to_truncate / slot_size;
 
-        let to_slot = min(from_slot + num_slots_to_truncate, to_slot);
-        Ok(Some((from_slot, to_slot)))
+        let purgeable_slot = to_slot;
+        let to_slot =
+            min(from_slot.saturating_add(num_slots_to_truncate), to_slot);
+        Ok(Some((from_slot, to_slot, purgeable_slot)))
     }
 
     /// Returns [from_slot, to_slot] range that's safe to truncate
@@ -143,56 +319,95 @@ impl<T: FinalityProvider> LedgerTrunctationWorker<T> {
         Some((next_from_slot, latest_final_slot - 1))
     }
 
-    /// Utility function for splitting truncation into smaller chunks
-    /// Cleans slots [from_slot; to_slot] inclusive range
+    /// Cleans slots [from_slot; to_slot] inclusive range,
+    /// see [Ledger::purge_slot_range]
+    ///
+    /// Column groups the `retention_policy` retains longer are only purged
+    /// up to their effective purge slot, in which case the lowest cleanup
+    /// slot only advances up to the lowest of those.
+    ///
+    /// Returns the number of slots purged from all columns
     pub async fn truncate_slot_range(
         ledger: &Arc<Ledger>,
+        retention_policy: &dyn RetentionPolicy,
         from_slot: u64,
         to_slot: u64,
-    ) {
-        // In order not to torture RocksDB's WriteBatch we split large tasks into chunks
-        const SINGLE_TRUNCATION_LIMIT: usize = 300;
-
+        purgeable_slot: u64,
+        compact_tombstones: bool,
+    ) -> LedgerResult<u64> {
         if to_slot < from_slot {
             warn!("LedgerTruncator: Nani?");
-            return;
+            return Ok(0);
         }
 
+        let group_to_slots = ColumnGroup::ALL.map(|group| {
+            let effective_slot =
+                retention_policy.effective_purge_slot(group, purgeable_slot);
+            (group, min(effective_slot, to_slot))
+        });
+        let common_to_slot = group_to_slots
+            .iter()
+            .map(|(_, slot)| *slot)
+            .min()
+            .unwrap_or(to_slot);
+
         info!(
             "LedgerTruncator: truncating slot range [{from_slot}; {to_slot}]"
         );
-        (from_slot..=to_slot)
-            .step_by(SINGLE_TRUNCATION_LIMIT)
-            .for_each(|cur_from_slot| {
-                let num_slots_to_truncate = min(
-                    to_slot - cur_from_slot + 1,
-                    SINGLE_TRUNCATION_LIMIT as u64,
-                );
-                let truncate_to_slot =
-                    cur_from_slot + num_slots_to_truncate - 1;
-
-                if let Err(err) =
-                    ledger.delete_slot_range(cur_from_slot, truncate_to_slot)
-                {
+        let mut num_purged = 0;
+        let mut group_from_slot = from_slot;
+        if common_to_slot >= from_slot {
+            num_purged = ledger
+                .purge_slot_range(from_slot, common_to_slot)
+                .inspect_err(|err| {
                     warn!(
                         "Failed to truncate slots {}-{}: {}",
-                        cur_from_slot, truncate_to_slot, err
-                    );
-                }
-            });
+                        from_slot, common_to_slot, err
+                    )
+                })?;
+            group_from_slot = common_to_slot + 1;
+        }
+        let mut group_result = Ok(());
+        for (group, group_to_slot) in group_to_slots {
+            if group_to_slot < group_from_slot {
+                continue;
+            }
+            if let Err(err) = ledger.purge_column_group_slot_range(
+                group,
+                group_from_slot,
+                group_to_slot,
+            ) {
+                warn!(
+                    "Failed to truncate {:?} slots {}-{}: {}",
+                    group, group_from_slot, group_to_slot, err
+                );
+                group_result = Err(err);
+            }
+        }
         // Flush memtables with tombstones prior to compaction
         if let Err(err) = ledger.flush() {
             error!("Failed to flush ledger: {err}");
         }
 
-        Self::compact_slot_range(ledger, from_slot, to_slot).await;
+        Self::compact_slot_range(
+            ledger,
+            from_slot,
+            to_slot,
+            compact_tombstones,
+        )
+        .await;
+        group_result.map(|_| num_purged)
     }
 
     /// Synchronous utility function that triggers and awaits compaction on all the columns
+    ///
+    /// Slot keyed columns are only compacted if `compact_tombstones` is set,
+    /// see [Ledger::compact_truncated_range]
     pub async fn compact_slot_range(
         ledger: &Arc<Ledger>,
         from_slot: u64,
         to_slot: u64,
+        compact_tombstones: bool,
     ) {
         if to_slot < from_slot {
             warn!("LedgerTruncator: Nani2?");
@@ -203,41 +418,47 @@ impl<T: FinalityProvider> LedgerTrunctationWorker<T> {
         // but it utilizes rocksdb threads, in order not to drain
         // our tokio rt threads, we split the effort in just 3 tasks
         let mut join_set = JoinSet::new();
-        join_set.spawn({
-            let ledger = ledger.clone();
-            async move {
-                ledger.compact_slot_range_cf::<Blocktime>(
-                    Some(from_slot),
-                    Some(to_slot + 1),
-                );
-                ledger.compact_slot_range_cf::<Blockhash>(
-                    Some(from_slot),
-                    Some(to_slot + 1),
-                );
-                ledger.compact_slot_range_cf::<PerfSamples>(
-                    Some(from_slot),
-                    Some(to_slot + 1),
-                );
-                ledger.compact_slot_range_cf::<SlotSignatures>(
-                    Some((from_slot, u32::MIN)),
-                    Some((to_slot + 1, u32::MAX)),
-                );
-            }
-        });
+        if compact_tombstones {
+            join_set.spawn({
+                let ledger = ledger.clone();
+                async move {
+                    if let Err(err) =
+                        ledger.compact_truncated_range(from_slot, to_slot)
+                    {
+                        error!("Failed to compact truncated range: {err}");
+                    }
+                }
+            });
+        }
 
         // Can not compact with specific range
         join_set.spawn({
             let ledger = ledger.clone();
             async move {
-                ledger.compact_slot_range_cf::<TransactionStatus>(None, None);
-                ledger.compact_slot_range_cf::<Transaction>(None, None);
+                for result in [
+                    ledger
+                        .compact_slot_range_cf::<TransactionStatus>(None, None),
+                    ledger.compact_slot_range_cf::<Transaction>(None, None),
+                ] {
+                    if let Err(err) = result {
+                        error!("Failed to compact column: {err}");
+                    }
+                }
             }
         });
         join_set.spawn({
             let ledger = ledger.clone();
             async move {
-                ledger.compact_slot_range_cf::<TransactionMemos>(None, None);
-                ledger.compact_slot_range_cf::<AddressSignatures>(None, None);
+                for result in [
+                    ledger
+                        .compact_slot_range_cf::<TransactionMemos>(None, None),
+                    ledger
+                        .compact_slot_range_cf::<AddressSignatures>(None, None),
+                ] {
+                    if let Err(err) = result {
+                        error!("Failed to compact column: {err}");
+                    }
+                }
             }
         });
 
@@ -245,24 +466,77 @@ impl<T: FinalityProvider> LedgerTrunctationWorker<T> {
     }
 }
 
+/// Summary of the work done by [LedgerTruncator] over its lifetime
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TruncatorSummary {
+    /// Number of truncation intervals the worker went through
+    pub total_cycles: u64,
+    /// Number of slots purged from all columns
+    pub total_slots_purged: u64,
+    /// Storage size reduction observed right after each truncation
+    pub total_bytes_reclaimed: u64,
+    /// Number of cycles that failed
+    pub total_errors: u64,
+    /// Error of the latest failed cycle
+    pub last_error: Option<String>,
+}
+
+impl TruncatorSummary {
+    fn record_error(&mut self, err: LedgerError) {
+        self.total_errors += 1;
+        self.last_error = Some(err.to_string());
+    }
+}
+
 #[derive(Debug)]
 struct WorkerController {
     cancellation_token: CancellationToken,
-    worker_handle: JoinHandle<()>,
+    worker_handle: JoinHandle<TruncatorSummary>,
 }
 
 #[derive(Debug)]
 enum ServiceState {
     Created,
     Running(WorkerController),
-    Stopped(JoinHandle<()>),
+    Stopped(JoinHandle<TruncatorSummary>),
+    Drained,
+}
+
+/// [FinalityProvider] that lags behind `inner` by `margin` slots.
+///
+/// This is the recommended way to keep a retention window of recent final
+/// slots, i.e. for late RPC queries: wrapping the provider passed to
+/// [LedgerTruncator] keeps the last `margin` final slots from being purged.
+pub struct SafetyMarginFinalityProvider {
+    inner: Arc<dyn FinalityProvider>,
+    margin: u64,
+}
+
+impl SafetyMarginFinalityProvider {
+    pub fn new(inner: Arc<dyn FinalityProvider>, margin: u64) -> Self {
+        Self { inner, margin }
+    }
+}
+
+impl FinalityProvider for SafetyMarginFinalityProvider {
+    fn get_latest_final_slot(&self) -> u64 {
+        self.inner
+            .get_latest_final_slot()
+            .saturating_sub(self.margin)
+    }
 }
 
 pub struct LedgerTruncator<T> {
     finality_provider: Arc<T>,
+    retention_policy: Arc<dyn RetentionPolicy>,
     ledger: Arc<Ledger>,
     ledger_size: u64,
+    min_free_space: Option<u64>,
+    free_space_provider: Arc<dyn FreeSpaceProvider>,
     truncation_time_interval: Duration,
+    compact_tombstones: bool,
+    catch_up_on_start: bool,
+    cancellation_token: CancellationToken,
     state: ServiceState,
 }
 
@@ -276,20 +550,92 @@ impl<T: FinalityProvider> LedgerTruncator<T> {
         Self {
             ledger,
             finality_provider,
+            retention_policy: Arc::new(UniformRetentionPolicy),
             truncation_time_interval,
             ledger_size,
+            min_free_space: None,
+            free_space_provider: Arc::new(StatvfsFreeSpaceProvider),
+            compact_tombstones: false,
+            catch_up_on_start: false,
+            cancellation_token: CancellationToken::new(),
             state: ServiceState::Created,
         }
     }
 
+    /// Sets the policy deciding how long each column group is retained,
+    /// defaults to [UniformRetentionPolicy]
+    pub fn with_retention_policy(
+        mut self,
+        retention_policy: Arc<dyn RetentionPolicy>,
+    ) -> Self {
+        self.retention_policy = retention_policy;
+        self
+    }
+
+    /// Additionally truncates the oldest final slots whenever the volume
+    /// the ledger is stored on has less than `min_free_space` bytes free,
+    /// for ledgers on shared volumes. The size limit still applies, pass
+    /// `u64::MAX` as the ledger size to only truncate by free space.
+    pub fn with_min_free_space(mut self, min_free_space: u64) -> Self {
+        self.min_free_space = Some(min_free_space);
+        self
+    }
+
+    /// Sets how the free space of [LedgerTruncator::with_min_free_space] is
+    /// queried, defaults to [StatvfsFreeSpaceProvider]
+    pub fn with_free_space_provider(
+        mut self,
+        free_space_provider: Arc<dyn FreeSpaceProvider>,
+    ) -> Self {
+        self.free_space_provider = free_space_provider;
+        self
+    }
+
+    /// Eagerly compacts the truncated range of the slot keyed columns after
+    /// each truncation, dropping the tombstones that slow down reads of the
+    /// adjacent slots until background compaction catches up.
+    /// Disabled by default since it costs additional I/O.
+    pub fn with_tombstone_compaction(mut self, enabled: bool) -> Self {
+        self.compact_tombstones = enabled;
+        self
+    }
+
+    /// Makes the worker purge in back to back cycles right after starting,
+    /// until it caught up with the size limit and the latest final slot,
+    /// before falling back to the interval cadence.
+    /// Meant for restarts after a downtime that left a large backlog.
+    pub fn with_catch_up_on_start(mut self, enabled: bool) -> Self {
+        self.catch_up_on_start = enabled;
+        self
+    }
+
+    /// Makes the worker also stop once `cancellation_token` is cancelled,
+    /// for services that orchestrate their shutdown with one.
+    /// [LedgerTruncator::stop] keeps working and doesn't cancel the token.
+    pub fn with_cancellation(
+        mut self,
+        cancellation_token: CancellationToken,
+    ) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    /// Starts the worker, the size limit becomes the budget of
+    /// [Ledger::growth_estimate]
     pub fn start(&mut self) {
         if let ServiceState::Created = self.state {
-            let cancellation_token = CancellationToken::new();
+            self.ledger.set_size_budget(Some(self.ledger_size));
+            let cancellation_token = self.cancellation_token.child_token();
             let worker = LedgerTrunctationWorker::new(
                 self.ledger.clone(),
                 self.finality_provider.clone(),
+                self.retention_policy.clone(),
                 self.truncation_time_interval,
                 self.ledger_size,
+                self.min_free_space,
+                self.free_space_provider.clone(),
+                self.compact_tombstones,
+                self.catch_up_on_start,
                 cancellation_token.clone(),
             );
             let worker_handle = tokio::spawn(worker.run());
@@ -303,6 +649,9 @@ impl<T: FinalityProvider> LedgerTruncator<T> {
         }
     }
 
+    /// Signals the worker to stop without waiting for it, a cycle that is
+    /// in flight still runs to completion in the background.
+    /// See [LedgerTruncator::stop_and_drain] to wait for it.
     pub fn stop(&mut self) {
         let state = std::mem::replace(&mut self.state, ServiceState::Created);
         if let ServiceState::Running(controller) = state {
@@ -314,17 +663,53 @@ impl<T: FinalityProvider> LedgerTruncator<T> {
         }
     }
 
-    pub async fn join(mut self) -> Result<(), LedgerTruncatorError> {
+    /// Signals the worker to stop right away and returns a future that
+    /// resolves once the in-flight truncation cycle, if any, has committed
+    /// all its writes and the worker exited.
+    ///
+    /// The intended shutdown order is:
+    /// 1. `stop_and_drain().await`
+    /// 2. [Ledger::flush]
+    ///
+    /// so that the flush observes a ledger without partially purged slots.
+    pub fn stop_and_drain(
+        &mut self,
+    ) -> impl Future<Output = Result<TruncatorSummary, LedgerTruncatorError>>
+    {
+        if matches!(self.state, ServiceState::Running(_)) {
+            self.stop();
+        }
+
+        let worker_handle =
+            match std::mem::replace(&mut self.state, ServiceState::Drained) {
+                ServiceState::Stopped(worker_handle) => Some(worker_handle),
+                state => {
+                    warn!("LedgerTruncator was not running, nothing to drain");
+                    self.state = state;
+                    None
+                }
+            };
+        async move {
+            match worker_handle {
+                Some(worker_handle) => Ok(worker_handle.await?),
+                None => Ok(TruncatorSummary::default()),
+            }
+        }
+    }
+
+    /// Stops the worker if it's running and returns a summary of its work
+    pub async fn join(
+        mut self,
+    ) -> Result<TruncatorSummary, LedgerTruncatorError> {
         if matches!(self.state, ServiceState::Running(_)) {
             self.stop();
         }
 
         if let ServiceState::Stopped(worker_handle) = self.state {
-            worker_handle.await?;
-            Ok(())
+            Ok(worker_handle.await?)
         } else {
             warn!("LedgerTruncator was not running, nothing to stop");
-            Ok(())
+            Ok(TruncatorSummary::default())
         }
     }
 }
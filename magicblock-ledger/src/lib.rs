@@ -6,5 +6,15 @@ pub mod ledger_truncator;
 mod metrics;
 mod store;
 
-pub use database::meta::PerfSample;
-pub use store::api::{Ledger, SignatureInfosForAddress};
+pub use database::{
+    ledger_column::{CompactionStats, CounterDiscrepancy},
+    meta::PerfSample,
+    write_batch::BatchOp,
+};
+pub use store::{
+    api::{ColumnGroup, Ledger, SignatureInfosForAddress},
+    growth::GrowthEstimate,
+    metrics_reporter::MetricsReporterHandle,
+    snapshot_view::SnapshotView,
+    statistics::RocksStats,
+};
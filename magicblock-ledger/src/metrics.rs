@@ -1,6 +1,10 @@
 use std::{
     cell::RefCell,
-    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
     time::{Duration, Instant},
 };
 
@@ -245,6 +249,136 @@ pub(crate) const PERF_METRIC_OP_NAME_GET: &str = "get";
 pub(crate) const PERF_METRIC_OP_NAME_MULTI_GET: &str = "multi_get";
 pub(crate) const PERF_METRIC_OP_NAME_PUT: &str = "put";
 
+// -----------------
+// LatencyHistogram
+// -----------------
+
+/// Estimated latency percentiles of an operation on a column,
+/// see [crate::database::ledger_column::LedgerColumn::latency_percentiles].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    /// Number of samples the estimates are based on
+    pub count: u64,
+}
+
+/// Fixed-bucket latency histogram, each power of two range of nanoseconds
+/// is split into `2^SUB_BUCKET_BITS` buckets, which bounds the relative
+/// error of the estimates to 12.5%.
+///
+/// Once it holds [LatencyHistogram::MAX_SAMPLES] samples all counts are
+/// halved, so the estimates keep following the recent latencies.
+#[derive(Debug)]
+pub(crate) struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; Self::NUM_BUCKETS],
+            count: 0,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    const SUB_BUCKET_BITS: u32 = 3;
+    const NUM_SUB_BUCKETS: u64 = 1 << Self::SUB_BUCKET_BITS;
+    const NUM_BUCKETS: usize = ((u64::BITS - Self::SUB_BUCKET_BITS + 1)
+        as usize)
+        << Self::SUB_BUCKET_BITS;
+    const MAX_SAMPLES: u64 = 10_000;
+
+    pub fn record(&mut self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.buckets[Self::bucket_index(nanos)] += 1;
+        self.count += 1;
+
+        if self.count >= Self::MAX_SAMPLES {
+            self.buckets.iter_mut().for_each(|count| *count /= 2);
+            self.count = self.buckets.iter().sum();
+        }
+    }
+
+    pub fn percentiles(&self) -> Option<Percentiles> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(Percentiles {
+            p50: self.percentile(50),
+            p95: self.percentile(95),
+            p99: self.percentile(99),
+            count: self.count,
+        })
+    }
+
+    /// Returns the middle of the bucket holding the `percent`-th sample
+    fn percentile(&self, percent: u64) -> Duration {
+        let rank = (self.count * percent).div_ceil(100).max(1);
+        let mut seen = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let (lower, width) = Self::bucket_bounds(index);
+                return Duration::from_nanos(lower.saturating_add(width / 2));
+            }
+        }
+        Duration::MAX
+    }
+
+    fn bucket_index(nanos: u64) -> usize {
+        if nanos < Self::NUM_SUB_BUCKETS {
+            return nanos as usize;
+        }
+        let exp = u64::BITS - 1 - nanos.leading_zeros();
+        let shift = exp - Self::SUB_BUCKET_BITS;
+        let sub_bucket = (nanos >> shift) & (Self::NUM_SUB_BUCKETS - 1);
+        ((shift as usize + 1) << Self::SUB_BUCKET_BITS) + sub_bucket as usize
+    }
+
+    /// Returns the lowest value and the width of the bucket at `index`
+    fn bucket_bounds(index: usize) -> (u64, u64) {
+        let group = index >> Self::SUB_BUCKET_BITS;
+        if group == 0 {
+            return (index as u64, 1);
+        }
+        let sub_bucket = index as u64 & (Self::NUM_SUB_BUCKETS - 1);
+        let shift = group as u32 - 1;
+        ((Self::NUM_SUB_BUCKETS + sub_bucket) << shift, 1 << shift)
+    }
+}
+
+/// Latency histograms of the sampled operations on a column by op name.
+/// Shared by all handles of the same column of a database, so that the
+/// columns of different ledgers, i.e. namespaces, are accounted for apart
+#[derive(Debug, Default)]
+pub struct ColumnLatencies {
+    histograms: Mutex<HashMap<&'static str, LatencyHistogram>>,
+}
+
+impl ColumnLatencies {
+    pub(crate) fn record(&self, op_name: &'static str, duration: Duration) {
+        self.histograms
+            .lock()
+            .expect("latency histograms poisoned")
+            .entry(op_name)
+            .or_default()
+            .record(duration);
+    }
+
+    pub(crate) fn percentiles(&self, op_name: &str) -> Option<Percentiles> {
+        self.histograms
+            .lock()
+            .expect("latency histograms poisoned")
+            .get(op_name)?
+            .percentiles()
+    }
+}
+
 // Thread local instance of RocksDB's PerfContext.
 thread_local! {
     static PER_THREAD_ROCKS_PERF_CONTEXT: RefCell<PerfContext> = RefCell::new(PerfContext::default());
@@ -282,7 +416,9 @@ pub(crate) fn report_rocksdb_read_perf(
     op_name: &'static str,
     total_op_duration: &Duration,
     column_options: &LedgerColumnOptions,
+    latencies: &ColumnLatencies,
 ) {
+    latencies.record(op_name, *total_op_duration);
     PER_THREAD_ROCKS_PERF_CONTEXT.with(|perf_context_cell| {
         set_perf_stats(PerfStatsLevel::Disable);
         let perf_context = perf_context_cell.borrow();
@@ -459,7 +595,9 @@ pub(crate) fn report_rocksdb_write_perf(
     op_name: &'static str,
     total_op_duration: &Duration,
     column_options: &LedgerColumnOptions,
+    latencies: &ColumnLatencies,
 ) {
+    latencies.record(op_name, *total_op_duration);
     PER_THREAD_ROCKS_PERF_CONTEXT.with(|perf_context_cell| {
         set_perf_stats(PerfStatsLevel::Disable);
         let perf_context = perf_context_cell.borrow();
@@ -530,6 +668,47 @@ pub(crate) fn report_rocksdb_write_perf(
     });
 }
 
+// -----------------
+// RocksDbOpSpan
+// -----------------
+
+/// A `tracing` span covering a RocksDB operation on a column, entered until
+/// dropped. Without the `tracing` feature it's zero-sized and compiles out.
+pub(crate) struct RocksDbOpSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+}
+
+impl RocksDbOpSpan {
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub(crate) fn enter(
+        column: &'static str,
+        op: &'static str,
+        key_size: usize,
+    ) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!(
+                "rocksdb",
+                column,
+                op,
+                key_size,
+                value_size = tracing::field::Empty,
+            )
+            .entered(),
+        }
+    }
+
+    /// Records the size of the read or written value(s)
+    #[inline(always)]
+    #[allow(unused_variables)]
+    pub(crate) fn record_value_size(&self, value_size: usize) {
+        #[cfg(feature = "tracing")]
+        self.span.record("value_size", value_size);
+    }
+}
+
 // -----------------
 // LedgerRpcApiMetrics
 // -----------------
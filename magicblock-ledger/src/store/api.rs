@@ -1,16 +1,18 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt, fs,
     path::{Path, PathBuf},
     sync::{
-        atomic::{AtomicI64, Ordering},
-        Arc, RwLock,
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
     },
+    thread,
+    time::{Duration, Instant},
 };
 
 use bincode::{deserialize, serialize};
 use log::*;
-use rocksdb::{Direction as IteratorDirection, FlushOptions};
+use rocksdb::{Direction as IteratorDirection, Env, FlushOptions};
 use solana_measure::measure::Measure;
 use solana_sdk::{
     clock::{Slot, UnixTimestamp},
@@ -29,19 +31,112 @@ use solana_transaction_status::{
 use crate::{
     conversions::transaction,
     database::{
+        async_ledger_column::AsyncLedgerColumn,
         columns as cf,
-        columns::{Column, ColumnName, DIRTY_COUNT},
+        columns::{
+            columns, Column, ColumnIndexDeprecation, ColumnName, DIRTY_COUNT,
+        },
         db::Database,
         iterator::IteratorMode,
-        ledger_column::{try_increase_entry_counter, LedgerColumn},
+        ledger_column::{
+            try_increase_entry_counter, CompactionStats, CountedWrite,
+            CounterDiscrepancy, LedgerColumn,
+        },
         meta::{AccountModData, AddressSignatureMeta, PerfSample},
         options::LedgerOptions,
+        write_batch::{BatchOp, WriteBatch},
     },
     errors::{LedgerError, LedgerResult},
     metrics::LedgerRpcApiMetrics,
-    store::utils::adjust_ulimit_nofile,
+    store::{
+        growth::{GrowthEstimate, GrowthTracker},
+        metrics_reporter::MetricsReporterHandle,
+        snapshot_view::SnapshotView,
+        statistics::RocksStats,
+        utils::adjust_ulimit_nofile,
+    },
 };
 
+/// Evaluates `$body` with `$column` bound to the [LedgerColumn] of
+/// `$ledger` whose `NAME` is `$name`, which has to evaluate to a
+/// [LedgerResult]. Fails with [LedgerError::UnknownColumn] for other names.
+/// Every column of [columns] has to be listed here.
+macro_rules! with_column {
+    ($ledger:expr, $name:expr, |$column:ident| $body:expr) => {
+        match $name {
+            cf::TransactionStatus::NAME => {
+                let $column = &$ledger.transaction_status_cf;
+                $body
+            }
+            cf::AddressSignatures::NAME => {
+                let $column = &$ledger.address_signatures_cf;
+                $body
+            }
+            cf::SlotSignatures::NAME => {
+                let $column = &$ledger.slot_signatures_cf;
+                $body
+            }
+            cf::Blocktime::NAME => {
+                let $column = &$ledger.blocktime_cf;
+                $body
+            }
+            cf::Blockhash::NAME => {
+                let $column = &$ledger.blockhash_cf;
+                $body
+            }
+            cf::Transaction::NAME => {
+                let $column = &$ledger.transaction_cf;
+                $body
+            }
+            cf::TransactionMemos::NAME => {
+                let $column = &$ledger.transaction_memos_cf;
+                $body
+            }
+            cf::PerfSamples::NAME => {
+                let $column = &$ledger.perf_samples_cf;
+                $body
+            }
+            cf::AccountModDatas::NAME => {
+                let $column = &$ledger.account_mod_datas_cf;
+                $body
+            }
+            cf::ProgramTransactionCounts::NAME => {
+                let $column = &$ledger.program_transaction_counts_cf;
+                $body
+            }
+            name => Err(LedgerError::UnknownColumn(name.to_string())),
+        }
+    };
+}
+
+/// Number of entries [Ledger::migrate_column] writes per batch
+const MIGRATION_BATCH_SIZE: usize = 10_000;
+
+/// Groups of columns that are purged together,
+/// see [Ledger::purge_column_group_slot_range]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColumnGroup {
+    /// Block times, blockhashes and perf samples
+    Blocks,
+    /// Transactions, their statuses and memos and the signature indexes
+    Transactions,
+}
+
+impl ColumnGroup {
+    pub const ALL: [ColumnGroup; 2] =
+        [ColumnGroup::Blocks, ColumnGroup::Transactions];
+}
+
+/// Number of entries removed per column when purging transactions
+#[derive(Default)]
+struct DeletedTransactionEntries {
+    slot_signatures: u64,
+    transaction_statuses: u64,
+    transactions: u64,
+    transaction_memos: u64,
+    address_signatures: u64,
+}
+
 #[derive(Default, Debug)]
 pub struct SignatureInfosForAddress {
     pub infos: Vec<ConfirmedTransactionStatusWithSignature>,
@@ -62,12 +157,17 @@ pub struct Ledger {
     transaction_memos_cf: LedgerColumn<cf::TransactionMemos>,
     perf_samples_cf: LedgerColumn<cf::PerfSamples>,
     account_mod_datas_cf: LedgerColumn<cf::AccountModDatas>,
+    program_transaction_counts_cf: LedgerColumn<cf::ProgramTransactionCounts>,
 
     transaction_successful_status_count: AtomicI64,
     transaction_failed_status_count: AtomicI64,
 
     lowest_cleanup_slot: RwLock<Slot>,
+    // Mirrors `lowest_cleanup_slot` for the compaction filter,
+    // see [LedgerOptions::cleanup_compaction_filter]
+    cleanup_slot_watermark: Arc<AtomicU64>,
     rpc_api_metrics: LedgerRpcApiMetrics,
+    growth_tracker: Mutex<GrowthTracker>,
 }
 
 impl fmt::Display for Ledger {
@@ -96,21 +196,167 @@ impl Ledger {
         self.db.storage_size()
     }
 
+    /// Same as [Ledger::storage_size], additionally records the size as a
+    /// sample for [Ledger::growth_estimate].
+    /// The [crate::ledger_truncator::LedgerTruncator] samples on each cycle.
+    pub fn sample_storage_size(&self) -> LedgerResult<u64> {
+        let size = self.storage_size()?;
+        self.growth_tracker
+            .lock()
+            .expect("growth tracker poisoned")
+            .record(Instant::now(), size);
+        Ok(size)
+    }
+
+    /// Sets the size the [GrowthEstimate::seconds_to_budget] is relative to
+    pub fn set_size_budget(&self, size_budget: Option<u64>) {
+        self.growth_tracker
+            .lock()
+            .expect("growth tracker poisoned")
+            .set_size_budget(size_budget);
+    }
+
+    /// Estimates the storage growth from the recent
+    /// [Ledger::sample_storage_size] samples, `None` until there are enough
+    /// of them
+    pub fn growth_estimate(&self) -> Option<GrowthEstimate> {
+        self.growth_tracker
+            .lock()
+            .expect("growth tracker poisoned")
+            .estimate()
+    }
+
+    /// Returns the counters of the RocksDB statistics, `None` unless the
+    /// ledger was opened with [LedgerOptions::enable_statistics]
+    pub fn statistics_snapshot(&self) -> Option<RocksStats> {
+        match self
+            .db
+            .backend
+            .get_property(rocksdb::properties::OPTIONS_STATISTICS)
+        {
+            Ok(report) => report.map(|report| RocksStats::parse(&report)),
+            Err(err) => {
+                warn!("Failed to read RocksDB statistics: {}", err);
+                None
+            }
+        }
+    }
+
     /// Opens a Ledger in directory, provides "infinite" window of shreds
     pub fn open(ledger_path: &Path) -> Result<Self, LedgerError> {
-        Self::do_open(ledger_path, LedgerOptions::default())
+        Self::do_open(ledger_path, LedgerOptions::default(), None)
     }
 
     pub fn open_with_options(
         ledger_path: &Path,
         options: LedgerOptions,
     ) -> Result<Self, LedgerError> {
-        Self::do_open(ledger_path, options)
+        Self::do_open(ledger_path, options, None)
+    }
+
+    /// Opens the ledger of `namespace` in directory. Its column families
+    /// are prefixed with the namespace, so ledgers of different namespaces
+    /// can share one database while their data stays isolated.
+    ///
+    /// Within a process the ledgers of all namespaces of a directory share
+    /// the same database, which stays locked until all of them are closed.
+    /// It's opened with the database and column options of the ledger
+    /// opening it first, the column families of a namespace that doesn't
+    /// exist yet are created with the options of the ledger creating them.
+    /// Ledgers opened later with different database level options, i.e.
+    /// the WAL, statistics, env or rate limiter ones, fail with
+    /// [LedgerError::SharedDatabaseOptionsMismatch].
+    /// Only the columns opened or created by a ledger get its
+    /// [LedgerOptions::cleanup_compaction_filter].
+    pub fn open_with_namespace(
+        ledger_path: &Path,
+        namespace: &str,
+    ) -> Result<Self, LedgerError> {
+        let options = LedgerOptions {
+            namespace: Some(namespace.to_string()),
+            ..LedgerOptions::default()
+        };
+        Self::do_open(ledger_path, options, None)
+    }
+
+    /// Opens the ledger with RocksDB doing its file I/O through `env`
+    /// instead of the default filesystem one, i.e. an in-memory env for
+    /// tests or one encrypting the data at rest.
+    /// The env's high priority thread pool is resized like the default
+    /// one's. Helpers that inspect the ledger directory directly, i.e.
+    /// [Ledger::storage_size], only see what `env` writes to the filesystem.
+    pub fn open_with_env(
+        ledger_path: &Path,
+        env: Env,
+    ) -> Result<Self, LedgerError> {
+        let options = LedgerOptions {
+            env: Some(env),
+            ..LedgerOptions::default()
+        };
+        Self::do_open(ledger_path, options, None)
+    }
+
+    /// Same as [Ledger::open], but retries up to `retries` times while the
+    /// database is locked by another process, i.e. one that is still
+    /// shutting down. The delay starts at `backoff` and doubles after each
+    /// attempt. Any other error is returned right away.
+    pub fn open_with_retry(
+        ledger_path: &Path,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<Self, LedgerError> {
+        let mut delay = backoff;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match Self::open(ledger_path) {
+                Err(err) if err.is_lock_contention() => {
+                    if attempt > retries {
+                        let LedgerError::RocksDb(source) = err else {
+                            unreachable!("lock contention is a RocksDB error");
+                        };
+                        return Err(LedgerError::LedgerLocked {
+                            attempts: attempt,
+                            source,
+                        });
+                    }
+                    warn!(
+                        "Ledger at {:?} is locked, retrying in {:?}: {}",
+                        ledger_path, delay, err
+                    );
+                    thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Opens only the given `columns` (see the `NAME` of each column) of an
+    /// existing ledger in read only mode, which saves the memory the other
+    /// columns would take, i.e. for sidecars that only need a few of them.
+    ///
+    /// Methods touching a column that wasn't opened fail with
+    /// [LedgerError::ColumnNotOpened], all writes fail since the ledger
+    /// is read only.
+    pub fn open_columns(
+        ledger_path: &Path,
+        column_names: &[&'static str],
+    ) -> LedgerResult<Self> {
+        let known_columns = columns();
+        if let Some(unknown) = column_names
+            .iter()
+            .find(|name| !known_columns.contains(*name))
+        {
+            return Err(LedgerError::UnknownColumn(unknown.to_string()));
+        }
+        Self::do_open(ledger_path, LedgerOptions::default(), Some(column_names))
     }
 
     fn do_open(
         ledger_path: &Path,
         options: LedgerOptions,
+        columns: Option<&[&'static str]>,
     ) -> Result<Self, LedgerError> {
         fs::create_dir_all(ledger_path)?;
         let ledger_path = ledger_path.join(
@@ -120,11 +366,17 @@ impl Ledger {
                 .blockstore_directory(),
         );
         adjust_ulimit_nofile(options.enforce_ulimit_nofile)?;
+        let cleanup_slot_watermark = options.cleanup_slot_watermark.clone();
 
         // Open the database
         let mut measure = Measure::start("ledger open");
         info!("Opening ledger at {:?}", ledger_path);
-        let db = Database::open(&ledger_path, options)?;
+        let db = match columns {
+            Some(columns) => {
+                Database::open_columns(&ledger_path, options, columns)?
+            }
+            None => Database::open(&ledger_path, options)?,
+        };
 
         let transaction_status_cf = db.column();
         let address_signatures_cf = db.column();
@@ -136,6 +388,7 @@ impl Ledger {
         let perf_samples_cf = db.column();
 
         let account_mod_datas_cf = db.column();
+        let program_transaction_counts_cf = db.column();
 
         let db = Arc::new(db);
 
@@ -157,17 +410,33 @@ impl Ledger {
             transaction_memos_cf,
             perf_samples_cf,
             account_mod_datas_cf,
+            program_transaction_counts_cf,
 
             transaction_successful_status_count: AtomicI64::new(DIRTY_COUNT),
             transaction_failed_status_count: AtomicI64::new(DIRTY_COUNT),
 
             lowest_cleanup_slot: RwLock::<Slot>::default(),
+            cleanup_slot_watermark,
             rpc_api_metrics: LedgerRpcApiMetrics::default(),
+            growth_tracker: Mutex::default(),
         };
+        ledger.validate_counters()?;
 
         Ok(ledger)
     }
 
+    /// Resets the negative entry counters of all columns to [DIRTY_COUNT],
+    /// see [LedgerColumn::validate_counter]
+    fn validate_counters(&self) -> LedgerResult<()> {
+        for name in columns() {
+            with_column!(self, name, |column| {
+                column.validate_counter();
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
     /// Collects and reports [`BlockstoreRocksDbColumnFamilyMetrics`] for
     /// all the column families.
     ///
@@ -182,6 +451,20 @@ impl Ledger {
         self.transaction_memos_cf.submit_rocksdb_cf_metrics();
         self.perf_samples_cf.submit_rocksdb_cf_metrics();
         self.account_mod_datas_cf.submit_rocksdb_cf_metrics();
+        self.program_transaction_counts_cf
+            .submit_rocksdb_cf_metrics();
+    }
+
+    /// Spawns a task calling [Ledger::submit_rocksdb_cf_metrics_for_all_cfs]
+    /// every `interval` until the returned handle is stopped.
+    /// The task holds its own handles to the database, so
+    /// [Ledger::close] fails while it's running.
+    /// Must be called within a tokio runtime.
+    pub fn start_metrics_reporter(
+        &self,
+        interval: Duration,
+    ) -> MetricsReporterHandle {
+        MetricsReporterHandle::spawn(&self.db, interval)
     }
 
     // -----------------
@@ -203,7 +486,10 @@ impl Ledger {
             .read()
             .expect(Self::LOWEST_CLEANUP_SLOT_POISONED);
         if *lowest_cleanup_slot > 0 && *lowest_cleanup_slot >= slot {
-            return Err(LedgerError::SlotCleanedUp);
+            return Err(LedgerError::SlotCleanedUp {
+                slot,
+                lowest_cleanup: *lowest_cleanup_slot,
+            });
         }
         // Make caller hold this lock properly; otherwise LedgerCleanupService can purge/compact
         // needed slots here at any given moment
@@ -240,6 +526,24 @@ impl Ledger {
             .expect(Self::LOWEST_CLEANUP_SLOT_POISONED)
     }
 
+    /// Moves the lowest cleanup slot up to `slot` without deleting anything,
+    /// slots at or below it are rejected by writes and hidden from reads that
+    /// respect the cleanup slot from then on.
+    /// With [LedgerOptions::cleanup_compaction_filter] set, their entries are
+    /// dropped by background or manual compaction instead of being purged,
+    /// which avoids the tombstones of explicit deletes. The entry counters
+    /// don't reflect the entries dropped that way, and the column caches may
+    /// keep serving them to reads that don't respect the cleanup slot.
+    pub fn advance_lowest_cleanup_slot(&self, slot: Slot) {
+        let mut lowest_cleanup_slot = self
+            .lowest_cleanup_slot
+            .write()
+            .expect(Self::LOWEST_CLEANUP_SLOT_POISONED);
+        *lowest_cleanup_slot = std::cmp::max(*lowest_cleanup_slot, slot);
+        self.cleanup_slot_watermark
+            .store(*lowest_cleanup_slot, Ordering::Relaxed);
+    }
+
     // -----------------
     // Block time
     // -----------------
@@ -279,6 +583,21 @@ impl Ledger {
         Ok((slot, hash))
     }
 
+    /// Returns the lowest and highest slots that still have a block written,
+    /// or `None` if there are none.
+    /// Note that the lowest slot might be higher than the lowest cleanup slot.
+    pub fn slot_bounds(&self) -> LedgerResult<Option<(Slot, Slot)>> {
+        let Some((lowest, _)) = self.blockhash_cf.get_next_from(Slot::MIN)?
+        else {
+            return Ok(None);
+        };
+        let highest = self
+            .blockhash_cf
+            .get_prev_from(Slot::MAX)?
+            .map_or(lowest, |(slot, _)| slot);
+        Ok(Some((lowest, highest)))
+    }
+
     // -----------------
     // Block
     // -----------------
@@ -286,12 +605,42 @@ impl Ledger {
     // NOTE: we kept the term block time even tough we don't produce blocks.
     // As far as we are concerned these are just the time when we advanced to
     // a specific slot.
+    /// Fails with [LedgerError::SlotCleanedUp] if `slot` was already
+    /// truncated, so that late writes don't leave data below the cleanup slot.
+    ///
+    /// Writing a block again with the same hash, i.e. when retrying, is a
+    /// no-op, while a different hash fails with
+    /// [LedgerError::BlockHashMismatch] instead of overwriting the block.
     pub fn write_block(
         &self,
         slot: Slot,
         timestamp: UnixTimestamp,
         blockhash: Hash,
     ) -> LedgerResult<()> {
+        let _lock = self.check_lowest_cleanup_slot(slot)?;
+        // Serializes concurrent writes of the same block
+        let _insert_lock = self
+            .blockhash_cf
+            .insert_lock
+            .lock()
+            .expect("insert lock poisoned");
+        // The hash is written last, so a block that has one is complete
+        match self.blockhash_cf.get(slot)? {
+            Some(existing) if existing == blockhash => return Ok(()),
+            Some(existing) => {
+                return Err(LedgerError::BlockHashMismatch {
+                    slot,
+                    existing,
+                    provided: blockhash,
+                })
+            }
+            None => {}
+        }
+
+        let _writes = (
+            self.blocktime_cf.begin_counted_write(),
+            self.blockhash_cf.begin_counted_write(),
+        );
         self.blocktime_cf.put(slot, &timestamp)?;
         self.blocktime_cf.try_increase_entry_counter(1);
 
@@ -321,7 +670,7 @@ impl Ledger {
                 .iter_current_index_filtered(IteratorMode::From(
                     (slot, u32::MAX),
                     IteratorDirection::Reverse,
-                ));
+                ))?;
 
             let mut signatures = vec![];
             for ((tx_slot, _tx_idx), tx_signature) in index_iterator {
@@ -515,7 +864,7 @@ impl Ledger {
                     .iter_current_index_filtered(IteratorMode::From(
                         (upper_slot, u32::MAX),
                         IteratorDirection::Reverse,
-                    ));
+                    ))?;
                 for ((tx_slot, _tx_idx), tx_signature) in index_iterator {
                     // Bail out if we reached the max number of signatures to collect
                     if matching.len() >= limit {
@@ -563,7 +912,7 @@ impl Ledger {
                         // The reverse range is not inclusive of the start_slot itself it seems
                         (pubkey, newest_slot, u32::MAX, Signature::default()),
                         IteratorDirection::Reverse,
-                    ));
+                    ))?;
 
                 for ((address, tx_slot, _tx_idx, signature), _) in
                     index_iterator
@@ -619,7 +968,7 @@ impl Ledger {
                     .iter_current_index_filtered(IteratorMode::From(
                         (lower_slot, u32::MAX),
                         IteratorDirection::Reverse,
-                    ));
+                    ))?;
                 for ((tx_slot, tx_idx), tx_signature) in index_iterator {
                     // Bail out if we reached the max number of signatures to collect
                     if matching.len() >= limit {
@@ -736,7 +1085,7 @@ impl Ledger {
                     .iter_current_index_filtered(IteratorMode::From(
                         (signature, highest_confirmed_slot),
                         IteratorDirection::Forward,
-                    ));
+                    ))?;
                 match iterator.next() {
                     Some(((tx_signature, slot), _data)) => {
                         if slot <= highest_confirmed_slot
@@ -779,6 +1128,8 @@ impl Ledger {
     /// * `transaction` - Transaction to be written, we take a SanititizedTransaction here
     ///                   since that is what we provide Geyser as well
     /// * `status` - status of the transaction
+    ///
+    /// Fails with [LedgerError::SlotCleanedUp] if `slot` was already truncated
     pub fn write_transaction(
         &self,
         signature: Signature,
@@ -787,6 +1138,7 @@ impl Ledger {
         status: TransactionStatusMeta,
         transaction_slot_index: usize,
     ) -> LedgerResult<()> {
+        let _lock = self.check_lowest_cleanup_slot(slot)?;
         let tx_account_locks = transaction.get_account_locks_unchecked();
 
         // 1. Write Transaction Status
@@ -803,6 +1155,7 @@ impl Ledger {
         let versioned = transaction.to_versioned_transaction();
         let transaction: generated::Transaction = versioned.into();
 
+        let _write = self.transaction_cf.begin_counted_write();
         self.transaction_cf
             .put_protobuf((signature, slot), &transaction)?;
         self.transaction_cf.try_increase_entry_counter(1);
@@ -821,6 +1174,37 @@ impl Ledger {
         Ok(result)
     }
 
+    /// Reads the transaction at `index` along with its status, treating it
+    /// as purged, i.e. `None`, if its slot is at or below the lowest cleanup
+    /// slot or if only one of them exists.
+    ///
+    /// Unlike [Ledger::read_transaction], this never returns entries of a
+    /// slot that is partially deleted, i.e. while a compaction filter drops
+    /// the slots below the cleanup slot one column after another, see
+    /// [Ledger::advance_lowest_cleanup_slot], or while a column group purge
+    /// is in progress. The lowest cleanup slot lock is held while reading
+    /// both columns, so a concurrent [Ledger::delete_slot_range] either
+    /// happens entirely before or after the read.
+    pub fn read_transaction_fenced(
+        &self,
+        index: (Signature, Slot),
+    ) -> LedgerResult<Option<(generated::Transaction, TransactionStatusMeta)>>
+    {
+        let _lock = match self.check_lowest_cleanup_slot(index.1) {
+            Ok(lock) => lock,
+            Err(LedgerError::SlotCleanedUp { .. }) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let Some(transaction) = self.transaction_cf.get_protobuf(index)? else {
+            return Ok(None);
+        };
+        let status = self
+            .transaction_status_cf
+            .get_protobuf(index)?
+            .and_then(|meta| meta.try_into().ok());
+        Ok(status.map(|status| (transaction, status)))
+    }
+
     pub fn count_transactions(&self) -> LedgerResult<i64> {
         self.transaction_cf.count_column_using_cache()
     }
@@ -843,6 +1227,8 @@ impl Ledger {
         slot: Slot,
         memos: String,
     ) -> LedgerResult<()> {
+        let _lock = self.check_lowest_cleanup_slot(slot)?;
+        let _write = self.transaction_memos_cf.begin_counted_write();
         let res = self.transaction_memos_cf.put((*signature, slot), &memos);
         self.transaction_memos_cf.try_increase_entry_counter(1);
         res
@@ -876,7 +1262,7 @@ impl Ledger {
                 .iter_current_index_filtered(IteratorMode::From(
                     (signature, lowest_available_slot),
                     IteratorDirection::Forward,
-                ));
+                ))?;
 
             let mut result = None;
             for ((stat_signature, slot), _) in iterator {
@@ -923,6 +1309,11 @@ impl Ledger {
         let transaction_slot_index = u32::try_from(transaction_slot_index)
             .map_err(|_| LedgerError::TransactionIndexOverflow)?;
 
+        let _writes = (
+            self.address_signatures_cf.begin_counted_write(),
+            self.slot_signatures_cf.begin_counted_write(),
+            self.transaction_status_cf.begin_counted_write(),
+        );
         for address in writable_keys {
             self.address_signatures_cf.put(
                 (*address, slot, transaction_slot_index, signature),
@@ -1081,6 +1472,7 @@ impl Ledger {
         // Always write as the current version.
         let bytes = serialize(perf_sample)
             .expect("`PerfSample` can be serialized with `bincode`");
+        let _write = self.perf_samples_cf.begin_counted_write();
         self.perf_samples_cf.put_bytes(index, &bytes)?;
         self.perf_samples_cf.try_increase_entry_counter(1);
 
@@ -1099,6 +1491,7 @@ impl Ledger {
         id: u64,
         data: &AccountModData,
     ) -> LedgerResult<()> {
+        let _write = self.account_mod_datas_cf.begin_counted_write();
         self.account_mod_datas_cf.put(id, data)?;
         self.account_mod_datas_cf.try_increase_entry_counter(1);
         Ok(())
@@ -1115,6 +1508,29 @@ impl Ledger {
         self.account_mod_datas_cf.count_column_using_cache()
     }
 
+    // -----------------
+    // ProgramTransactionCounts
+    // -----------------
+    /// Adds `delta` to the transaction count of `program_id`,
+    /// safe to call concurrently for the same program
+    pub fn increment_program_transaction_count(
+        &self,
+        program_id: Pubkey,
+        delta: i64,
+    ) -> LedgerResult<()> {
+        self.program_transaction_counts_cf.merge(program_id, delta)
+    }
+
+    pub fn read_program_transaction_count(
+        &self,
+        program_id: Pubkey,
+    ) -> LedgerResult<i64> {
+        Ok(self
+            .program_transaction_counts_cf
+            .get(program_id)?
+            .unwrap_or_default())
+    }
+
     pub fn read_slot_signature(
         &self,
         index: (Slot, u32),
@@ -1122,6 +1538,43 @@ impl Ledger {
         self.slot_signatures_cf.get(index)
     }
 
+    /// Purges all per-slot data in the \[`from_slot`, `to_slot`\] range from
+    /// all the columns and moves the lowest cleanup slot accordingly.
+    /// Returns the number of purged slots.
+    ///
+    /// Large ranges are split into chunks, each purged atomically. If a chunk
+    /// fails the following ones aren't purged, so that the lowest cleanup slot
+    /// never moves past slots that still have data.
+    pub fn purge_slot_range(
+        &self,
+        from_slot: Slot,
+        to_slot: Slot,
+    ) -> LedgerResult<u64> {
+        // In order not to torture RocksDB's WriteBatch we split large tasks into chunks
+        const SINGLE_PURGE_LIMIT: u64 = 300;
+
+        if to_slot < from_slot {
+            warn!("Invalid purge range [{from_slot}; {to_slot}]");
+            return Ok(0);
+        }
+
+        let mut num_purged = 0;
+        let mut cur_from_slot = from_slot;
+        loop {
+            let cur_to_slot = std::cmp::min(
+                cur_from_slot.saturating_add(SINGLE_PURGE_LIMIT - 1),
+                to_slot,
+            );
+            self.delete_slot_range(cur_from_slot, cur_to_slot)?;
+            num_purged += cur_to_slot - cur_from_slot + 1;
+
+            if cur_to_slot == to_slot {
+                return Ok(num_purged);
+            }
+            cur_from_slot = cur_to_slot + 1;
+        }
+    }
+
     /// Permanently removes ledger data for slots in the inclusive range `[from_slot, to_slot]`.
     /// # Note:
     /// - This is a destructive operation that cannot be undone
@@ -1132,36 +1585,129 @@ impl Ledger {
         from_slot: Slot,
         to_slot: Slot,
     ) -> LedgerResult<()> {
+        // All columns are purged within one WriteBatch so that a crash can't
+        // leave entries of a slot in some columns but not in others
         let mut batch = self.db.batch();
 
         let mut lowest_cleanup_slot = self
             .lowest_cleanup_slot
             .write()
             .expect(Self::LOWEST_CLEANUP_SLOT_POISONED);
+
+        self.delete_blocks_in_batch(&mut batch, from_slot, to_slot);
+        let deleted_transactions =
+            self.delete_transactions_in_batch(&mut batch, from_slot, to_slot)?;
+
+        let _writes = (
+            self.begin_counted_block_writes(),
+            self.begin_counted_transaction_writes(),
+        );
+        self.db.write(batch)?;
+        // Only move the cleanup slot once the slots are actually gone
         *lowest_cleanup_slot = std::cmp::max(*lowest_cleanup_slot, to_slot);
+        self.cleanup_slot_watermark
+            .store(*lowest_cleanup_slot, Ordering::Relaxed);
+        drop(lowest_cleanup_slot);
 
         let num_deleted_slots = to_slot + 1 - from_slot;
-        self.blocktime_cf.delete_range_in_batch(
-            &mut batch,
-            from_slot,
-            to_slot + 1,
-        );
-        self.blockhash_cf.delete_range_in_batch(
-            &mut batch,
-            from_slot,
-            to_slot + 1,
-        );
+        self.blocktime_cf
+            .try_decrease_entry_counter(num_deleted_slots);
+        self.blockhash_cf
+            .try_decrease_entry_counter(num_deleted_slots);
+        self.perf_samples_cf
+            .try_decrease_entry_counter(num_deleted_slots);
+        self.decrease_transaction_entry_counters(&deleted_transactions);
+
+        Ok(())
+    }
+
+    /// Purges the \[`from_slot`, `to_slot`\] range from the columns of
+    /// `group` only, without moving the lowest cleanup slot.
+    ///
+    /// This allows some columns to be kept longer than others, i.e. block
+    /// headers can outlive the transactions of their slot. Slots up to the
+    /// lowest cleanup slot are still purged from all columns via
+    /// [Ledger::purge_slot_range].
+    pub fn purge_column_group_slot_range(
+        &self,
+        group: ColumnGroup,
+        from_slot: Slot,
+        to_slot: Slot,
+    ) -> LedgerResult<()> {
+        if to_slot < from_slot {
+            warn!("Invalid purge range [{from_slot}; {to_slot}]");
+            return Ok(());
+        }
+
+        let mut batch = self.db.batch();
+        match group {
+            ColumnGroup::Blocks => {
+                self.delete_blocks_in_batch(&mut batch, from_slot, to_slot);
+                self.db.write(batch)?;
+
+                // The range might have been partially purged already,
+                // so we can't tell how many entries were removed
+                self.blocktime_cf.mark_counter_dirty();
+                self.blockhash_cf.mark_counter_dirty();
+                self.perf_samples_cf.mark_counter_dirty();
+            }
+            ColumnGroup::Transactions => {
+                let deleted_transactions = self.delete_transactions_in_batch(
+                    &mut batch, from_slot, to_slot,
+                )?;
+                let _writes = self.begin_counted_transaction_writes();
+                self.db.write(batch)?;
+                self.decrease_transaction_entry_counters(&deleted_transactions);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// See [LedgerColumn::begin_counted_write]
+    fn begin_counted_block_writes(&self) -> [CountedWrite<'_>; 3] {
+        [
+            self.blocktime_cf.begin_counted_write(),
+            self.blockhash_cf.begin_counted_write(),
+            self.perf_samples_cf.begin_counted_write(),
+        ]
+    }
+
+    /// See [LedgerColumn::begin_counted_write]
+    fn begin_counted_transaction_writes(&self) -> [CountedWrite<'_>; 5] {
+        [
+            self.slot_signatures_cf.begin_counted_write(),
+            self.transaction_status_cf.begin_counted_write(),
+            self.transaction_cf.begin_counted_write(),
+            self.transaction_memos_cf.begin_counted_write(),
+            self.address_signatures_cf.begin_counted_write(),
+        ]
+    }
+
+    fn delete_blocks_in_batch(
+        &self,
+        batch: &mut WriteBatch,
+        from_slot: Slot,
+        to_slot: Slot,
+    ) {
+        self.blocktime_cf
+            .delete_range_in_batch(batch, from_slot, to_slot + 1);
+        self.blockhash_cf
+            .delete_range_in_batch(batch, from_slot, to_slot + 1);
         self.perf_samples_cf.delete_range_in_batch(
-            &mut batch,
+            batch,
             from_slot,
             to_slot + 1,
         );
+    }
 
-        let mut slot_signatures_deleted = 0;
-        let mut transaction_status_deleted = 0;
-        let mut transactions_deleted = 0;
-        let mut transaction_memos_deleted = 0;
-        let mut address_signatures_deleted = 0;
+    fn delete_transactions_in_batch(
+        &self,
+        batch: &mut WriteBatch,
+        from_slot: Slot,
+        to_slot: Slot,
+    ) -> LedgerResult<DeletedTransactionEntries> {
+        let mut deleted = DeletedTransactionEntries::default();
         self.slot_signatures_cf
             .iter(IteratorMode::From(
                 (from_slot, u32::MIN),
@@ -1170,21 +1716,21 @@ impl Ledger {
             .take_while(|((slot, _), _)| slot <= &to_slot)
             .try_for_each(|((slot, transaction_index), raw_signature)| {
                 self.slot_signatures_cf
-                    .delete_in_batch(&mut batch, (slot, transaction_index));
-                slot_signatures_deleted += 1;
+                    .delete_in_batch(batch, (slot, transaction_index));
+                deleted.slot_signatures += 1;
 
                 let signature = Signature::try_from(raw_signature.as_ref())?;
                 self.transaction_status_cf
-                    .delete_in_batch(&mut batch, (signature, slot));
-                transaction_status_deleted += 1;
+                    .delete_in_batch(batch, (signature, slot));
+                deleted.transaction_statuses += 1;
 
                 self.transaction_cf
-                    .delete_in_batch(&mut batch, (signature, slot));
-                transactions_deleted += 1;
+                    .delete_in_batch(batch, (signature, slot));
+                deleted.transactions += 1;
 
                 self.transaction_memos_cf
-                    .delete_in_batch(&mut batch, (signature, slot));
-                transaction_memos_deleted += 1;
+                    .delete_in_batch(batch, (signature, slot));
+                deleted.transaction_memos += 1;
 
                 let transaction = self
                     .transaction_cf
@@ -1195,10 +1741,10 @@ impl Ledger {
                 transaction.message.static_account_keys().iter().for_each(
                     |address| {
                         self.address_signatures_cf.delete_in_batch(
-                            &mut batch,
+                            batch,
                             (*address, slot, transaction_index, signature),
                         );
-                        address_signatures_deleted += 1;
+                        deleted.address_signatures += 1;
                     },
                 );
 
@@ -1206,24 +1752,23 @@ impl Ledger {
                 Ok::<_, LedgerError>(())
             })?;
 
-        self.db.write(batch)?;
+        Ok(deleted)
+    }
 
-        self.blocktime_cf
-            .try_decrease_entry_counter(num_deleted_slots);
-        self.blockhash_cf
-            .try_decrease_entry_counter(num_deleted_slots);
-        self.perf_samples_cf
-            .try_decrease_entry_counter(num_deleted_slots);
+    fn decrease_transaction_entry_counters(
+        &self,
+        deleted: &DeletedTransactionEntries,
+    ) {
         self.slot_signatures_cf
-            .try_decrease_entry_counter(slot_signatures_deleted);
+            .try_decrease_entry_counter(deleted.slot_signatures);
         self.transaction_status_cf
-            .try_decrease_entry_counter(transaction_status_deleted);
+            .try_decrease_entry_counter(deleted.transaction_statuses);
         self.transaction_cf
-            .try_decrease_entry_counter(transactions_deleted);
+            .try_decrease_entry_counter(deleted.transactions);
         self.transaction_memos_cf
-            .try_decrease_entry_counter(transaction_memos_deleted);
+            .try_decrease_entry_counter(deleted.transaction_memos);
         self.address_signatures_cf
-            .try_decrease_entry_counter(address_signatures_deleted);
+            .try_decrease_entry_counter(deleted.address_signatures);
 
         // To not spend time querying DB for value we set drop the counter
         // This shouldn't happen very often due to rarity of actual truncations.
@@ -1231,30 +1776,219 @@ impl Ledger {
             .store(DIRTY_COUNT, Ordering::Release);
         self.transaction_failed_status_count
             .store(DIRTY_COUNT, Ordering::Release);
-
-        Ok(())
     }
 
     pub fn compact_slot_range_cf<C: Column + ColumnName>(
         &self,
         from: Option<C::Index>,
         to: Option<C::Index>,
-    ) {
-        self.db.column::<C>().compact_range(from, to);
+    ) -> LedgerResult<()> {
+        self.db.column::<C>().compact_range(from, to)
+    }
+
+    /// Compacts the \[`from_slot`, `to_slot`\] range of the slot keyed
+    /// columns, eagerly dropping the tombstones left by purging that range
+    /// instead of waiting for background compaction to catch up.
+    /// Columns keyed by signature or address can't be compacted by slot.
+    pub fn compact_truncated_range(
+        &self,
+        from_slot: Slot,
+        to_slot: Slot,
+    ) -> LedgerResult<()> {
+        let end_slot = to_slot.saturating_add(1);
+        self.blocktime_cf
+            .compact_range(Some(from_slot), Some(end_slot))?;
+        self.blockhash_cf
+            .compact_range(Some(from_slot), Some(end_slot))?;
+        self.perf_samples_cf
+            .compact_range(Some(from_slot), Some(end_slot))?;
+        self.slot_signatures_cf.compact_range(
+            Some((from_slot, u32::MIN)),
+            Some((end_slot, u32::MAX)),
+        )
+    }
+
+    /// Size of the SST files of each column along with its `NAME`,
+    /// data still in memtables isn't accounted for
+    pub fn storage_size_by_column(
+        &self,
+    ) -> LedgerResult<Vec<(&'static str, u64)>> {
+        columns()
+            .into_iter()
+            .map(|name| Ok((name, self.column_storage_size(name)?)))
+            .collect()
+    }
+
+    /// Compacts the columns one after another, starting with the largest
+    /// one per [Ledger::storage_size_by_column], since it likely reclaims
+    /// the most space, i.e. when the disk is about to run full.
+    /// Stops once the compactions reclaimed at least `reclaim_target`
+    /// bytes, if given.
+    /// Returns the stats of the compacted columns in compaction order.
+    pub fn compact_columns_by_size(
+        &self,
+        reclaim_target: Option<u64>,
+    ) -> LedgerResult<Vec<(&'static str, CompactionStats)>> {
+        let mut sizes = self.storage_size_by_column()?;
+        sizes.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        let mut compacted = Vec::with_capacity(sizes.len());
+        let mut reclaimed = 0;
+        for (name, _) in sizes {
+            if reclaim_target.is_some_and(|target| reclaimed >= target) {
+                break;
+            }
+            let stats = self.compact_column_blocking(name)?;
+            reclaimed += stats.bytes_reclaimed();
+            compacted.push((name, stats));
+        }
+        Ok(compacted)
+    }
+
+    /// Compares the cached entry counter of each column against a fresh
+    /// count of its entries and returns the columns where they differ,
+    /// i.e. for a self-test that catches counter drift.
+    /// Scans every column whose counter isn't dirty, which can take a while
+    /// on a large ledger. The counters are left as they are, see
+    /// [Ledger::repair_counters] to fix them as well.
+    pub fn verify_counters(&self) -> LedgerResult<Vec<CounterDiscrepancy>> {
+        self.check_counters(false)
+    }
+
+    /// Same as [Ledger::verify_counters], but also replaces the counters
+    /// that were found off by the actual count
+    pub fn repair_counters(&self) -> LedgerResult<Vec<CounterDiscrepancy>> {
+        self.check_counters(true)
+    }
+
+    fn check_counters(
+        &self,
+        repair: bool,
+    ) -> LedgerResult<Vec<CounterDiscrepancy>> {
+        let mut discrepancies = Vec::new();
+        for name in columns() {
+            discrepancies
+                .extend(self.column_counter_discrepancy(name, repair)?);
+        }
+        Ok(discrepancies)
+    }
+
+    fn column_counter_discrepancy(
+        &self,
+        name: &str,
+        repair: bool,
+    ) -> LedgerResult<Option<CounterDiscrepancy>> {
+        with_column!(self, name, |column| column.verify_counter(repair))
+    }
+
+    fn column_storage_size(&self, name: &str) -> LedgerResult<u64> {
+        with_column!(self, name, |column| column.storage_size())
+    }
+
+    fn compact_column_blocking(
+        &self,
+        name: &str,
+    ) -> LedgerResult<CompactionStats> {
+        with_column!(self, name, |column| column
+            .compact_range_blocking(None, None))
+    }
+
+    /// Lets `f` fill a single [WriteBatch] that is then committed atomically
+    /// with one WAL append, which amortizes the per-write overhead of bursts
+    /// of many small writes. RocksDB groups concurrent commits into the same
+    /// WAL write as well, so several ingesting threads don't serialize on it.
+    /// Puts may overwrite entries and deletes may target missing ones, so
+    /// the entry counters of the columns the batch modifies are left dirty
+    /// and recounted when next counted.
+    /// Fails with [LedgerError::SlotCleanedUp] without writing anything if
+    /// a put targets a slot at or below the lowest cleanup slot.
+    pub fn ingest_batch(
+        &self,
+        f: impl FnOnce(&mut WriteBatch),
+    ) -> LedgerResult<()> {
+        let mut batch = self.db.batch();
+        f(&mut batch);
+        let modified_columns: HashSet<_> =
+            batch.iter_ops().map(BatchOp::cf).collect();
+        {
+            // Keep the truncator from purging while the batch lands
+            let _lock = match batch.min_put_slot {
+                Some(slot) => self.check_lowest_cleanup_slot(slot)?,
+                None => self.ensure_lowest_cleanup_slot().0,
+            };
+            self.db.write(batch)?;
+        }
+
+        for name in modified_columns {
+            self.mark_counter_dirty(name)?;
+        }
+        Ok(())
+    }
+
+    /// Copies the entries of the `Old` column that use its current index
+    /// into the `New` column, i.e. to move data out of a deprecated column.
+    /// `map` converts each entry to the key and raw value written to `New`,
+    /// entries it returns `None` for are skipped. The `Old` column is left
+    /// untouched.
+    /// Entries are written in batches, so a failure may leave a part of them
+    /// migrated. Migrated entries may overwrite existing ones, so the entry
+    /// counter of `New` is left dirty. Returns the number of migrated
+    /// entries.
+    pub fn migrate_column<Old, New>(
+        &self,
+        map: impl Fn(Old::Index, &[u8]) -> Option<(New::Index, Vec<u8>)>,
+    ) -> LedgerResult<u64>
+    where
+        Old: ColumnIndexDeprecation + ColumnName,
+        New: Column + ColumnName,
+    {
+        let old = self.db.column::<Old>();
+
+        let mut num_migrated = 0;
+        let mut batch = self.db.batch();
+        for (index, value) in
+            old.iter_current_index_filtered(IteratorMode::Start)?
+        {
+            let Some((new_index, new_value)) = map(index, &value) else {
+                continue;
+            };
+            batch.put_bytes::<New>(new_index, &new_value);
+            num_migrated += 1;
+
+            if batch.len() >= MIGRATION_BATCH_SIZE {
+                self.db.write(batch)?;
+                self.mark_counter_dirty(New::NAME)?;
+                batch = self.db.batch();
+            }
+        }
+        self.db.write(batch)?;
+        self.mark_counter_dirty(New::NAME)?;
+
+        Ok(num_migrated)
+    }
+
+    /// Leaves the entry counter of the column `name` dirty, i.e. after
+    /// writing a [WriteBatch] to it that may have overwritten entries
+    fn mark_counter_dirty(&self, name: &str) -> LedgerResult<()> {
+        with_column!(self, name, |column| {
+            column.mark_counter_dirty();
+            Ok(())
+        })
     }
 
     /// Flushes all columns
     pub fn flush(&self) -> LedgerResult<()> {
         let cfs = [
-            self.transaction_status_cf.handle(),
-            self.address_signatures_cf.handle(),
-            self.slot_signatures_cf.handle(),
-            self.blocktime_cf.handle(),
-            self.blockhash_cf.handle(),
-            self.transaction_cf.handle(),
-            self.transaction_memos_cf.handle(),
-            self.perf_samples_cf.handle(),
-            self.account_mod_datas_cf.handle(),
+            self.transaction_status_cf.try_handle()?,
+            self.address_signatures_cf.try_handle()?,
+            self.slot_signatures_cf.try_handle()?,
+            self.blocktime_cf.try_handle()?,
+            self.blockhash_cf.try_handle()?,
+            self.transaction_cf.try_handle()?,
+            self.transaction_memos_cf.try_handle()?,
+            self.perf_samples_cf.try_handle()?,
+            self.account_mod_datas_cf.try_handle()?,
+            self.program_transaction_counts_cf.try_handle()?,
         ];
 
         self.db
@@ -1262,8 +1996,111 @@ impl Ledger {
             .flush_cfs_opt(&cfs, &FlushOptions::default())
     }
 
-    /// Graceful db shutdown
-    pub fn shutdown(&self, wait: bool) -> LedgerResult<()> {
+    /// Flushes the mem-tables of all column families one by one and,
+    /// if `sync_wal` is set, flushes the WAL and fsyncs it afterwards.
+    /// Once this returns, all writes that preceded it are on disk, i.e.
+    /// before creating a checkpoint.
+    ///
+    /// Syncing the WAL blocks until the device confirms the write,
+    /// which can take from milliseconds to much longer on busy disks,
+    /// so `sync_wal` shouldn't be set on hot paths.
+    pub fn flush_all(&self, sync_wal: bool) -> LedgerResult<()> {
+        for cf_name in columns() {
+            self.db
+                .backend
+                .flush_cf(&self.db.backend.try_cf_handle(cf_name)?)?;
+        }
+        if sync_wal {
+            self.db.backend.flush_wal(true)?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes all column families, syncs the WAL and closes the database,
+    /// so that its LOCK file is released once this returns, i.e. before
+    /// moving the ledger directory.
+    ///
+    /// Fails with [LedgerError::LedgerInUse] if other handles still share
+    /// the database, i.e. columns obtained from [Ledger::async_column] or
+    /// ledgers of other namespaces, see [LedgerOptions::namespace].
+    /// The database is closed once the last of them is dropped then.
+    pub fn close(self) -> LedgerResult<()> {
+        // Ledgers of other namespaces share the database through their own
+        // backends, so fail before flushing anything on their behalf
+        let other_ledgers = Arc::strong_count(&self.db.backend.db) - 1;
+        if other_ledgers > 0 {
+            return Err(LedgerError::LedgerInUse(other_ledgers));
+        }
+        if self.db.is_primary_access() {
+            self.flush_all(true)?;
+        }
+
+        let Ledger {
+            db,
+            blocktime_cf,
+            blockhash_cf,
+            slot_signatures_cf,
+            address_signatures_cf,
+            transaction_status_cf,
+            transaction_cf,
+            transaction_memos_cf,
+            perf_samples_cf,
+            account_mod_datas_cf,
+            program_transaction_counts_cf,
+            ..
+        } = self;
+        // The columns share the database as well
+        drop((
+            blocktime_cf,
+            blockhash_cf,
+            slot_signatures_cf,
+            address_signatures_cf,
+            transaction_status_cf,
+            transaction_cf,
+            transaction_memos_cf,
+            perf_samples_cf,
+            account_mod_datas_cf,
+            program_transaction_counts_cf,
+        ));
+
+        let db = Arc::try_unwrap(db).map_err(|db| {
+            LedgerError::LedgerInUse(Arc::strong_count(&db) - 1)
+        })?;
+        let backend = Arc::try_unwrap(db.backend).map_err(|backend| {
+            LedgerError::LedgerInUse(Arc::strong_count(&backend) - 1)
+        })?;
+        // Another namespace may have opened the shared database meanwhile
+        let db = Arc::try_unwrap(backend.db).map_err(|db| {
+            LedgerError::LedgerInUse(Arc::strong_count(&db) - 1)
+        })?;
+        drop(db);
+
+        Ok(())
+    }
+
+    /// Runs `f` with a view of the ledger at the current point in time,
+    /// so all its reads are consistent with each other even while other
+    /// threads keep writing to the ledger.
+    /// The snapshot is released once `f` returns.
+    pub fn with_snapshot<R>(&self, f: impl FnOnce(&SnapshotView) -> R) -> R {
+        let view = SnapshotView::new(&self.db);
+        f(&view)
+    }
+
+    /// Async access to column `C`, i.e. for RPC handlers,
+    /// see [AsyncLedgerColumn].
+    /// The returned column doesn't share the entry count cache of the ledger.
+    pub fn async_column<C>(&self) -> AsyncLedgerColumn<C>
+    where
+        C: Column + ColumnName + Send + Sync + 'static,
+        C::Index: Send + 'static,
+    {
+        AsyncLedgerColumn::new(Arc::new(self.db.column::<C>()))
+    }
+
+    /// Graceful db shutdown
+    pub fn shutdown(&self, wait: bool) -> LedgerResult<()> {
         self.flush()?;
         self.db.backend.db.cancel_all_background_work(wait);
 
@@ -1859,9 +2696,8 @@ mod tests {
         assert!(store.write_block(slot_uno, 1, Hash::new_unique()).is_ok());
         assert!(store.write_block(slot_dos, 2, Hash::new_unique()).is_ok());
         assert!(store.write_block(slot_tres, 3, Hash::new_unique()).is_ok());
-        assert!(store
-            .write_block(slot_cuatro, 4, Hash::new_unique())
-            .is_ok());
+        // Cuatro and cinco share the same slot and thus block
+        assert_eq!(slot_cuatro, slot_cinco);
         assert!(store.write_block(slot_cinco, 5, Hash::new_unique()).is_ok());
         assert!(store.write_block(slot_seis, 6, Hash::new_unique()).is_ok());
 
@@ -2295,6 +3131,7 @@ mod tests {
 
         let (sig_uno, slot_uno) = (Signature::new_unique(), 10);
         let (sig_dos, slot_dos) = (Signature::new_unique(), 10);
+        let blockhash = Hash::new_unique();
 
         let (tx_uno, sanitized_uno) =
             create_confirmed_transaction(slot_uno, 5, Some(100), None);
@@ -2313,9 +3150,7 @@ mod tests {
                 )
                 .is_ok());
 
-            assert!(store
-                .write_block(slot_uno, 100, Hash::new_unique())
-                .is_ok());
+            assert!(store.write_block(slot_uno, 100, blockhash).is_ok());
 
             assert!(store
                 .write_transaction_memos(
@@ -2336,9 +3171,7 @@ mod tests {
                     0,
                 )
                 .is_ok());
-            assert!(store
-                .write_block(slot_dos, 100, Hash::new_unique())
-                .is_ok());
+            assert!(store.write_block(slot_dos, 100, blockhash).is_ok());
             assert!(store
                 .write_transaction_memos(
                     &sig_dos,
@@ -2390,6 +3223,983 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_write_block_idempotent() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        let blockhash = Hash::new_unique();
+        store.write_block(1, 100, blockhash).unwrap();
+        store.write_block(2, 200, Hash::new_unique()).unwrap();
+        assert_eq!(store.count_blockhashes().unwrap(), 2);
+
+        // Retries with the same hash neither fail nor count the block twice
+        store.write_block(1, 100, blockhash).unwrap();
+        store.write_block(1, 150, blockhash).unwrap();
+        assert_eq!(store.get_block_time(1).unwrap(), Some(100));
+        assert_eq!(store.count_blockhashes().unwrap(), 2);
+        assert_eq!(store.count_block_times().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_write_block_hash_mismatch() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        let blockhash = Hash::new_unique();
+        store.write_block(1, 100, blockhash).unwrap();
+        let conflicting = Hash::new_unique();
+        match store.write_block(1, 200, conflicting) {
+            Err(LedgerError::BlockHashMismatch {
+                slot,
+                existing,
+                provided,
+            }) => {
+                assert_eq!(slot, 1);
+                assert_eq!(existing, blockhash);
+                assert_eq!(provided, conflicting);
+            }
+            other => panic!("expected a block hash mismatch, got {other:?}"),
+        }
+        // The block is left untouched
+        assert_eq!(store.get_block_hash(1).unwrap(), Some(blockhash));
+        assert_eq!(store.get_block_time(1).unwrap(), Some(100));
+    }
+
+    #[test]
+    fn test_purge_slot_range() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        let signatures = (0..1_000)
+            .map(|slot| {
+                let sig = Signature::new_unique();
+                let (tx, sanitized) =
+                    create_confirmed_transaction(slot, 1, Some(100), None);
+                store
+                    .write_transaction(
+                        sig,
+                        slot,
+                        sanitized,
+                        tx.tx_with_meta.get_status_meta().unwrap(),
+                        0,
+                    )
+                    .unwrap();
+                store.write_block(slot, 100, Hash::new_unique()).unwrap();
+                sig
+            })
+            .collect::<Vec<_>>();
+
+        // Spans multiple chunks
+        assert_eq!(store.purge_slot_range(100, 799).unwrap(), 700);
+        assert_eq!(store.get_lowest_cleanup_slot(), 799);
+        assert_eq!(store.purge_slot_range(10, 9).unwrap(), 0);
+
+        for (slot, sig) in signatures.iter().enumerate() {
+            let slot = slot as Slot;
+            let shall_exist = !(100..=799).contains(&slot);
+            assert_eq!(
+                store
+                    .transaction_cf
+                    .get_protobuf((*sig, slot))
+                    .unwrap()
+                    .is_some(),
+                shall_exist
+            );
+            assert_eq!(
+                store
+                    .transaction_status_cf
+                    .get_protobuf((*sig, slot))
+                    .unwrap()
+                    .is_some(),
+                shall_exist
+            );
+            assert_eq!(
+                store.slot_signatures_cf.get((slot, 0)).unwrap().is_some(),
+                shall_exist
+            );
+            assert_eq!(
+                store.blocktime_cf.get(slot).unwrap().is_some(),
+                shall_exist
+            );
+            assert_eq!(
+                store.blockhash_cf.get(slot).unwrap().is_some(),
+                shall_exist
+            );
+        }
+    }
+
+    #[test]
+    fn test_slot_bounds() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        assert_eq!(store.slot_bounds().unwrap(), None);
+
+        for slot in [3, 17, 42, 1_000] {
+            store.write_block(slot, 100, Hash::new_unique()).unwrap();
+        }
+        assert_eq!(store.slot_bounds().unwrap(), Some((3, 1_000)));
+
+        store.purge_slot_range(0, 20).unwrap();
+        assert_eq!(store.slot_bounds().unwrap(), Some((42, 1_000)));
+
+        store.purge_slot_range(21, 999).unwrap();
+        assert_eq!(store.slot_bounds().unwrap(), Some((1_000, 1_000)));
+    }
+
+    #[test]
+    fn test_open_with_retry() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        // Gives up while the ledger stays locked
+        let Err(err) = Ledger::open_with_retry(
+            ledger_path.path(),
+            2,
+            Duration::from_millis(10),
+        ) else {
+            panic!("ledger should stay locked");
+        };
+        assert!(
+            matches!(err, LedgerError::LedgerLocked { attempts: 3, .. }),
+            "{err}"
+        );
+        assert!(err.to_string().contains("locked"));
+
+        // Succeeds once the lock is released
+        let releaser = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(store);
+        });
+        let store = Ledger::open_with_retry(
+            ledger_path.path(),
+            10,
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        releaser.join().unwrap();
+        store.write_block(1, 100, Hash::new_unique()).unwrap();
+    }
+
+    #[test]
+    fn test_flush_all_persists_across_reopen() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+
+        let hashes = {
+            let store = Ledger::open(ledger_path.path()).unwrap();
+            let hashes = (0..10)
+                .map(|slot| {
+                    let hash = Hash::new_unique();
+                    store.write_block(slot, 100 + slot as i64, hash).unwrap();
+                    hash
+                })
+                .collect::<Vec<_>>();
+            store.flush_all(true).unwrap();
+            hashes
+        };
+
+        let store = Ledger::open(ledger_path.path()).unwrap();
+        for (slot, hash) in hashes.into_iter().enumerate() {
+            let slot = slot as u64;
+            assert_eq!(store.get_block_hash(slot).unwrap(), Some(hash));
+            assert_eq!(
+                store.get_block_time(slot).unwrap(),
+                Some(100 + slot as i64)
+            );
+        }
+    }
+
+    #[test]
+    fn test_compact_truncated_range() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        let signatures = (0..1000)
+            .map(|slot| {
+                let (tx, sanitized) =
+                    create_confirmed_transaction(slot, 1, Some(100), None);
+                let signature = Signature::new_unique();
+                store
+                    .write_transaction(
+                        signature,
+                        slot,
+                        sanitized,
+                        tx.tx_with_meta.get_status_meta().unwrap(),
+                        0,
+                    )
+                    .unwrap();
+                store.write_block(slot, 100, Hash::new_unique()).unwrap();
+                signature
+            })
+            .collect::<Vec<_>>();
+
+        store.purge_slot_range(0, 899).unwrap();
+        store.flush().unwrap();
+        store.compact_truncated_range(0, 899).unwrap();
+
+        assert!(store.slot_signatures_cf.get((899, 0)).unwrap().is_none());
+        assert_eq!(store.slot_bounds().unwrap(), Some((900, 999)));
+        for (slot, signature) in signatures.into_iter().enumerate().skip(900) {
+            let slot = slot as u64;
+            assert_eq!(
+                store.read_slot_signature((slot, 0)).unwrap(),
+                Some(signature)
+            );
+            assert!(store
+                .read_transaction((signature, slot))
+                .unwrap()
+                .is_some());
+            assert!(store.get_block_time(slot).unwrap().is_some());
+        }
+        assert!(store.get_block(901).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_open_columns() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+
+        let signature = Signature::new_unique();
+        {
+            let store = Ledger::open(ledger_path.path()).unwrap();
+            let (tx, sanitized) =
+                create_confirmed_transaction(1, 1, Some(100), None);
+            store
+                .write_transaction(
+                    signature,
+                    1,
+                    sanitized,
+                    tx.tx_with_meta.get_status_meta().unwrap(),
+                    0,
+                )
+                .unwrap();
+            store.write_block(1, 100, Hash::new_unique()).unwrap();
+            store.flush().unwrap();
+        }
+
+        assert!(matches!(
+            Ledger::open_columns(ledger_path.path(), &["not_a_column"]),
+            Err(LedgerError::UnknownColumn(_))
+        ));
+
+        let store = Ledger::open_columns(
+            ledger_path.path(),
+            &[cf::Blocktime::NAME, cf::SlotSignatures::NAME],
+        )
+        .unwrap();
+        assert_eq!(store.get_block_time(1).unwrap(), Some(100));
+        assert_eq!(store.read_slot_signature((1, 0)).unwrap(), Some(signature));
+
+        // Columns that weren't opened error instead of panicking
+        assert!(matches!(
+            store.get_block_hash(1),
+            Err(LedgerError::ColumnNotOpened(name)) if name == cf::Blockhash::NAME
+        ));
+        assert!(matches!(
+            store.read_transaction((signature, 1)),
+            Err(LedgerError::ColumnNotOpened(_))
+        ));
+        assert!(matches!(
+            store.delete_slot_range(0, 1),
+            Err(LedgerError::ColumnNotOpened(_))
+        ));
+    }
+
+    #[test]
+    fn test_open_with_io_limits() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let options = LedgerOptions {
+            max_open_files: 64,
+            rate_limiter_bytes_per_sec: Some(1024 * 1024),
+            max_background_jobs: Some(2),
+            ..LedgerOptions::default()
+        };
+        let store =
+            Ledger::open_with_options(ledger_path.path(), options).unwrap();
+
+        let sig = Signature::new_unique();
+        let (tx, sanitized) =
+            create_confirmed_transaction(1, 5, Some(100), None);
+        store
+            .write_transaction(
+                sig,
+                1,
+                sanitized,
+                tx.tx_with_meta.get_status_meta().unwrap(),
+                0,
+            )
+            .unwrap();
+        for slot in 1..=10 {
+            store
+                .write_block(slot, slot as i64, Hash::new_unique())
+                .unwrap();
+        }
+        store.flush().unwrap();
+        store
+            .compact_slot_range_cf::<cf::Blocktime>(None, None)
+            .unwrap();
+
+        assert!(store.read_transaction((sig, 1)).unwrap().is_some());
+        assert_eq!(store.read_slot_signature((1, 0)).unwrap(), Some(sig));
+        for slot in 1..=10 {
+            assert_eq!(store.get_block_time(slot).unwrap(), Some(slot as i64));
+        }
+    }
+
+    #[test]
+    fn test_open_with_wal_limits() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let options = || LedgerOptions {
+            max_total_wal_size: 64 * 1024,
+            wal_size_limit_mb: 1,
+            recycle_log_file_num: 2,
+            ..LedgerOptions::default()
+        };
+
+        let blockhashes: Vec<_> = {
+            let store =
+                Ledger::open_with_options(ledger_path.path(), options())
+                    .unwrap();
+            assert!(store.db.live_files_metadata().unwrap().is_empty());
+            let blockhashes = (1..=2_000)
+                .map(|slot| {
+                    let blockhash = Hash::new_unique();
+                    store.write_block(slot, slot as i64, blockhash).unwrap();
+                    blockhash
+                })
+                .collect();
+            // Exceeding the WAL limit flushes memtables in the background
+            // without being asked
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while store.db.live_files_metadata().unwrap().is_empty() {
+                assert!(Instant::now() < deadline, "WAL limit didn't flush");
+                thread::sleep(Duration::from_millis(10));
+            }
+            blockhashes
+        };
+
+        let store =
+            Ledger::open_with_options(ledger_path.path(), options()).unwrap();
+        for (slot, blockhash) in (1..).zip(blockhashes) {
+            assert_eq!(store.get_block_time(slot).unwrap(), Some(slot as i64));
+            assert_eq!(store.blockhash_cf.get(slot).unwrap(), Some(blockhash));
+        }
+        assert_eq!(store.count_blockhashes().unwrap(), 2_000);
+    }
+
+    #[test]
+    fn test_close_releases_lock() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+        let blockhash = Hash::new_unique();
+        store.write_block(1, 100, blockhash).unwrap();
+        store.close().unwrap();
+
+        let store = Ledger::open(ledger_path.path()).unwrap();
+        assert_eq!(store.get_block_time(1).unwrap(), Some(100));
+        assert_eq!(store.blockhash_cf.get(1).unwrap(), Some(blockhash));
+
+        // Other handles keep the database open
+        let column = store.async_column::<cf::Blocktime>();
+        assert!(matches!(store.close(), Err(LedgerError::LedgerInUse(1))));
+        assert!(Ledger::open(ledger_path.path()).is_err());
+        drop(column);
+        Ledger::open(ledger_path.path()).unwrap().close().unwrap();
+    }
+
+    #[test]
+    fn test_open_with_env() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let hash = Hash::new_unique();
+        {
+            let ledger =
+                Ledger::open_with_env(ledger_path.path(), Env::new().unwrap())
+                    .unwrap();
+            ledger.write_block(1, 100, hash).unwrap();
+            assert_eq!(ledger.get_block_time(1).unwrap(), Some(100));
+            assert_eq!(ledger.count_block_times().unwrap(), 1);
+        }
+
+        // Written through the default env, so readable without it
+        let ledger = Ledger::open(ledger_path.path()).unwrap();
+        assert_eq!(ledger.get_block_time(1).unwrap(), Some(100));
+        assert_eq!(ledger.blockhash_cf.get(1).unwrap(), Some(hash));
+
+        // Nothing of an in-memory env reaches the disk
+        let mem_path = get_tmp_ledger_path_auto_delete!();
+        let ledger =
+            Ledger::open_with_env(mem_path.path(), Env::mem_env().unwrap())
+                .unwrap();
+        ledger.write_block(1, 100, hash).unwrap();
+        ledger.flush().unwrap();
+        assert_eq!(ledger.get_block_time(1).unwrap(), Some(100));
+        assert_eq!(ledger.storage_size().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_open_with_namespace() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let ledger_a =
+            Ledger::open_with_namespace(ledger_path.path(), "a").unwrap();
+        // Created in the database ledger_a already uses
+        let ledger_b =
+            Ledger::open_with_namespace(ledger_path.path(), "b").unwrap();
+
+        let (hash_a, hash_b) = (Hash::new_unique(), Hash::new_unique());
+        ledger_a.write_block(1, 100, hash_a).unwrap();
+        assert_eq!(ledger_b.get_block_time(1).unwrap(), None);
+        ledger_b.write_block(1, 200, hash_b).unwrap();
+        ledger_b.write_block(2, 300, Hash::new_unique()).unwrap();
+
+        assert_eq!(ledger_a.get_block_time(1).unwrap(), Some(100));
+        assert_eq!(ledger_a.blockhash_cf.get(1).unwrap(), Some(hash_a));
+        assert_eq!(ledger_a.get_block_time(2).unwrap(), None);
+        assert_eq!(
+            ledger_a.blockhash_cf.count_column_using_cache().unwrap(),
+            1
+        );
+        assert_eq!(ledger_b.get_block_time(1).unwrap(), Some(200));
+        assert_eq!(ledger_b.blockhash_cf.get(1).unwrap(), Some(hash_b));
+        assert_eq!(
+            ledger_b.blockhash_cf.count_column_using_cache().unwrap(),
+            2
+        );
+
+        // Truncating one namespace leaves the other one untouched
+        ledger_b.delete_slot_range(1, 1).unwrap();
+        assert_eq!(ledger_b.blocktime_cf.get(1).unwrap(), None);
+        assert_eq!(ledger_b.blocktime_cf.get(2).unwrap(), Some(300));
+        assert_eq!(ledger_a.get_block_time(1).unwrap(), Some(100));
+
+        // The ledgers of other namespaces keep the database open
+        assert!(matches!(ledger_a.close(), Err(LedgerError::LedgerInUse(1))));
+        // The namespaces persist, the unnamespaced columns stay empty
+        ledger_b.close().unwrap();
+        let ledger_b =
+            Ledger::open_with_namespace(ledger_path.path(), "b").unwrap();
+        let ledger_a =
+            Ledger::open_with_namespace(ledger_path.path(), "a").unwrap();
+        assert_eq!(ledger_a.get_block_time(1).unwrap(), Some(100));
+        assert_eq!(ledger_b.get_block_time(2).unwrap(), Some(300));
+
+        // Database level options of the shared database can't be overridden
+        let options = LedgerOptions {
+            namespace: Some("c".to_string()),
+            enable_statistics: true,
+            ..LedgerOptions::default()
+        };
+        assert!(matches!(
+            Ledger::open_with_options(ledger_path.path(), options),
+            Err(LedgerError::SharedDatabaseOptionsMismatch(_))
+        ));
+
+        drop(ledger_b);
+        ledger_a.close().unwrap();
+        let ledger = Ledger::open(ledger_path.path()).unwrap();
+        assert_eq!(ledger.get_block_time(1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_with_snapshot() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        let blockhash = Hash::new_unique();
+        store.write_block(1, 100, blockhash).unwrap();
+
+        let sig = Signature::new_unique();
+        store.with_snapshot(|view| {
+            // Write concurrently while the snapshot is held
+            thread::scope(|scope| {
+                scope.spawn(|| {
+                    store.write_block(2, 200, Hash::new_unique()).unwrap();
+                    store.blocktime_cf.put(1, &300).unwrap();
+                    store
+                        .write_transaction_memos(&sig, 2, "memo".to_string())
+                        .unwrap();
+                });
+            });
+
+            assert_eq!(view.block_time(1).unwrap(), Some(100));
+            assert_eq!(view.block_hash(1).unwrap(), Some(blockhash));
+            assert!(view.block_time(2).unwrap().is_none());
+            assert!(view.block_hash(2).unwrap().is_none());
+            assert!(view.transaction_memos(sig, 2).unwrap().is_none());
+        });
+
+        // New snapshots observe the writes
+        assert_eq!(store.get_block_time(2).unwrap(), Some(200));
+        store.with_snapshot(|view| {
+            assert_eq!(view.block_time(1).unwrap(), Some(300));
+            assert_eq!(view.block_time(2).unwrap(), Some(200));
+            assert_eq!(
+                view.transaction_memos(sig, 2).unwrap(),
+                Some("memo".to_string())
+            );
+        });
+    }
+
+    #[test]
+    fn test_cleanup_compaction_filter() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let options = LedgerOptions {
+            cleanup_compaction_filter: true,
+            ..Default::default()
+        };
+        let store =
+            Ledger::open_with_options(ledger_path.path(), options).unwrap();
+
+        let signatures: Vec<_> = (0..=10)
+            .map(|slot| {
+                let signature = Signature::new_unique();
+                store.write_block(slot, 100, Hash::new_unique()).unwrap();
+                store
+                    .write_transaction_memos(&signature, slot, "memo".into())
+                    .unwrap();
+                signature
+            })
+            .collect();
+        store.flush().unwrap();
+
+        store.advance_lowest_cleanup_slot(5);
+        assert_eq!(store.get_lowest_cleanup_slot(), 5);
+        // Nothing is deleted until the columns are compacted
+        assert!(store.blocktime_cf.get(3).unwrap().is_some());
+
+        store
+            .compact_slot_range_cf::<cf::Blocktime>(None, None)
+            .unwrap();
+        store
+            .compact_slot_range_cf::<cf::TransactionMemos>(None, None)
+            .unwrap();
+        for (slot, signature) in signatures.iter().enumerate() {
+            let slot = slot as Slot;
+            let shall_exist = slot > 5;
+            assert_eq!(
+                store.blocktime_cf.get(slot).unwrap().is_some(),
+                shall_exist
+            );
+            assert_eq!(
+                store
+                    .transaction_memos_cf
+                    .get((*signature, slot))
+                    .unwrap()
+                    .is_some(),
+                shall_exist
+            );
+        }
+        // Columns that weren't compacted yet still have the entries
+        assert!(store.blockhash_cf.get(3).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_write_below_cleanup_slot() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        for slot in 1..=5 {
+            store.write_block(slot, 100, Hash::new_unique()).unwrap();
+        }
+        store.purge_slot_range(0, 3).unwrap();
+        assert_eq!(store.get_lowest_cleanup_slot(), 3);
+
+        for slot in [2, 3] {
+            assert!(matches!(
+                store.write_block(slot, 100, Hash::new_unique()),
+                Err(LedgerError::SlotCleanedUp { slot: s, lowest_cleanup: 3 }) if s == slot
+            ));
+        }
+        assert!(store.blocktime_cf.get(2).unwrap().is_none());
+
+        let sig = Signature::new_unique();
+        let (tx, sanitized) =
+            create_confirmed_transaction(2, 5, Some(100), None);
+        assert!(matches!(
+            store.write_transaction(
+                sig,
+                2,
+                sanitized,
+                tx.tx_with_meta.get_status_meta().unwrap(),
+                0,
+            ),
+            Err(LedgerError::SlotCleanedUp { .. })
+        ));
+        assert!(matches!(
+            store.write_transaction_memos(&sig, 2, "memo".to_string()),
+            Err(LedgerError::SlotCleanedUp { .. })
+        ));
+        assert_eq!(store.count_slot_signatures().unwrap(), 0);
+
+        // Slots above the cleanup slot are still writable
+        store.write_block(6, 200, Hash::new_unique()).unwrap();
+        store.write_block(7, 100, Hash::new_unique()).unwrap();
+        assert_eq!(store.get_block_time(6).unwrap(), Some(200));
+    }
+
+    #[test]
+    fn test_read_transaction_fenced() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        let write = |slot| {
+            let sig = Signature::new_unique();
+            let (tx, sanitized) =
+                create_confirmed_transaction(slot, 5, Some(100), None);
+            let meta = tx.tx_with_meta.get_status_meta().unwrap();
+            store
+                .write_transaction(sig, slot, sanitized, meta.clone(), 0)
+                .unwrap();
+            ((sig, slot), meta)
+        };
+        let read_fenced = |index| store.read_transaction_fenced(index).unwrap();
+
+        // Either column deleted first never exposes the remaining one
+        let (index, meta) = write(5);
+        let (transaction, status) = read_fenced(index).unwrap();
+        assert_eq!(
+            transaction,
+            store.read_transaction(index).unwrap().unwrap()
+        );
+        assert_eq!(status, meta);
+        store.transaction_status_cf.delete(index).unwrap();
+        assert!(store.read_transaction(index).unwrap().is_some());
+        assert_eq!(read_fenced(index), None);
+        store.transaction_cf.delete(index).unwrap();
+        assert_eq!(read_fenced(index), None);
+
+        let (index, _) = write(6);
+        store.transaction_cf.delete(index).unwrap();
+        assert!(store.read_transaction_status(index).unwrap().is_some());
+        assert_eq!(read_fenced(index), None);
+
+        // Slots at or below the cleanup slot are purged, even while their
+        // entries are still around
+        let (index, _) = write(7);
+        let (above, _) = write(8);
+        store.advance_lowest_cleanup_slot(7);
+        assert!(store.read_transaction(index).unwrap().is_some());
+        assert_eq!(read_fenced(index), None);
+        store.transaction_status_cf.delete(index).unwrap();
+        assert_eq!(read_fenced(index), None);
+        assert!(read_fenced(above).is_some());
+    }
+
+    #[test]
+    fn test_statistics_snapshot() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+        assert_eq!(store.statistics_snapshot(), None);
+        drop(store);
+
+        let options = LedgerOptions {
+            enable_statistics: true,
+            ..LedgerOptions::default()
+        };
+        let store =
+            Ledger::open_with_options(ledger_path.path(), options).unwrap();
+        for slot in 0..100 {
+            store.write_block(slot, 100, Hash::new_unique()).unwrap();
+        }
+        store.flush().unwrap();
+        for slot in 0..100 {
+            assert!(store.get_block_time(slot).unwrap().is_some());
+        }
+
+        let stats = store.statistics_snapshot().unwrap();
+        assert!(!stats.tickers.is_empty());
+        assert!(stats.bytes_written > 0, "{stats:?}");
+        assert!(stats.bytes_read > 0, "{stats:?}");
+        assert!(stats.block_cache_hit + stats.block_cache_miss > 0);
+        assert_eq!(
+            stats.tickers.get("rocksdb.bytes.written"),
+            Some(&stats.bytes_written)
+        );
+    }
+
+    #[test]
+    fn test_compact_columns_by_size() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        // Memos take the most space, followed by the blocks
+        let memo = "x".repeat(1_024);
+        for slot in 0..200 {
+            store
+                .write_transaction_memos(
+                    &Signature::new_unique(),
+                    slot,
+                    memo.clone(),
+                )
+                .unwrap();
+        }
+        for slot in 0..50 {
+            store.write_block(slot, 100, Hash::new_unique()).unwrap();
+        }
+        store.flush().unwrap();
+
+        let sizes = store.storage_size_by_column().unwrap();
+        assert_eq!(sizes.len(), columns().len());
+        let size_of =
+            |name| sizes.iter().find(|(column, _)| *column == name).unwrap().1;
+        let memos_size = size_of(cf::TransactionMemos::NAME);
+        assert!(memos_size > size_of(cf::Blockhash::NAME));
+        assert!(size_of(cf::Blockhash::NAME) > size_of(cf::Blocktime::NAME));
+        assert_eq!(size_of(cf::PerfSamples::NAME), 0);
+
+        let compacted = store.compact_columns_by_size(None).unwrap();
+        let order = compacted.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert_eq!(order.len(), columns().len());
+        assert_eq!(
+            order[..3],
+            [
+                cf::TransactionMemos::NAME,
+                cf::Blockhash::NAME,
+                cf::Blocktime::NAME
+            ]
+        );
+        assert_eq!(compacted[0].1.bytes_before, memos_size);
+
+        // Nothing needs to be reclaimed for a zero target
+        assert!(store.compact_columns_by_size(Some(0)).unwrap().is_empty());
+
+        // Dropping the memos already reclaims enough
+        store.transaction_memos_cf.delete_all().unwrap();
+        store.flush().unwrap();
+        let compacted = store.compact_columns_by_size(Some(1)).unwrap();
+        assert_eq!(compacted.len(), 1);
+        assert_eq!(compacted[0].0, cf::TransactionMemos::NAME);
+        assert!(compacted[0].1.bytes_reclaimed() > 0);
+    }
+
+    #[test]
+    fn test_verify_counters() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        for slot in 0..10 {
+            store.write_block(slot, 100, Hash::new_unique()).unwrap();
+        }
+        assert_eq!(store.count_block_times().unwrap(), 10);
+        assert_eq!(store.count_blockhashes().unwrap(), 10);
+        assert!(store.verify_counters().unwrap().is_empty());
+
+        // Skew the counters as a drifting counter would
+        store.blocktime_cf.try_increase_entry_counter(3);
+        store.blockhash_cf.try_decrease_entry_counter(1);
+        let mut discrepancies = store.verify_counters().unwrap();
+        discrepancies.sort_by_key(|discrepancy| discrepancy.cached);
+        assert_eq!(
+            discrepancies,
+            [
+                CounterDiscrepancy {
+                    column: cf::Blockhash::NAME,
+                    cached: 9,
+                    actual: 10,
+                },
+                CounterDiscrepancy {
+                    column: cf::Blocktime::NAME,
+                    cached: 13,
+                    actual: 10,
+                },
+            ]
+        );
+        // Verifying leaves the counters as they were
+        assert_eq!(store.count_block_times().unwrap(), 13);
+        assert_eq!(store.count_blockhashes().unwrap(), 9);
+
+        assert_eq!(store.repair_counters().unwrap().len(), 2);
+        assert_eq!(store.count_block_times().unwrap(), 10);
+        assert_eq!(store.count_blockhashes().unwrap(), 10);
+        assert!(store.verify_counters().unwrap().is_empty());
+
+        // Negative counters are reset like on open
+        store
+            .blocktime_cf
+            .entry_counter
+            .store(-5, Ordering::Relaxed);
+        store.validate_counters().unwrap();
+        assert_eq!(
+            store.blocktime_cf.entry_counter.load(Ordering::Relaxed),
+            DIRTY_COUNT
+        );
+        assert_eq!(store.count_block_times().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_migrate_column() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        // Rebuild the slot signatures index out of the address signatures
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+        let meta = |writeable| AddressSignatureMeta { writeable };
+        let signatures: Vec<_> =
+            (0..5).map(|_| Signature::new_unique()).collect();
+        for (slot, signature) in signatures.iter().enumerate() {
+            let slot = slot as Slot;
+            store
+                .address_signatures_cf
+                .put((writable, slot, 0, *signature), &meta(true))
+                .unwrap();
+            store
+                .address_signatures_cf
+                .put((readonly, slot, 0, *signature), &meta(false))
+                .unwrap();
+        }
+        // Entries still using the deprecated index aren't migrated
+        store
+            .db
+            .backend
+            .put_cf(
+                &store.address_signatures_cf.handle(),
+                &cf::AddressSignatures::deprecated_key((
+                    0,
+                    writable,
+                    10,
+                    Signature::new_unique(),
+                )),
+                &serialize(&meta(true)).unwrap(),
+            )
+            .unwrap();
+        // Overwritten by the migration, which mustn't count it twice
+        store
+            .slot_signatures_cf
+            .put((0, 0), &Signature::new_unique())
+            .unwrap();
+        assert_eq!(store.count_slot_signatures().unwrap(), 1);
+
+        let num_migrated = store
+            .migrate_column::<cf::AddressSignatures, cf::SlotSignatures>(
+                |(address, slot, transaction_index, signature), value| {
+                    let meta: AddressSignatureMeta = deserialize(value).ok()?;
+                    (address == writable && meta.writeable).then(|| {
+                        (
+                            (slot, transaction_index),
+                            serialize(&signature).unwrap(),
+                        )
+                    })
+                },
+            )
+            .unwrap();
+
+        assert_eq!(num_migrated, 5);
+        for (slot, signature) in signatures.iter().enumerate() {
+            assert_eq!(
+                store.read_slot_signature((slot as Slot, 0)).unwrap(),
+                Some(*signature)
+            );
+        }
+        assert_eq!(store.read_slot_signature((10, 0)).unwrap(), None);
+        assert_eq!(store.count_slot_signatures().unwrap(), 5);
+        assert_eq!(store.count_address_signatures().unwrap(), 11);
+    }
+
+    #[test]
+    fn test_ingest_batch() {
+        init_logger!();
+        let ledger_path = get_tmp_ledger_path_auto_delete!();
+        let store = Ledger::open(ledger_path.path()).unwrap();
+
+        let (_, sanitized) =
+            create_confirmed_transaction(1, 5, Some(100), None);
+        let transaction: generated::Transaction =
+            sanitized.to_versioned_transaction().into();
+        let indexes: Vec<_> = (0..1000)
+            .map(|i| (Signature::new_unique(), i / 10))
+            .collect();
+        // Counters are cached from here on, so the batch has to update them
+        assert_eq!(store.count_transactions().unwrap(), 0);
+        assert_eq!(store.count_transaction_memos().unwrap(), 0);
+
+        store
+            .ingest_batch(|batch| {
+                for index in &indexes {
+                    batch.put_protobuf::<cf::Transaction>(*index, &transaction);
+                    batch
+                        .put::<cf::TransactionMemos>(
+                            *index,
+                            &format!("{index:?}"),
+                        )
+                        .unwrap();
+                }
+            })
+            .unwrap();
+
+        for index in &indexes {
+            assert_eq!(
+                store.read_transaction(*index).unwrap().as_ref(),
+                Some(&transaction)
+            );
+            assert_eq!(
+                store.read_transaction_memos(index.0, index.1).unwrap(),
+                Some(format!("{index:?}"))
+            );
+        }
+        assert_eq!(store.count_transactions().unwrap(), 1000);
+        assert_eq!(store.count_transaction_memos().unwrap(), 1000);
+        assert_eq!(store.count_blockhashes().unwrap(), 0);
+
+        // Overwrites don't add entries, deletes remove them
+        store
+            .ingest_batch(|batch| {
+                for index in &indexes[..10] {
+                    batch.put_protobuf::<cf::Transaction>(*index, &transaction);
+                    batch.delete::<cf::TransactionMemos>(*index);
+                }
+            })
+            .unwrap();
+        assert_eq!(store.count_transactions().unwrap(), 1000);
+        assert_eq!(store.count_transaction_memos().unwrap(), 990);
+        assert_eq!(
+            store.read_transaction_memos(indexes[0].0, 0).unwrap(),
+            None
+        );
+
+        // Puts to cleaned up slots reject the whole batch
+        store.advance_lowest_cleanup_slot(50);
+        let signature = Signature::new_unique();
+        assert!(matches!(
+            store.ingest_batch(|batch| {
+                batch.put_protobuf::<cf::Transaction>(
+                    (signature, 100),
+                    &transaction,
+                );
+                batch.put_protobuf::<cf::Transaction>(
+                    (signature, 50),
+                    &transaction,
+                );
+            }),
+            Err(LedgerError::SlotCleanedUp {
+                slot: 50,
+                lowest_cleanup: 50
+            })
+        ));
+        assert_eq!(store.read_transaction((signature, 100)).unwrap(), None);
+        assert_eq!(store.count_transactions().unwrap(), 1000);
+    }
+
     #[test]
     fn test_truncate_slots() {
         init_logger!();
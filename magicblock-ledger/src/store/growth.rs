@@ -0,0 +1,100 @@
+use std::{collections::VecDeque, time::Instant};
+
+/// Number of storage size samples the growth rate is derived from
+const MAX_GROWTH_SAMPLES: usize = 32;
+
+/// Estimated growth of the ledger storage, see [crate::Ledger::growth_estimate]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrowthEstimate {
+    /// Net growth over the sampled window, negative if the ledger shrank
+    pub bytes_per_sec: f64,
+    /// Time until the storage reaches the size budget at the current rate,
+    /// `0` if it's already exceeded. `None` without a budget or if the
+    /// ledger isn't growing.
+    pub seconds_to_budget: Option<u64>,
+}
+
+/// Ring buffer of storage size samples
+#[derive(Debug, Default)]
+pub(crate) struct GrowthTracker {
+    samples: VecDeque<(Instant, u64)>,
+    size_budget: Option<u64>,
+}
+
+impl GrowthTracker {
+    pub(crate) fn record(&mut self, at: Instant, size: u64) {
+        if self.samples.len() == MAX_GROWTH_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((at, size));
+    }
+
+    pub(crate) fn set_size_budget(&mut self, size_budget: Option<u64>) {
+        self.size_budget = size_budget;
+    }
+
+    /// Derives the rate from the oldest and the newest sample, so that
+    /// truncations in between are accounted for.
+    /// Returns `None` until two samples that are apart in time were recorded.
+    pub(crate) fn estimate(&self) -> Option<GrowthEstimate> {
+        let (first_at, first_size) = *self.samples.front()?;
+        let (last_at, last_size) = *self.samples.back()?;
+        let elapsed = last_at.checked_duration_since(first_at)?.as_secs_f64();
+        if elapsed == 0.0 {
+            return None;
+        }
+
+        let bytes_per_sec = (last_size as f64 - first_size as f64) / elapsed;
+        let seconds_to_budget = self.size_budget.and_then(|budget| {
+            if last_size >= budget {
+                Some(0)
+            } else if bytes_per_sec > 0.0 {
+                Some(((budget - last_size) as f64 / bytes_per_sec) as u64)
+            } else {
+                None
+            }
+        });
+        Some(GrowthEstimate {
+            bytes_per_sec,
+            seconds_to_budget,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_growth_estimate() {
+        let start = Instant::now();
+        let mut tracker = GrowthTracker::default();
+        assert_eq!(tracker.estimate(), None);
+        tracker.record(start, 1_000);
+        assert_eq!(tracker.estimate(), None);
+
+        // 100 bytes/sec with a truncation in between
+        tracker.record(start + Duration::from_secs(5), 1_500);
+        tracker.record(start + Duration::from_secs(6), 1_200);
+        tracker.record(start + Duration::from_secs(10), 2_000);
+        let estimate = tracker.estimate().unwrap();
+        assert!((estimate.bytes_per_sec - 100.0).abs() < f64::EPSILON);
+        assert_eq!(estimate.seconds_to_budget, None);
+
+        tracker.set_size_budget(Some(5_000));
+        assert_eq!(tracker.estimate().unwrap().seconds_to_budget, Some(30));
+        tracker.set_size_budget(Some(1_500));
+        assert_eq!(tracker.estimate().unwrap().seconds_to_budget, Some(0));
+
+        // Old samples are dropped, leaving a shrinking ledger
+        tracker.set_size_budget(Some(5_000));
+        for i in 0..MAX_GROWTH_SAMPLES as u64 {
+            tracker.record(start + Duration::from_secs(20 + i), 4_000 - i * 10);
+        }
+        let estimate = tracker.estimate().unwrap();
+        assert!((estimate.bytes_per_sec + 10.0).abs() < f64::EPSILON);
+        assert_eq!(estimate.seconds_to_budget, None);
+    }
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use log::*;
+use tokio::{
+    task::{JoinError, JoinHandle},
+    time::interval,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::database::{
+    columns::{self as cf, Column, ColumnName},
+    db::Database,
+};
+
+/// Submits the RocksDB metrics of a single column
+type MetricsSubmitter = Box<dyn Fn() + Send + Sync>;
+
+/// Handle of the task started by [crate::Ledger::start_metrics_reporter]
+#[derive(Debug)]
+pub struct MetricsReporterHandle {
+    cancellation_token: CancellationToken,
+    worker_handle: JoinHandle<u64>,
+}
+
+impl MetricsReporterHandle {
+    /// Spawns a task submitting the metrics of all opened columns of `db`
+    /// every `interval`, starting right away.
+    /// Must be called within a tokio runtime.
+    pub(crate) fn spawn(db: &Database, interval_duration: Duration) -> Self {
+        let submitters = [
+            submitter::<cf::TransactionStatus>(db),
+            submitter::<cf::AddressSignatures>(db),
+            submitter::<cf::SlotSignatures>(db),
+            submitter::<cf::Blocktime>(db),
+            submitter::<cf::Blockhash>(db),
+            submitter::<cf::Transaction>(db),
+            submitter::<cf::TransactionMemos>(db),
+            submitter::<cf::PerfSamples>(db),
+            submitter::<cf::AccountModDatas>(db),
+            submitter::<cf::ProgramTransactionCounts>(db),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        let cancellation_token = CancellationToken::new();
+        let worker_handle = tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move {
+                let mut rounds = 0;
+                let mut interval = interval(interval_duration);
+                loop {
+                    tokio::select! {
+                        biased;
+                        _ = cancellation_token.cancelled() => {
+                            return rounds;
+                        }
+                        _ = interval.tick() => {
+                            submitters.iter().for_each(|submit| submit());
+                            rounds += 1;
+                        }
+                    }
+                }
+            }
+        });
+        Self {
+            cancellation_token,
+            worker_handle,
+        }
+    }
+
+    /// Signals the reporter to stop without waiting for it
+    pub fn stop(&self) {
+        self.cancellation_token.cancel();
+    }
+
+    /// Stops the reporter and waits for it to exit, returns the number of
+    /// rounds of metrics it submitted
+    pub async fn join(self) -> Result<u64, JoinError> {
+        self.stop();
+        let rounds = self.worker_handle.await?;
+        debug!("Ledger metrics reporter stopped after {rounds} rounds");
+        Ok(rounds)
+    }
+}
+
+/// Returns `None` for columns that weren't opened,
+/// see [crate::Ledger::open_columns]
+fn submitter<C>(db: &Database) -> Option<MetricsSubmitter>
+where
+    C: Column + ColumnName + Send + Sync + 'static,
+{
+    db.backend.try_cf_handle(C::NAME).ok()?;
+    let column = db.column::<C>();
+    Some(Box::new(move || column.submit_rocksdb_cf_metrics()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempfile::tempdir;
+
+    use crate::Ledger;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_metrics_reporter() {
+        let temp_dir = tempdir().unwrap();
+        let ledger = Ledger::open(temp_dir.path()).unwrap();
+
+        let reporter = ledger.start_metrics_reporter(Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(35)).await;
+        // At 0, 10, 20 and 30ms
+        assert_eq!(reporter.join().await.unwrap(), 4);
+
+        // Stopped reporters release their handles to the database
+        let reporter = ledger.start_metrics_reporter(Duration::from_secs(60));
+        reporter.stop();
+        assert_eq!(reporter.join().await.unwrap(), 0);
+        ledger.close().unwrap();
+    }
+}
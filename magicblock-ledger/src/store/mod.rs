@@ -1,3 +1,7 @@
 pub mod api;
 pub mod data_mod_persister;
+pub mod growth;
+pub mod metrics_reporter;
+pub mod snapshot_view;
+pub mod statistics;
 mod utils;
@@ -0,0 +1,102 @@
+use prost::Message;
+use rocksdb::Snapshot;
+use solana_sdk::{
+    clock::{Slot, UnixTimestamp},
+    hash::Hash,
+    signature::Signature,
+};
+use solana_storage_proto::convert::generated;
+use solana_transaction_status::TransactionStatusMeta;
+
+use crate::{
+    database::{
+        codec::ColumnCodec,
+        columns as cf,
+        columns::{ColumnName, ProtobufColumn, TypedColumn},
+        db::Database,
+    },
+    errors::{LedgerError, LedgerResult},
+};
+
+/// Reads of the ledger columns that all observe the same point in time,
+/// see [crate::Ledger::with_snapshot].
+pub struct SnapshotView<'a> {
+    db: &'a Database,
+    snapshot: Snapshot<'a>,
+}
+
+impl<'a> SnapshotView<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self {
+            db,
+            snapshot: db.backend.snapshot(),
+        }
+    }
+
+    fn get<C: TypedColumn + ColumnName>(
+        &self,
+        index: C::Index,
+    ) -> LedgerResult<Option<C::Type>> {
+        let cf = self.db.backend.try_cf_handle(C::NAME)?;
+        let key = C::key(index);
+        self.snapshot
+            .get_pinned_cf(&cf, &key)?
+            .map(|value| {
+                C::Codec::decode(value.as_ref())
+                    .map_err(|err| LedgerError::deserialize(C::NAME, &key, err))
+            })
+            .transpose()
+    }
+
+    fn get_protobuf<C: ProtobufColumn + ColumnName>(
+        &self,
+        index: C::Index,
+    ) -> LedgerResult<Option<C::Type>> {
+        let cf = self.db.backend.try_cf_handle(C::NAME)?;
+        self.snapshot
+            .get_pinned_cf(&cf, C::key(index))?
+            .map(|value| Ok(C::Type::decode(value.as_ref())?))
+            .transpose()
+    }
+
+    pub fn block_time(
+        &self,
+        slot: Slot,
+    ) -> LedgerResult<Option<UnixTimestamp>> {
+        self.get::<cf::Blocktime>(slot)
+    }
+
+    pub fn block_hash(&self, slot: Slot) -> LedgerResult<Option<Hash>> {
+        self.get::<cf::Blockhash>(slot)
+    }
+
+    pub fn slot_signature(
+        &self,
+        index: (Slot, u32),
+    ) -> LedgerResult<Option<Signature>> {
+        self.get::<cf::SlotSignatures>(index)
+    }
+
+    pub fn transaction(
+        &self,
+        index: (Signature, Slot),
+    ) -> LedgerResult<Option<generated::Transaction>> {
+        self.get_protobuf::<cf::Transaction>(index)
+    }
+
+    pub fn transaction_status(
+        &self,
+        index: (Signature, Slot),
+    ) -> LedgerResult<Option<TransactionStatusMeta>> {
+        let meta = self.get_protobuf::<cf::TransactionStatus>(index)?;
+        Ok(meta.and_then(|meta| meta.try_into().ok()))
+    }
+
+    pub fn transaction_memos(
+        &self,
+        signature: Signature,
+        slot: Slot,
+    ) -> LedgerResult<Option<String>> {
+        self.get::<cf::TransactionMemos>((signature, slot))
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+const BLOCK_CACHE_HIT: &str = "rocksdb.block.cache.hit";
+const BLOCK_CACHE_MISS: &str = "rocksdb.block.cache.miss";
+const BYTES_READ: &str = "rocksdb.bytes.read";
+const BYTES_WRITTEN: &str = "rocksdb.bytes.written";
+const STALL_MICROS: &str = "rocksdb.stall.micros";
+
+/// Counters of the RocksDB statistics, see [crate::Ledger::statistics_snapshot].
+/// All of them are cumulative since the database was opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RocksStats {
+    pub block_cache_hit: u64,
+    pub block_cache_miss: u64,
+    /// Bytes read by point lookups
+    pub bytes_read: u64,
+    /// Bytes written by puts, merges and deletes
+    pub bytes_written: u64,
+    /// Time writes were delayed or stopped for
+    pub stall_micros: u64,
+    /// All the tickers of the report keyed by their name, including the
+    /// ones above
+    pub tickers: HashMap<String, u64>,
+}
+
+impl RocksStats {
+    /// Parses the tickers out of the report RocksDB returns for the
+    /// `rocksdb.options-statistics` property, i.e. lines like
+    /// `rocksdb.block.cache.miss COUNT : 42`. Histograms are skipped.
+    pub(crate) fn parse(report: &str) -> Self {
+        let tickers: HashMap<_, _> = report
+            .lines()
+            .filter_map(|line| {
+                match line.split_whitespace().collect::<Vec<_>>()[..] {
+                    [name, "COUNT", ":", count] => {
+                        Some((name.to_string(), count.parse().ok()?))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        let ticker = |name| tickers.get(name).copied().unwrap_or_default();
+
+        Self {
+            block_cache_hit: ticker(BLOCK_CACHE_HIT),
+            block_cache_miss: ticker(BLOCK_CACHE_MISS),
+            bytes_read: ticker(BYTES_READ),
+            bytes_written: ticker(BYTES_WRITTEN),
+            stall_micros: ticker(STALL_MICROS),
+            tickers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_statistics() {
+        let report = "\
+rocksdb.block.cache.miss COUNT : 12
+rocksdb.block.cache.hit COUNT : 30
+rocksdb.bytes.written COUNT : 4096
+rocksdb.stall.micros COUNT : 7
+rocksdb.db.get.micros P50 : 1.5 P95 : 3.0 P99 : 4.0 P100 : 9.0 COUNT : 42 SUM : 77
+rocksdb.malformed COUNT : many
+";
+        let stats = RocksStats::parse(report);
+        assert_eq!(stats.block_cache_hit, 30);
+        assert_eq!(stats.block_cache_miss, 12);
+        assert_eq!(stats.bytes_read, 0);
+        assert_eq!(stats.bytes_written, 4096);
+        assert_eq!(stats.stall_micros, 7);
+        assert_eq!(stats.tickers.len(), 4);
+        assert!(!stats.tickers.contains_key("rocksdb.db.get.micros"));
+    }
+}
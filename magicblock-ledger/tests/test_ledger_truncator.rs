@@ -1,5 +1,7 @@
 mod common;
 use std::{
+    io,
+    path::Path,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
@@ -8,12 +10,36 @@ use std::{
 };
 
 use magicblock_core::traits::FinalityProvider;
-use magicblock_ledger::{ledger_truncator::LedgerTruncator, Ledger};
+use magicblock_ledger::{
+    ledger_truncator::{
+        FreeSpaceProvider, LedgerTruncator, RetentionPolicy,
+        SafetyMarginFinalityProvider,
+    },
+    ColumnGroup, Ledger,
+};
 use solana_sdk::{hash::Hash, signature::Signature};
+use tokio_util::sync::CancellationToken;
 
 use crate::common::{setup, write_dummy_transaction};
 
 const TEST_TRUNCATION_TIME_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Lets the tasks woken up so far run until all of them wait for the clock,
+/// which the paused runtime only auto-advances once it's idle
+async fn settle() {
+    tokio::time::sleep(Duration::from_nanos(1)).await;
+}
+
+/// Advances the paused clock one truncation interval at a time, letting
+/// the worker complete the cycle due at the start of each of them
+async fn advance_intervals(num_intervals: u32) {
+    for _ in 0..num_intervals {
+        settle().await;
+        tokio::time::advance(TEST_TRUNCATION_TIME_INTERVAL).await;
+    }
+    settle().await;
+}
+
 #[derive(Default)]
 pub struct TestFinalityProvider {
     pub latest_final_slot: AtomicU64,
@@ -25,6 +51,17 @@ impl FinalityProvider for TestFinalityProvider {
     }
 }
 
+#[derive(Default)]
+pub struct TestFreeSpaceProvider {
+    pub free_space: AtomicU64,
+}
+
+impl FreeSpaceProvider for TestFreeSpaceProvider {
+    fn free_space(&self, _path: &Path) -> io::Result<u64> {
+        Ok(self.free_space.load(Ordering::Relaxed))
+    }
+}
+
 fn verify_transactions_state(
     ledger: &Ledger,
     start_slot: u64,
@@ -54,8 +91,37 @@ fn verify_transactions_state(
     }
 }
 
+/// Asserts that every column has the slots above `cleanup_slot`
+/// and none of the others
+fn verify_columns_consistent(
+    ledger: &Ledger,
+    signatures: &[Signature],
+    cleanup_slot: u64,
+) {
+    for (slot, signature) in signatures.iter().enumerate() {
+        let slot = slot as u64;
+        let shall_exist = slot > cleanup_slot;
+        let present = [
+            ledger.read_slot_signature((slot, 0)).unwrap().is_some(),
+            ledger
+                .read_transaction((*signature, slot))
+                .unwrap()
+                .is_some(),
+            ledger
+                .read_transaction_status((*signature, slot))
+                .unwrap()
+                .is_some(),
+            ledger
+                .read_transaction_memos(*signature, slot)
+                .unwrap()
+                .is_some(),
+        ];
+        assert_eq!(present, [shall_exist; 4], "slot {slot}");
+    }
+}
+
 /// Tests that ledger is not truncated if finality slot - 0
-#[tokio::test]
+#[tokio::test(start_paused = true)]
 async fn test_truncator_not_purged_finality() {
     const SLOT_TRUNCATION_INTERVAL: u64 = 5;
 
@@ -85,16 +151,18 @@ async fn test_truncator_not_purged_finality() {
         .collect::<Vec<_>>();
 
     ledger_truncator.start();
-    tokio::time::sleep(Duration::from_millis(10)).await;
+    advance_intervals(1).await;
     ledger_truncator.stop();
-    assert!(ledger_truncator.join().await.is_ok());
+    let summary = ledger_truncator.join().await.unwrap();
+    assert_eq!(summary.total_slots_purged, 0);
+    assert!(summary.last_error.is_none());
 
     // Not truncated due to final_slot 0
     verify_transactions_state(&ledger, 0, &signatures, true);
 }
 
 // Tests that ledger is not truncated while there is still enough space
-#[tokio::test]
+#[tokio::test(start_paused = true)]
 async fn test_truncator_not_purged_size() {
     const NUM_TRANSACTIONS: u64 = 100;
 
@@ -124,16 +192,18 @@ async fn test_truncator_not_purged_size() {
         .collect::<Vec<_>>();
 
     ledger_truncator.start();
-    tokio::time::sleep(Duration::from_millis(10)).await;
+    advance_intervals(1).await;
     ledger_truncator.stop();
-    assert!(ledger_truncator.join().await.is_ok());
+    let summary = ledger_truncator.join().await.unwrap();
+    assert_eq!(summary.total_slots_purged, 0);
+    assert!(summary.last_error.is_none());
 
     // Not truncated due to final_slot 0
     verify_transactions_state(&ledger, 0, &signatures, true);
 }
 
 // Tests that ledger got truncated but not after finality slot
-#[tokio::test]
+#[tokio::test(start_paused = true)]
 async fn test_truncator_non_empty_ledger() {
     const FINAL_SLOT: u64 = 80;
 
@@ -158,19 +228,134 @@ async fn test_truncator_non_empty_ledger() {
     );
 
     ledger_truncator.start();
-    tokio::time::sleep(TEST_TRUNCATION_TIME_INTERVAL).await;
+    advance_intervals(1).await;
 
     ledger_truncator.stop();
-    assert!(ledger_truncator.join().await.is_ok());
+    let summary = ledger_truncator.join().await.unwrap();
 
     let cleanup_slot = ledger.get_lowest_cleanup_slot();
     assert_ne!(ledger.get_lowest_cleanup_slot(), 0);
+    assert!(summary.total_cycles > 0);
+    assert_eq!(summary.total_slots_purged, cleanup_slot + 1);
+    assert_eq!(summary.total_errors, 0);
+    assert!(summary.last_error.is_none());
+    verify_transactions_state(
+        &ledger,
+        0,
+        &signatures[..(cleanup_slot + 1) as usize],
+        false,
+    );
+    verify_transactions_state(
+        &ledger,
+        cleanup_slot + 1,
+        &signatures[(cleanup_slot + 1) as usize..],
+        true,
+    );
+}
+
+// Tests that the ledger is truncated up to the final slot once the volume
+// runs short of free space, even though it's within its size limit
+#[tokio::test(start_paused = true)]
+async fn test_truncator_min_free_space() {
+    const FINAL_SLOT: u64 = 80;
+    const MIN_FREE_SPACE: u64 = 1 << 30;
+
+    let ledger = Arc::new(setup());
+    let signatures = (0..FINAL_SLOT + 20)
+        .map(|i| {
+            let (_, signature) = write_dummy_transaction(&ledger, i, 0);
+            ledger.write_block(i, 0, Hash::new_unique()).unwrap();
+            signature
+        })
+        .collect::<Vec<_>>();
+
+    let finality_provider = Arc::new(TestFinalityProvider {
+        latest_final_slot: FINAL_SLOT.into(),
+    });
+    let free_space_provider = Arc::new(TestFreeSpaceProvider {
+        free_space: MIN_FREE_SPACE.into(),
+    });
+    let mut ledger_truncator = LedgerTruncator::new(
+        ledger.clone(),
+        finality_provider,
+        TEST_TRUNCATION_TIME_INTERVAL,
+        u64::MAX,
+    )
+    .with_min_free_space(MIN_FREE_SPACE)
+    .with_free_space_provider(free_space_provider.clone());
+
+    ledger_truncator.start();
+    advance_intervals(2).await;
+    assert_eq!(ledger.get_lowest_cleanup_slot(), 0);
+    verify_transactions_state(&ledger, 0, &signatures, true);
+
+    // The deficit exceeds the whole ledger, yet the final slot survives
+    free_space_provider.free_space.store(0, Ordering::Relaxed);
+    advance_intervals(1).await;
+    ledger_truncator.stop();
+    let summary = ledger_truncator.join().await.unwrap();
+
+    assert_eq!(ledger.get_lowest_cleanup_slot(), FINAL_SLOT - 1);
+    assert_eq!(summary.total_slots_purged, FINAL_SLOT);
+    assert!(summary.last_error.is_none());
+    let final_slot = FINAL_SLOT as usize;
+    verify_transactions_state(&ledger, 0, &signatures[..final_slot], false);
+    verify_transactions_state(
+        &ledger,
+        FINAL_SLOT,
+        &signatures[final_slot..],
+        true,
+    );
+}
+
+// Tests that slots within the safety margin of the final slot survive truncation
+#[tokio::test(start_paused = true)]
+async fn test_truncator_with_safety_margin() {
+    const FINAL_SLOT: u64 = 80;
+    const MARGIN: u64 = 20;
+
+    let ledger = Arc::new(setup());
+    let signatures = (0..FINAL_SLOT + 20)
+        .map(|i| {
+            let (_, signature) = write_dummy_transaction(&ledger, i, 0);
+            ledger.write_block(i, 0, Hash::new_unique()).unwrap();
+            signature
+        })
+        .collect::<Vec<_>>();
+
+    let finality_provider = Arc::new(TestFinalityProvider {
+        latest_final_slot: FINAL_SLOT.into(),
+    });
+    let finality_provider =
+        Arc::new(SafetyMarginFinalityProvider::new(finality_provider, MARGIN));
+    assert_eq!(
+        finality_provider.get_latest_final_slot(),
+        FINAL_SLOT - MARGIN
+    );
+
+    let mut ledger_truncator = LedgerTruncator::new(
+        ledger.clone(),
+        finality_provider,
+        TEST_TRUNCATION_TIME_INTERVAL,
+        0,
+    );
+
+    ledger_truncator.start();
+    advance_intervals(4).await;
+
+    ledger_truncator.stop();
+    ledger_truncator.join().await.unwrap();
+
+    let cleanup_slot = ledger.get_lowest_cleanup_slot();
+    assert_ne!(cleanup_slot, 0);
+    assert!(cleanup_slot < FINAL_SLOT - MARGIN);
     verify_transactions_state(
         &ledger,
         0,
         &signatures[..(cleanup_slot + 1) as usize],
         false,
     );
+    // Slots in the margin are kept even though they are final
     verify_transactions_state(
         &ledger,
         cleanup_slot + 1,
@@ -179,6 +364,260 @@ async fn test_truncator_non_empty_ledger() {
     );
 }
 
+// Tests that after truncation every column agrees on which slots exist
+#[tokio::test(start_paused = true)]
+async fn test_truncator_columns_consistent() {
+    const FINAL_SLOT: u64 = 80;
+    const NUM_SLOTS: u64 = FINAL_SLOT + 20;
+
+    let ledger = Arc::new(setup());
+    let signatures = (0..NUM_SLOTS)
+        .map(|i| {
+            let (_, signature) = write_dummy_transaction(&ledger, i, 0);
+            ledger
+                .write_transaction_memos(&signature, i, format!("memo {i}"))
+                .unwrap();
+            ledger.write_block(i, 0, Hash::new_unique()).unwrap();
+            signature
+        })
+        .collect::<Vec<_>>();
+
+    let finality_provider = Arc::new(TestFinalityProvider {
+        latest_final_slot: FINAL_SLOT.into(),
+    });
+    let mut ledger_truncator = LedgerTruncator::new(
+        ledger.clone(),
+        finality_provider,
+        TEST_TRUNCATION_TIME_INTERVAL,
+        0,
+    );
+
+    ledger_truncator.start();
+    advance_intervals(1).await;
+    ledger_truncator.stop();
+    ledger_truncator.join().await.unwrap();
+
+    let cleanup_slot = ledger.get_lowest_cleanup_slot();
+    assert_ne!(cleanup_slot, 0);
+    verify_columns_consistent(&ledger, &signatures, cleanup_slot);
+
+    let num_remaining = (NUM_SLOTS - cleanup_slot - 1) as i64;
+    assert_eq!(ledger.count_block_times().unwrap(), num_remaining);
+    assert_eq!(ledger.count_blockhashes().unwrap(), num_remaining);
+    assert_eq!(ledger.count_slot_signatures().unwrap(), num_remaining);
+    assert_eq!(ledger.count_transactions().unwrap(), num_remaining);
+    assert_eq!(ledger.count_transaction_status().unwrap(), num_remaining);
+    assert_eq!(ledger.count_transaction_memos().unwrap(), num_remaining);
+}
+
+// Tests that draining waits for the in-flight cycle so that a flush
+// right after observes fully purged slots only
+#[tokio::test(start_paused = true)]
+async fn test_truncator_stop_and_drain() {
+    const FINAL_SLOT: u64 = 80;
+
+    let ledger = Arc::new(setup());
+    let signatures = (0..FINAL_SLOT + 20)
+        .map(|i| {
+            let (_, signature) = write_dummy_transaction(&ledger, i, 0);
+            ledger
+                .write_transaction_memos(&signature, i, format!("memo {i}"))
+                .unwrap();
+            ledger.write_block(i, 0, Hash::new_unique()).unwrap();
+            signature
+        })
+        .collect::<Vec<_>>();
+
+    let finality_provider = Arc::new(TestFinalityProvider {
+        latest_final_slot: FINAL_SLOT.into(),
+    });
+    let mut ledger_truncator = LedgerTruncator::new(
+        ledger.clone(),
+        finality_provider,
+        Duration::from_secs(60),
+        0,
+    )
+    .with_tombstone_compaction(true);
+
+    // The first cycle starts right away and yields while compacting,
+    // draining once it started purging
+    ledger_truncator.start();
+    while ledger.get_lowest_cleanup_slot() == 0 {
+        tokio::task::yield_now().await;
+    }
+    let summary = ledger_truncator.stop_and_drain().await.unwrap();
+    ledger.flush().unwrap();
+
+    assert_eq!(summary.total_cycles, 1);
+    assert_eq!(summary.total_errors, 0);
+    let cleanup_slot = ledger.get_lowest_cleanup_slot();
+    assert_ne!(cleanup_slot, 0);
+    assert_eq!(summary.total_slots_purged, cleanup_slot + 1);
+    verify_columns_consistent(&ledger, &signatures, cleanup_slot);
+
+    // Already drained
+    let summary = ledger_truncator.stop_and_drain().await.unwrap();
+    assert_eq!(summary.total_cycles, 0);
+}
+
+// Tests that a backlog is purged right away instead of one cycle per interval
+#[tokio::test(start_paused = true)]
+async fn test_truncator_catch_up_on_start() {
+    const FINAL_SLOT: u64 = 80;
+
+    let ledger = Arc::new(setup());
+    let signatures = (0..FINAL_SLOT + 20)
+        .map(|i| {
+            let (_, signature) = write_dummy_transaction(&ledger, i, 0);
+            ledger
+                .write_transaction_memos(&signature, i, format!("memo {i}"))
+                .unwrap();
+            ledger.write_block(i, 0, Hash::new_unique()).unwrap();
+            signature
+        })
+        .collect::<Vec<_>>();
+
+    let finality_provider = Arc::new(TestFinalityProvider {
+        latest_final_slot: FINAL_SLOT.into(),
+    });
+    let mut ledger_truncator = LedgerTruncator::new(
+        ledger.clone(),
+        finality_provider,
+        Duration::from_secs(60),
+        0,
+    )
+    .with_catch_up_on_start(true);
+
+    // The clock doesn't move, so the backlog has to be purged without
+    // waiting for the next interval
+    ledger_truncator.start();
+    while ledger.get_lowest_cleanup_slot() < FINAL_SLOT - 1 {
+        tokio::task::yield_now().await;
+    }
+    let summary = ledger_truncator.stop_and_drain().await.unwrap();
+
+    // Several cycles ran within a single interval
+    assert!(summary.total_cycles > 1);
+    assert_eq!(summary.total_errors, 0);
+    assert_eq!(summary.total_slots_purged, FINAL_SLOT);
+    verify_columns_consistent(&ledger, &signatures, FINAL_SLOT - 1);
+}
+
+/// Keeps blocks for `blocks_tail` more slots than transactions
+struct BlocksTailRetentionPolicy {
+    blocks_tail: u64,
+}
+
+impl RetentionPolicy for BlocksTailRetentionPolicy {
+    fn effective_purge_slot(
+        &self,
+        group: ColumnGroup,
+        purgeable_slot: u64,
+    ) -> u64 {
+        match group {
+            ColumnGroup::Blocks => {
+                purgeable_slot.saturating_sub(self.blocks_tail)
+            }
+            ColumnGroup::Transactions => purgeable_slot,
+        }
+    }
+}
+
+// Tests that column groups with longer retention keep older slots
+#[tokio::test(start_paused = true)]
+async fn test_truncator_with_retention_policy() {
+    const FINAL_SLOT: u64 = 80;
+    const BLOCKS_TAIL: u64 = 75;
+    // Highest slot blocks can be truncated up to
+    const BLOCKS_PURGE_SLOT: u64 = FINAL_SLOT - 1 - BLOCKS_TAIL;
+
+    let ledger = Arc::new(setup());
+    let signatures = (0..FINAL_SLOT + 20)
+        .map(|i| {
+            let (_, signature) = write_dummy_transaction(&ledger, i, 0);
+            ledger.write_block(i, 0, Hash::new_unique()).unwrap();
+            signature
+        })
+        .collect::<Vec<_>>();
+
+    let finality_provider = Arc::new(TestFinalityProvider {
+        latest_final_slot: FINAL_SLOT.into(),
+    });
+    let mut ledger_truncator = LedgerTruncator::new(
+        ledger.clone(),
+        finality_provider,
+        TEST_TRUNCATION_TIME_INTERVAL,
+        0,
+    )
+    .with_retention_policy(Arc::new(BlocksTailRetentionPolicy {
+        blocks_tail: BLOCKS_TAIL,
+    }));
+
+    ledger_truncator.start();
+    advance_intervals(1).await;
+    ledger_truncator.stop();
+    ledger_truncator.join().await.unwrap();
+
+    // Slots are only fully purged up to the longest retention
+    assert_eq!(ledger.get_lowest_cleanup_slot(), BLOCKS_PURGE_SLOT);
+    verify_transactions_state(
+        &ledger,
+        0,
+        &signatures[..=BLOCKS_PURGE_SLOT as usize],
+        false,
+    );
+
+    // Transactions right after it are purged, while their blocks are kept
+    let slot = BLOCKS_PURGE_SLOT + 1;
+    verify_transactions_state(
+        &ledger,
+        slot,
+        &signatures[slot as usize..=slot as usize],
+        false,
+    );
+    // Starting after the first kept block, since a block also reads the
+    // blockhash of its parent
+    for slot in BLOCKS_PURGE_SLOT + 2..FINAL_SLOT + 20 {
+        assert!(ledger.get_block(slot).unwrap().is_some(), "slot {slot}");
+    }
+
+    // Transactions beyond the truncated range are untouched
+    verify_transactions_state(
+        &ledger,
+        FINAL_SLOT / 2,
+        &signatures[(FINAL_SLOT / 2) as usize..],
+        true,
+    );
+}
+
+// Tests that the truncator stops once an external token is cancelled
+#[tokio::test(start_paused = true)]
+async fn test_truncator_with_cancellation() {
+    let ledger = Arc::new(setup());
+    let finality_provider = Arc::new(TestFinalityProvider {
+        latest_final_slot: 0.into(),
+    });
+    let cancellation_token = CancellationToken::new();
+
+    let mut ledger_truncator = LedgerTruncator::new(
+        ledger,
+        finality_provider,
+        TEST_TRUNCATION_TIME_INTERVAL,
+        0,
+    )
+    .with_cancellation(cancellation_token.clone());
+
+    ledger_truncator.start();
+    settle().await;
+    tokio::time::advance(TEST_TRUNCATION_TIME_INTERVAL / 2).await;
+    cancellation_token.cancel();
+    advance_intervals(5).await;
+
+    // No cycles ran after the cancellation
+    let summary = ledger_truncator.join().await.unwrap();
+    assert_eq!(summary.total_cycles, 1);
+}
+
 async fn transaction_spammer(
     ledger: Arc<Ledger>,
     finality_provider: Arc<TestFinalityProvider>,
@@ -205,7 +644,7 @@ async fn transaction_spammer(
 }
 
 // Tests if ledger truncated correctly during tx spamming with finality slot increments
-#[tokio::test]
+#[tokio::test(start_paused = true)]
 async fn test_truncator_with_tx_spammer() {
     let ledger = Arc::new(setup());
     let finality_provider = Arc::new(TestFinalityProvider {
@@ -227,16 +666,16 @@ async fn test_truncator_with_tx_spammer() {
         20,
     ));
 
-    // Sleep some time
-    tokio::time::sleep(Duration::from_secs(3)).await;
+    // The spammer is done after 10 iterations of 10ms, the remaining
+    // intervals let the truncator catch up with the final slot
+    advance_intervals(60).await;
 
     let signatures_result = handle.await;
     assert!(signatures_result.is_ok());
     let signatures = signatures_result.unwrap();
 
-    // Stop truncator assuming that complete after sleep
-    ledger_truncator.stop();
-    assert!(ledger_truncator.join().await.is_ok());
+    // Drain the in-flight cycle before flushing
+    ledger_truncator.stop_and_drain().await.unwrap();
 
     assert!(ledger.flush().is_ok());
 